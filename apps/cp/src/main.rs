@@ -11,7 +11,7 @@ use shared_lib::rust_official::cchar::c_char;
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
     if args.len() < 3 {
         println!("Usage: {} <src> <dest>", args.get(0));
         exit(1);
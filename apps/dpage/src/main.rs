@@ -13,7 +13,7 @@ use shared_lib::{demand_page, println};
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
     let (filename, ch) = if args.len() < 3 {
         ("/memmap", b'\n')
     } else {
@@ -3,6 +3,7 @@
 #![feature(format_args_nl)]
 
 use core::arch::asm;
+use core::ffi::c_void;
 use core::panic::PanicInfo;
 use shared_lib::args::Args;
 use shared_lib::newlib_support::exit;
@@ -11,7 +12,7 @@ use shared_lib::rust_official::cchar::c_char;
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
     let cmd = if args.len() >= 2 { args.get(1) } else { "hlt" };
 
     match cmd {
@@ -28,12 +29,71 @@ pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
             let z = 0;
             println!("100/{} = {}", z, 100 / z)
         }
+        "syscall" => pass_bad_pointers(),
         _ => {}
     }
 
     exit(0);
 }
 
+#[repr(C)]
+struct SyscallResult {
+    value: u64,
+    error: i32,
+}
+
+// declared here to pass raw addresses that the safe wrappers of shared_lib never produce
+extern "C" {
+    fn SyscallLogString(level: i64, s: *const c_char) -> SyscallResult;
+    fn SyscallPutString(fd: i32, buf: *const c_void, count: usize) -> SyscallResult;
+    fn SyscallOpenWindow(w: i32, h: i32, x: i32, y: i32, title: *const c_char) -> SyscallResult;
+    fn SyscallWinWriteString(
+        layer_id_flags: u64,
+        x: i32,
+        y: i32,
+        color: u32,
+        s: *const c_char,
+    ) -> SyscallResult;
+    fn SyscallReadEvent(events: *const c_void, len: usize) -> SyscallResult;
+    fn SyscallOpenFile(path: *const c_char, flags: i32) -> SyscallResult;
+    fn SyscallReadFile(fd: i32, buf: *const c_void, count: usize) -> SyscallResult;
+    fn SyscallMapFile(fd: i32, file_size: *mut usize, flags: i32) -> SyscallResult;
+}
+
+/// Passes addresses the kernel must reject with EFAULT instead of crashing.
+fn pass_bad_pointers() {
+    let bad_addrs: [u64; 5] = [
+        0,                     // null
+        0x100,                 // kernel
+        0x8000_0000_0000_0000, // non-canonical
+        0xffff_8000_ffff_0000, // unmapped user page
+        0xffff_ffff_ffff_fff0, // wraps around the end of the address space
+    ];
+
+    for &addr in bad_addrs.iter() {
+        let p = addr as *const c_char;
+        let buf = addr as *const c_void;
+        let results = unsafe {
+            [
+                ("LogString", SyscallLogString(3, p)),
+                ("PutString", SyscallPutString(1, buf, 32)),
+                ("OpenWindow", SyscallOpenWindow(100, 50, 10, 10, p)),
+                ("WinWriteString", SyscallWinWriteString(0, 0, 0, 0, p)),
+                ("ReadEvent", SyscallReadEvent(buf, 1)),
+                ("OpenFile", SyscallOpenFile(p, 0)),
+                ("ReadFile", SyscallReadFile(0, buf, 32)),
+                ("MapFile", SyscallMapFile(0, addr as *mut usize, 0)),
+            ]
+        };
+        for (name, result) in results.iter() {
+            println!(
+                "{}({:#x}): value={} errno={}",
+                name, addr, result.value, result.error
+            );
+        }
+    }
+}
+
 #[panic_handler]
 fn panic(_info: &PanicInfo) -> ! {
     loop {
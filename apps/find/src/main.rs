@@ -0,0 +1,66 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use core::arch::asm;
+use core::panic::PanicInfo;
+use shared_lib::args::Args;
+use shared_lib::dir::Dir;
+use shared_lib::newlib_support::exit;
+use shared_lib::println;
+use shared_lib::rust_official::cchar::c_char;
+
+/// Usage: find [dir] [name]
+///
+/// Prints paths under `dir` recursively. Only paths whose file name contains `name` are printed if given.
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
+    let args = unsafe { Args::new(argc, argv) };
+    let dir = if args.len() >= 2 { args.get(1) } else { "/" };
+    let pattern = if args.len() >= 3 { args.get(2) } else { "" };
+
+    if let Err(e) = Dir::open(dir) {
+        println!("failed to open {}: {}", dir, e.strerror());
+        exit(1);
+    }
+
+    let dir = dir.trim_end_matches('/');
+    walk(String::from(dir), pattern);
+    exit(0);
+}
+
+fn walk(dir_path: String, pattern: &str) {
+    let dir = match Dir::open(if dir_path.is_empty() { "/" } else { &dir_path }) {
+        Ok(d) => d,
+        Err(_) => return,
+    };
+
+    for entry in dir {
+        let name = match entry.name() {
+            Ok(n) => n,
+            Err(_) => continue,
+        };
+        if name == "." || name == ".." {
+            continue;
+        }
+
+        let path = format!("{}/{}", dir_path, name);
+        if name.contains(pattern) {
+            println!("{}", path);
+        }
+        if entry.is_directory() {
+            walk(path, pattern);
+        }
+    }
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {
+        unsafe { asm!("hlt") }
+    }
+}
@@ -2,60 +2,132 @@
 #![no_main]
 #![feature(format_args_nl)]
 
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::arch::asm;
 use core::panic::PanicInfo;
-use safe_regex::{regex, Matcher0};
 use shared_lib::args::Args;
-use shared_lib::file::{buf_to_str, open_file, read_string, OpenMode};
+use shared_lib::file::{open_file, read_line, OpenMode};
 use shared_lib::newlib_support::exit;
+use shared_lib::regex::Regex;
 use shared_lib::rust_official::cchar::c_char;
 use shared_lib::{print, println};
 
+#[derive(Default)]
+struct Options {
+    ignore_case: bool,
+    invert: bool,
+    line_number: bool,
+    count: bool,
+}
+
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
-    if args.len() < 2 {
-        println!("Usage: {} <pattern> <file>\n", args.get(0));
-        exit(1);
+    let args = unsafe { Args::new(argc, argv) };
+    let mut options = Options::default();
+    let mut i = 1;
+    while i < args.len() && args.get(i).starts_with('-') && args.get(i).len() > 1 {
+        for c in args.get(i).chars().skip(1) {
+            match c {
+                'i' => options.ignore_case = true,
+                'v' => options.invert = true,
+                'n' => options.line_number = true,
+                'c' => options.count = true,
+                _ => {
+                    println!("grep: unknown option -{}", c);
+                    usage(args.get(0));
+                }
+            }
+        }
+        i += 1;
+    }
+    if i >= args.len() {
+        usage(args.get(0));
     }
-    let path = if args.len() < 3 {
-        "@stdin"
+
+    let pattern = args.get(i);
+    let regex = if options.ignore_case {
+        Regex::new_case_insensitive(pattern)
     } else {
-        args.get(2)
+        Regex::new(pattern)
     };
-
-    // Because implementing regexp is not the essence of OS, supported patterns are:
-    match args.get(1) {
-        "3FE.D000" => grep(path, regex!(br".*3FE.D000.*")),
-        "i.t" => grep(path, regex!(br".*i.t.*")),
-        "Conv" => grep(path, regex!(br".*Conv.*")),
-        "3E" => grep(path, regex!(br".*3E.*")),
-        "N" => grep(path, regex!(br".*N.*")),
-        "^3" => grep(path, regex!(br"3.*")),
-        "2nd" => grep(path, regex!(br".*2nd.*")),
-        p => {
-            println!("Unsupported pattern: {}", p);
-            exit(1);
+    let regex = match regex {
+        Ok(regex) => regex,
+        Err(e) => {
+            println!("grep: invalid pattern: {}", e);
+            exit(2);
         }
     };
 
-    exit(0);
+    let mut paths = (i + 1..args.len()).map(|i| args.get(i)).collect::<Vec<_>>();
+    if paths.is_empty() {
+        paths.push("@stdin");
+    }
+    let show_path = paths.len() > 1;
+
+    let mut selected = false;
+    let mut failed = false;
+    for path in paths {
+        match grep(path, &regex, &options, show_path) {
+            Some(count) => selected |= count > 0,
+            None => failed = true,
+        }
+    }
+
+    if failed {
+        exit(2);
+    }
+    exit(if selected { 0 } else { 1 });
+}
+
+fn usage(name: &str) -> ! {
+    println!("Usage: {} [-i] [-v] [-n] [-c] <pattern> [file...]", name);
+    exit(2);
 }
 
-fn grep<F: Fn(&[u8]) -> Option<()>>(path: &str, matcher: Matcher0<F>) {
+/// Prints the selected lines of the file, and returns how many lines are selected,
+/// or `None` if the file cannot be opened.
+fn grep(path: &str, regex: &Regex, options: &Options, show_path: bool) -> Option<usize> {
     let fp = open_file(path, OpenMode::R);
     if fp.is_null() {
-        println!("failed to open {}", path);
-        exit(1);
+        println!("grep: failed to open {}", path);
+        return None;
+    }
+
+    let mut count = 0;
+    let mut line = Vec::new();
+    let mut line_number = 0;
+    while read_line(fp, &mut line) {
+        line_number += 1;
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        let text = String::from_utf8_lossy(&line);
+        if regex.is_match(&text) == options.invert {
+            continue;
+        }
+        count += 1;
+        if options.count {
+            continue;
+        }
+        if show_path {
+            print!("{}:", path);
+        }
+        if options.line_number {
+            print!("{}:", line_number);
+        }
+        println!("{}", text);
     }
 
-    let mut line = [0_u8; 256];
-    while !read_string(fp, line.as_mut()).is_null() {
-        if matcher.is_match(line.as_slice()) {
-            let str = buf_to_str(&line).unwrap();
-            print!("{}", str);
+    if options.count {
+        if show_path {
+            print!("{}:", path);
         }
+        println!("{}", count);
     }
+    Some(count)
 }
 
 #[panic_handler]
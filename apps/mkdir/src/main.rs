@@ -0,0 +1,37 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::arch::asm;
+use core::panic::PanicInfo;
+use shared_lib::args::Args;
+use shared_lib::fs::create_dir;
+use shared_lib::newlib_support::exit;
+use shared_lib::println;
+use shared_lib::rust_official::cchar::c_char;
+
+/// Usage: mkdir <dir>...
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
+    let args = unsafe { Args::new(argc, argv) };
+    if args.len() < 2 {
+        println!("Usage: {} <dir>...", args.get(0));
+        exit(1);
+    }
+
+    let mut exit_code = 0;
+    for i in 1..args.len() {
+        if let Err(e) = create_dir(args.get(i)) {
+            println!("failed to create {}: {}", args.get(i), e.strerror());
+            exit_code = 1;
+        }
+    }
+    exit(exit_code)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {
+        unsafe { asm!("hlt") }
+    }
+}
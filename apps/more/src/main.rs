@@ -7,10 +7,12 @@ extern crate alloc;
 use alloc::string::ToString;
 use alloc::vec;
 use core::arch::asm;
+use core::cmp;
 use core::panic::PanicInfo;
 use core::str::FromStr;
 use shared_lib::app_event::AppEventType;
 use shared_lib::args::Args;
+use shared_lib::env::getenv;
 use shared_lib::file::{buf_to_str, open_file, read_string, OpenMode};
 use shared_lib::newlib_support::{exit, FILE};
 use shared_lib::rust_official::cchar::c_char;
@@ -18,7 +20,7 @@ use shared_lib::{print, println, read_event};
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let (page_size, input) = convert_args(Args::new(argc, argv));
+    let (page_size, input) = convert_args(unsafe { Args::new(argc, argv) });
 
     let mut lines = vec![];
     let mut line = [0_u8; 256];
@@ -40,7 +42,7 @@ pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
 
 fn convert_args(args: Args) -> (usize, *mut FILE) {
     let (page_size, arg_file) = match convert_to_page_size(&args) {
-        None => (10, 1),
+        None => (default_page_size(), 1),
         Some(page_size) => (page_size, 2),
     };
 
@@ -71,6 +73,13 @@ fn convert_to_page_size(args: &Args) -> Option<usize> {
     usize::from_str(&arg[1..]).ok()
 }
 
+/// Fills the terminal leaving a row for `--more--`.
+fn default_page_size() -> usize {
+    getenv("LINES")
+        .and_then(|lines| usize::from_str(&lines).ok())
+        .map_or(10, |lines| cmp::max(lines, 2) - 1)
+}
+
 fn wait_key() {
     let mut events = [Default::default(); 1];
     loop {
@@ -91,6 +100,7 @@ fn wait_key() {
                     return;
                 }
             }
+            AppEventType::TerminalResize => {}
             _ => println!("unknown event: type = {:?}", events[0].type_),
         }
     }
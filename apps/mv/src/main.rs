@@ -0,0 +1,49 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use core::arch::asm;
+use core::panic::PanicInfo;
+use shared_lib::args::Args;
+use shared_lib::dir::Dir;
+use shared_lib::fs::rename;
+use shared_lib::newlib_support::exit;
+use shared_lib::println;
+use shared_lib::rust_official::cchar::c_char;
+
+/// Usage: mv <src> <dest>
+///
+/// `src` is moved into `dest` if `dest` is an existing directory.
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
+    let args = unsafe { Args::new(argc, argv) };
+    if args.len() < 3 {
+        println!("Usage: {} <src> <dest>", args.get(0));
+        exit(1);
+    }
+
+    let src = args.get(1);
+    let dest = if Dir::open(args.get(2)).is_ok() {
+        let name = src.trim_end_matches('/').rsplit('/').next().unwrap_or(src);
+        format!("{}/{}", args.get(2).trim_end_matches('/'), name)
+    } else {
+        String::from(args.get(2))
+    };
+
+    if let Err(e) = rename(src, &dest) {
+        println!("failed to move {} to {}: {}", src, dest, e.strerror());
+        exit(1);
+    }
+    exit(0)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {
+        unsafe { asm!("hlt") }
+    }
+}
@@ -12,7 +12,7 @@ use shared_lib::{print, println};
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
     let path = if args.len() < 2 {
         "/memmap"
     } else {
@@ -0,0 +1,748 @@
+//! Regular expressions with the syntax of POSIX extended regular expressions,
+//! matched by a Pike VM in time linear to the text.
+//!
+//! Supported syntax:
+//! - `.` matching any character but a newline, and literal characters escaped by `\` if needed
+//! - bracket expressions like `[a-z_]`, `[^0-9]` and `[[:alpha:]]`
+//! - `\d`, `\w`, `\s` and their negations `\D`, `\W`, `\S`
+//! - anchors `^` and `$`, and word boundaries `\b` and `\B`
+//! - alternation `|` and groups `( )`
+//! - repetitions `*`, `+`, `?`, `{n}`, `{n,}` and `{n,m}`
+//!
+//! It only needs `alloc`, so that its tests run on the host unlike `shared_lib`.
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt;
+use core::mem;
+
+/// Maximum number of instructions a pattern is compiled into, which bounds repetitions like `a{1000}`.
+const MAX_INSTRUCTIONS: usize = 10000;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Error {
+    UnmatchedParen,
+    UnmatchedBracket,
+    NothingToRepeat,
+    InvalidRepeat,
+    InvalidClass,
+    TrailingBackslash,
+    TooLarge,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Error::UnmatchedParen => "unmatched ( or )",
+            Error::UnmatchedBracket => "unmatched [",
+            Error::NothingToRepeat => "nothing to repeat",
+            Error::InvalidRepeat => "invalid repetition count",
+            Error::InvalidClass => "invalid character class",
+            Error::TrailingBackslash => "trailing backslash",
+            Error::TooLarge => "pattern too large",
+        };
+        f.write_str(s)
+    }
+}
+
+pub struct Regex {
+    insts: Vec<Inst>,
+    ignore_case: bool,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        Self::compile(pattern, false)
+    }
+
+    /// Compiles `pattern` so that letters match regardless of their case.
+    pub fn new_case_insensitive(pattern: &str) -> Result<Self, Error> {
+        Self::compile(pattern, true)
+    }
+
+    fn compile(pattern: &str, ignore_case: bool) -> Result<Self, Error> {
+        let mut parser = Parser {
+            chars: pattern.chars().collect(),
+            pos: 0,
+        };
+        let node = parser.parse_alternation()?;
+        if parser.pos < parser.chars.len() {
+            // only an unmatched ')' stops the parser before the end
+            return Err(Error::UnmatchedParen);
+        }
+
+        let mut compiler = Compiler { insts: Vec::new() };
+        compiler.emit(&node)?;
+        compiler.push(Inst::Match)?;
+        Ok(Self {
+            insts: compiler.insts,
+            ignore_case,
+        })
+    }
+
+    /// Returns true if `text` contains a match of the pattern.
+    pub fn is_match(&self, text: &str) -> bool {
+        let mut current = Threads::new(self.insts.len());
+        let mut next = Threads::new(self.insts.len());
+        let mut stack = Vec::new();
+
+        let mut chars = text.chars();
+        let mut prev = None;
+        let mut cur = chars.next();
+        loop {
+            // starting a thread at every position finds a match anywhere in the text
+            if self.add_thread(&mut current, &mut stack, 0, prev, cur) {
+                return true;
+            }
+            let c = match cur {
+                Some(c) => c,
+                None => return false,
+            };
+            let following = chars.next();
+
+            next.clear();
+            for i in 0..current.pcs.len() {
+                let pc = current.pcs[i];
+                if self.step(pc, c)
+                    && self.add_thread(&mut next, &mut stack, pc + 1, Some(c), following)
+                {
+                    return true;
+                }
+            }
+            mem::swap(&mut current, &mut next);
+            prev = Some(c);
+            cur = following;
+        }
+    }
+
+    /// Adds the thread at `pc` and the threads reachable from it without consuming a character.
+    /// Returns true if one of them matches.
+    fn add_thread(
+        &self,
+        threads: &mut Threads,
+        stack: &mut Vec<usize>,
+        pc: usize,
+        prev: Option<char>,
+        cur: Option<char>,
+    ) -> bool {
+        stack.clear();
+        stack.push(pc);
+        while let Some(pc) = stack.pop() {
+            if !threads.insert(pc) {
+                continue;
+            }
+            match &self.insts[pc] {
+                Inst::Jump(to) => stack.push(*to),
+                Inst::Split(first, second) => {
+                    stack.push(*second);
+                    stack.push(*first);
+                }
+                Inst::Assert(assertion) => {
+                    if assertion.holds(prev, cur) {
+                        stack.push(pc + 1);
+                    }
+                }
+                Inst::Match => return true,
+                Inst::Char(_) | Inst::Any | Inst::Class(_) => {}
+            }
+        }
+        false
+    }
+
+    /// Returns true if the instruction at `pc` consumes `c`.
+    fn step(&self, pc: usize, c: char) -> bool {
+        match &self.insts[pc] {
+            Inst::Char(expected) => {
+                *expected == c || (self.ignore_case && fold_case(*expected) == fold_case(c))
+            }
+            Inst::Any => c != '\n',
+            Inst::Class(class) => {
+                class.contains(c)
+                    || (self.ignore_case
+                        && (class.contains(fold_case(c)) || class.contains(upper_case(c))))
+            }
+            _ => false,
+        }
+    }
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+fn upper_case(c: char) -> char {
+    c.to_uppercase().next().unwrap_or(c)
+}
+
+/// Program counters of threads at a position of the text, without duplicates.
+struct Threads {
+    pcs: Vec<usize>,
+    added: Vec<bool>,
+}
+
+impl Threads {
+    fn new(num_insts: usize) -> Self {
+        Self {
+            pcs: Vec::with_capacity(num_insts),
+            added: vec![false; num_insts],
+        }
+    }
+
+    fn insert(&mut self, pc: usize) -> bool {
+        if self.added[pc] {
+            return false;
+        }
+        self.added[pc] = true;
+        self.pcs.push(pc);
+        true
+    }
+
+    fn clear(&mut self) {
+        for &pc in &self.pcs {
+            self.added[pc] = false;
+        }
+        self.pcs.clear();
+    }
+}
+
+enum Inst {
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    /// Continues at both, preferring the first.
+    Split(usize, usize),
+    Jump(usize),
+    Match,
+}
+
+#[derive(Copy, Clone)]
+enum Assertion {
+    Start,
+    End,
+    WordBoundary,
+    NotWordBoundary,
+}
+
+impl Assertion {
+    fn holds(&self, prev: Option<char>, cur: Option<char>) -> bool {
+        let is_word = |c: Option<char>| c.map_or(false, |c| c.is_alphanumeric() || c == '_');
+        match self {
+            Assertion::Start => prev.is_none(),
+            Assertion::End => cur.is_none(),
+            Assertion::WordBoundary => is_word(prev) != is_word(cur),
+            Assertion::NotWordBoundary => is_word(prev) == is_word(cur),
+        }
+    }
+}
+
+/// A set of characters as sorted ranges of code points.
+#[derive(Clone)]
+struct Class {
+    ranges: Vec<(u32, u32)>,
+}
+
+impl Class {
+    fn new(mut ranges: Vec<(u32, u32)>) -> Self {
+        ranges.sort_unstable();
+        let mut merged: Vec<(u32, u32)> = Vec::with_capacity(ranges.len());
+        for (lo, hi) in ranges {
+            match merged.last_mut() {
+                Some(last) if lo <= last.1.saturating_add(1) => last.1 = last.1.max(hi),
+                _ => merged.push((lo, hi)),
+            }
+        }
+        Self { ranges: merged }
+    }
+
+    fn negate(&self) -> Self {
+        let mut ranges = Vec::new();
+        let mut next = 0;
+        for &(lo, hi) in &self.ranges {
+            if next < lo {
+                ranges.push((next, lo - 1));
+            }
+            next = hi + 1;
+        }
+        if next <= char::MAX as u32 {
+            ranges.push((next, char::MAX as u32));
+        }
+        Self { ranges }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        let c = c as u32;
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi)
+    }
+}
+
+fn ranges(pairs: &[(char, char)]) -> Vec<(u32, u32)> {
+    pairs
+        .iter()
+        .map(|&(lo, hi)| (lo as u32, hi as u32))
+        .collect()
+}
+
+/// Returns the ranges of a POSIX character class name like `alpha`.
+fn named_class(name: &str) -> Option<Vec<(u32, u32)>> {
+    let pairs: &[(char, char)] = match name {
+        "alpha" => &[('A', 'Z'), ('a', 'z')],
+        "digit" => &[('0', '9')],
+        "alnum" => &[('0', '9'), ('A', 'Z'), ('a', 'z')],
+        "upper" => &[('A', 'Z')],
+        "lower" => &[('a', 'z')],
+        "space" => &[(' ', ' '), ('\t', '\r')],
+        "blank" => &[(' ', ' '), ('\t', '\t')],
+        "xdigit" => &[('0', '9'), ('A', 'F'), ('a', 'f')],
+        "punct" => &[('!', '/'), (':', '@'), ('[', '`'), ('{', '~')],
+        "word" => &[('0', '9'), ('A', 'Z'), ('_', '_'), ('a', 'z')],
+        _ => return None,
+    };
+    Some(ranges(pairs))
+}
+
+/// Returns the class of an escape like `\d`, or None if `c` is not a class escape.
+fn escaped_class(c: char) -> Option<Class> {
+    let name = match c.to_ascii_lowercase() {
+        'd' => "digit",
+        'w' => "word",
+        's' => "space",
+        _ => return None,
+    };
+    let class = Class::new(named_class(name).unwrap());
+    Some(if c.is_ascii_uppercase() {
+        class.negate()
+    } else {
+        class
+    })
+}
+
+fn escaped_char(c: char) -> char {
+    match c {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        'f' => '\x0c',
+        'v' => '\x0b',
+        _ => c,
+    }
+}
+
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Assert(Assertion),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+    },
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        Some(c)
+    }
+
+    fn parse_alternation(&mut self) -> Result<Node, Error> {
+        let mut branches = vec![self.parse_concat()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.parse_concat()?);
+        }
+        Ok(if branches.len() == 1 {
+            branches.pop().unwrap()
+        } else {
+            Node::Alternate(branches)
+        })
+    }
+
+    fn parse_concat(&mut self) -> Result<Node, Error> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.parse_atom()?;
+            nodes.push(self.parse_repetitions(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.pop().unwrap(),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn parse_repetitions(&mut self, mut node: Node) -> Result<Node, Error> {
+        loop {
+            let (min, max) = match self.peek() {
+                Some('*') => self.operator((0, None)),
+                Some('+') => self.operator((1, None)),
+                Some('?') => self.operator((0, Some(1))),
+                Some('{') => match self.parse_bounds()? {
+                    Some(bounds) => bounds,
+                    // a brace not starting a repetition count is a literal
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            if matches!(node, Node::Assert(_) | Node::Empty) {
+                return Err(Error::NothingToRepeat);
+            }
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+            };
+        }
+    }
+
+    /// Consumes a repetition operator and returns its bounds.
+    fn operator(&mut self, bounds: (u32, Option<u32>)) -> (u32, Option<u32>) {
+        self.pos += 1;
+        bounds
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}` at the current position.
+    /// Returns None without moving if the brace does not start them.
+    fn parse_bounds(&mut self) -> Result<Option<(u32, Option<u32>)>, Error> {
+        let start = self.pos;
+        self.pos += 1;
+        let min = self.parse_number();
+        let max = if self.peek() == Some(',') {
+            self.pos += 1;
+            self.parse_number()
+        } else {
+            min
+        };
+        if min.is_none() || self.peek() != Some('}') {
+            self.pos = start;
+            return Ok(None);
+        }
+        self.pos += 1;
+        let min = min.unwrap();
+        if max.map_or(false, |max| max < min) {
+            return Err(Error::InvalidRepeat);
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self) -> Option<u32> {
+        let start = self.pos;
+        let mut n: u32 = 0;
+        while let Some(d) = self.peek().and_then(|c| c.to_digit(10)) {
+            n = n.saturating_mul(10).saturating_add(d);
+            self.pos += 1;
+        }
+        (self.pos > start).then(|| n)
+    }
+
+    fn parse_atom(&mut self) -> Result<Node, Error> {
+        match self.next().unwrap() {
+            '(' => {
+                let node = self.parse_alternation()?;
+                if self.next() != Some(')') {
+                    return Err(Error::UnmatchedParen);
+                }
+                Ok(node)
+            }
+            '[' => self.parse_bracket().map(Node::Class),
+            '.' => Ok(Node::Any),
+            '^' => Ok(Node::Assert(Assertion::Start)),
+            '$' => Ok(Node::Assert(Assertion::End)),
+            '*' | '+' | '?' => Err(Error::NothingToRepeat),
+            '\\' => {
+                let c = self.next().ok_or(Error::TrailingBackslash)?;
+                Ok(match c {
+                    'b' => Node::Assert(Assertion::WordBoundary),
+                    'B' => Node::Assert(Assertion::NotWordBoundary),
+                    _ => match escaped_class(c) {
+                        Some(class) => Node::Class(class),
+                        None => Node::Char(escaped_char(c)),
+                    },
+                })
+            }
+            c => Ok(Node::Char(c)),
+        }
+    }
+
+    /// Parses a bracket expression after `[`.
+    fn parse_bracket(&mut self) -> Result<Class, Error> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.pos += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let c = self.next().ok_or(Error::UnmatchedBracket)?;
+            if c == ']' && !first {
+                break;
+            }
+            first = false;
+
+            let lo = match c {
+                '[' if self.peek() == Some(':') => {
+                    let rest: Vec<char> = self.chars[self.pos + 1..].to_vec();
+                    let len = rest
+                        .windows(2)
+                        .position(|w| w == [':', ']'])
+                        .ok_or(Error::InvalidClass)?;
+                    let name: String = rest[..len].iter().collect();
+                    ranges.extend(named_class(&name).ok_or(Error::InvalidClass)?);
+                    self.pos += 1 + len + 2;
+                    continue;
+                }
+                '\\' => {
+                    let c = self.next().ok_or(Error::UnmatchedBracket)?;
+                    if let Some(class) = escaped_class(c) {
+                        ranges.extend(class.ranges);
+                        continue;
+                    }
+                    escaped_char(c)
+                }
+                c => c,
+            };
+
+            // a '-' at the end of the brackets is a literal
+            let is_range = self.peek() == Some('-')
+                && self.chars.get(self.pos + 1).map_or(false, |&c| c != ']');
+            if !is_range {
+                ranges.push((lo as u32, lo as u32));
+                continue;
+            }
+            self.pos += 1;
+            let hi = match self.next().unwrap() {
+                '\\' => escaped_char(self.next().ok_or(Error::UnmatchedBracket)?),
+                c => c,
+            };
+            if hi < lo {
+                return Err(Error::InvalidClass);
+            }
+            ranges.push((lo as u32, hi as u32));
+        }
+
+        let class = Class::new(ranges);
+        Ok(if negated { class.negate() } else { class })
+    }
+}
+
+struct Compiler {
+    insts: Vec<Inst>,
+}
+
+impl Compiler {
+    fn push(&mut self, inst: Inst) -> Result<usize, Error> {
+        if self.insts.len() >= MAX_INSTRUCTIONS {
+            return Err(Error::TooLarge);
+        }
+        self.insts.push(inst);
+        Ok(self.insts.len() - 1)
+    }
+
+    /// Sets where the `Split` or `Jump` at `pc` continues.
+    fn patch(&mut self, pc: usize, to: usize) {
+        match &mut self.insts[pc] {
+            Inst::Jump(x) => *x = to,
+            Inst::Split(_, second) => *second = to,
+            _ => unreachable!(),
+        }
+    }
+
+    fn emit(&mut self, node: &Node) -> Result<(), Error> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c) => {
+                self.push(Inst::Char(*c))?;
+            }
+            Node::Any => {
+                self.push(Inst::Any)?;
+            }
+            Node::Class(class) => {
+                self.push(Inst::Class(class.clone()))?;
+            }
+            Node::Assert(assertion) => {
+                self.push(Inst::Assert(*assertion))?;
+            }
+            Node::Concat(nodes) => {
+                for node in nodes {
+                    self.emit(node)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 == branches.len() {
+                        self.emit(branch)?;
+                        break;
+                    }
+                    let split = self.insts.len();
+                    self.push(Inst::Split(split + 1, 0))?;
+                    self.emit(branch)?;
+                    jumps.push(self.push(Inst::Jump(0))?);
+                    let next = self.insts.len();
+                    self.patch(split, next);
+                }
+                let end = self.insts.len();
+                for jump in jumps {
+                    self.patch(jump, end);
+                }
+            }
+            Node::Repeat { node, min, max } => {
+                for _ in 0..*min {
+                    self.emit(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.insts.len();
+                        self.push(Inst::Split(split + 1, 0))?;
+                        self.emit(node)?;
+                        self.push(Inst::Jump(split))?;
+                        let end = self.insts.len();
+                        self.patch(split, end);
+                    }
+                    Some(max) => {
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            let split = self.insts.len();
+                            self.push(Inst::Split(split + 1, 0))?;
+                            splits.push(split);
+                            self.emit(node)?;
+                        }
+                        let end = self.insts.len();
+                        for split in splits {
+                            self.patch(split, end);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_match(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    fn error(pattern: &str) -> Error {
+        Regex::new(pattern).err().unwrap()
+    }
+
+    #[test]
+    fn literals_and_any() {
+        assert!(is_match("abc", "xxabcxx"));
+        assert!(!is_match("abc", "abxc"));
+        assert!(is_match("a.c", "abc"));
+        assert!(!is_match("a.c", "a\nc"));
+        assert!(is_match("a\\.c", "a.c"));
+        assert!(!is_match("a\\.c", "abc"));
+        assert!(is_match("", "anything"));
+    }
+
+    #[test]
+    fn classes() {
+        assert!(is_match("[a-c_]x", "_x"));
+        assert!(is_match("[a-c_]x", "bx"));
+        assert!(!is_match("[a-c_]x", "dx"));
+        assert!(is_match("[^0-9]", "a"));
+        assert!(!is_match("^[^0-9]+$", "a1"));
+        assert!(is_match("[]a]", "]"));
+        assert!(is_match("[a-]", "-"));
+        assert!(is_match("^[[:alpha:]]+$", "Hello"));
+        assert!(!is_match("[[:digit:]]", "abc"));
+        assert!(is_match("\\d\\s\\w", "1 a"));
+        assert!(!is_match("\\D", "123"));
+        assert!(is_match("[\\d.]", "."));
+    }
+
+    #[test]
+    fn anchors_and_word_boundaries() {
+        assert!(is_match("^ab", "abc"));
+        assert!(!is_match("^ab", "cab"));
+        assert!(is_match("ab$", "cab"));
+        assert!(!is_match("ab$", "abc"));
+        assert!(is_match("^$", ""));
+        assert!(is_match("\\bcat\\b", "a cat!"));
+        assert!(!is_match("\\bcat\\b", "concatenate"));
+        assert!(is_match("\\Bcat", "concatenate"));
+    }
+
+    #[test]
+    fn alternation_and_groups() {
+        assert!(is_match("cat|dog", "hotdog"));
+        assert!(!is_match("cat|dog", "cow"));
+        assert!(is_match("^(ab|cd)+$", "abcdab"));
+        assert!(!is_match("^(ab|cd)+$", "abc"));
+        assert!(is_match("a(|b)c", "ac"));
+        assert!(is_match("a(|b)c", "abc"));
+    }
+
+    #[test]
+    fn repetitions() {
+        assert!(is_match("^ab*c$", "ac"));
+        assert!(is_match("^ab*c$", "abbbc"));
+        assert!(!is_match("^ab+c$", "ac"));
+        assert!(is_match("^ab+c$", "abc"));
+        assert!(is_match("^ab?c$", "ac"));
+        assert!(!is_match("^ab?c$", "abbc"));
+        assert!(is_match("^a{2}$", "aa"));
+        assert!(!is_match("^a{2}$", "aaa"));
+        assert!(is_match("^a{2,}$", "aaaa"));
+        assert!(!is_match("^a{2,}$", "a"));
+        assert!(is_match("^a{1,3}$", "aaa"));
+        assert!(!is_match("^a{1,3}$", "aaaa"));
+        // a brace not starting a count is a literal
+        assert!(is_match("a{x}", "a{x}"));
+        // nested repetitions stay linear
+        assert!(!is_match("^(a*)*b$", &"a".repeat(1000)));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let regex = Regex::new_case_insensitive("^hello [a-c]+$").unwrap();
+        assert!(regex.is_match("HeLLo ABC"));
+        assert!(!regex.is_match("hello abd"));
+        assert!(!is_match("hello", "HELLO"));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(error("(ab"), Error::UnmatchedParen);
+        assert_eq!(error("ab)"), Error::UnmatchedParen);
+        assert_eq!(error("[ab"), Error::UnmatchedBracket);
+        assert_eq!(error("a{3,1}"), Error::InvalidRepeat);
+        assert_eq!(error("*a"), Error::NothingToRepeat);
+        assert_eq!(error("^*"), Error::NothingToRepeat);
+        assert_eq!(error("[z-a]"), Error::InvalidClass);
+        assert_eq!(error("[[:nope:]]"), Error::InvalidClass);
+        assert_eq!(error("ab\\"), Error::TrailingBackslash);
+        assert_eq!(error("a{10000}"), Error::TooLarge);
+    }
+}
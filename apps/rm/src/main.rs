@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+#![feature(format_args_nl)]
+
+use core::arch::asm;
+use core::panic::PanicInfo;
+use shared_lib::args::Args;
+use shared_lib::fs::{remove_dir, remove_file};
+use shared_lib::newlib_support::exit;
+use shared_lib::println;
+use shared_lib::rust_official::cchar::c_char;
+
+const EISDIR: i32 = 21; // Is a directory
+
+/// Usage: rm [-d] <file>...
+///
+/// Removes files. Empty directories are also removed with `-d`.
+#[no_mangle]
+pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
+    let args = unsafe { Args::new(argc, argv) };
+    let remove_dirs = args.len() >= 2 && args.get(1) == "-d";
+    let first = if remove_dirs { 2 } else { 1 };
+    if args.len() <= first {
+        println!("Usage: {} [-d] <file>...", args.get(0));
+        exit(1);
+    }
+
+    let mut exit_code = 0;
+    for i in first..args.len() {
+        let path = args.get(i);
+        let result = match remove_file(path) {
+            Err(e) if remove_dirs && e.error_number() == EISDIR => remove_dir(path),
+            result => result,
+        };
+        if let Err(e) = result {
+            println!("failed to remove {}: {}", path, e.strerror());
+            exit_code = 1;
+        }
+    }
+    exit(exit_code)
+}
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {
+        unsafe { asm!("hlt") }
+    }
+}
@@ -8,7 +8,10 @@ pub struct Args {
 }
 
 impl Args {
-    pub fn new(argc: i32, argv: *const *const c_char) -> Self {
+    /// # Safety
+    ///
+    /// `argv` must be an array of `argc` valid C strings, such as the one passed to `main`.
+    pub unsafe fn new(argc: i32, argv: *const *const c_char) -> Self {
         Self { argc, argv }
     }
 
@@ -0,0 +1,84 @@
+use crate::newlib_support::close;
+use crate::syscall::{SyscallOpenDirectory, SyscallReadDirectory};
+use crate::{ByteBuffer, SyscallError};
+use core::str::Utf8Error;
+
+/// Maximum length of a file name excluding the terminating NUL.
+pub const NAME_MAX: usize = 255;
+
+const ATTR_DIRECTORY: u8 = 0x10;
+
+/// An entry of a directory; the layout is shared with the kernel.
+#[repr(C)]
+pub struct DirEntry {
+    name: [u8; NAME_MAX + 1],
+    size: u32,
+    attr: u8,
+}
+
+impl DirEntry {
+    fn empty() -> Self {
+        Self {
+            name: [0; NAME_MAX + 1],
+            size: 0,
+            attr: 0,
+        }
+    }
+
+    pub fn name(&self) -> Result<&str, Utf8Error> {
+        let len = self.name.iter().position(|&c| c == 0).unwrap_or(NAME_MAX);
+        core::str::from_utf8(&self.name[..len])
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Attribute bits of the FAT directory entry.
+    pub fn attr(&self) -> u8 {
+        self.attr
+    }
+
+    pub fn is_directory(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+}
+
+/// Iterates over entries of a directory. The directory is closed when dropped.
+///
+/// ```ignore
+/// for entry in Dir::open("/apps")? {
+///     println!("{}", entry.name().unwrap());
+/// }
+/// ```
+pub struct Dir {
+    fd: i32,
+}
+
+impl Dir {
+    pub fn open(path: &str) -> Result<Dir, SyscallError> {
+        let mut buf = ByteBuffer::new();
+        buf.write_str_with_nul(path);
+
+        let result = unsafe { SyscallOpenDirectory(buf.as_ptr_c_char()) };
+        result.to_result().map(|fd| Dir { fd: fd as i32 })
+    }
+}
+
+impl Iterator for Dir {
+    type Item = DirEntry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut entry = DirEntry::empty();
+        match unsafe { SyscallReadDirectory(self.fd, &mut entry) }.to_result() {
+            Ok(1) => Some(entry),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for Dir {
+    fn drop(&mut self) {
+        close(self.fd);
+    }
+}
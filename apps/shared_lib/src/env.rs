@@ -0,0 +1,65 @@
+//! Environment variables of the app.
+//!
+//! The terminal passes them as `envp`, which follows the NULL terminating `argv`,
+//! and `_start` loads them from there before `main`.
+
+use crate::c_char;
+use crate::rust_official::cstr::CStr;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+static mut VARS: Vec<(String, String)> = Vec::new();
+
+/// Loads the NULL-terminated array of `NAME=value` strings, replacing the current variables.
+///
+/// # Safety
+///
+/// `envp` must be NULL or a valid NULL-terminated array of C strings.
+pub unsafe fn init(envp: *const *const c_char) {
+    let vars = &mut *core::ptr::addr_of_mut!(VARS);
+    vars.clear();
+    if envp.is_null() {
+        return;
+    }
+    for i in 0.. {
+        let ptr = *envp.add(i);
+        if ptr.is_null() {
+            break;
+        }
+        let env = String::from_utf8_lossy(CStr::from_ptr(ptr).to_bytes());
+        if let Some((name, value)) = env.split_once('=') {
+            vars.push((name.to_string(), value.to_string()));
+        }
+    }
+}
+
+pub fn getenv(name: &str) -> Option<String> {
+    vars()
+        .iter()
+        .find(|(n, _)| n == name)
+        .map(|(_, value)| value.clone())
+}
+
+/// Sets the variable, which is passed to the apps started by `process::spawn`.
+///
+/// Returns false if `name` is empty or contains `=`.
+pub fn setenv(name: &str, value: &str) -> bool {
+    if name.is_empty() || name.contains('=') {
+        return false;
+    }
+    let vars = unsafe { &mut *core::ptr::addr_of_mut!(VARS) };
+    match vars.iter_mut().find(|(n, _)| n == name) {
+        Some((_, v)) => *v = value.to_string(),
+        None => vars.push((name.to_string(), value.to_string())),
+    }
+    true
+}
+
+pub fn unsetenv(name: &str) {
+    unsafe { &mut *core::ptr::addr_of_mut!(VARS) }.retain(|(n, _)| n != name);
+}
+
+/// Returns all the variables in the order they are set.
+pub fn vars() -> &'static [(String, String)] {
+    unsafe { &*core::ptr::addr_of!(VARS) }
+}
@@ -3,6 +3,7 @@ use crate::newlib_support::FILE;
 use crate::rust_official::cstr::CStr;
 use crate::syscall::SyscallMapFile;
 use crate::{c_char, ByteBuffer, SyscallError};
+use alloc::vec::Vec;
 use core::ffi::c_void;
 use core::str::Utf8Error;
 
@@ -35,6 +36,21 @@ pub fn read_string(file: *mut FILE, buf: &mut [u8]) -> *mut c_char {
     unsafe { fgets(b, buf.len() as i32, file) }
 }
 
+/// Reads a line into `line` including the newline, however long it is.
+/// Returns false at the end of the file.
+pub fn read_line(file: *mut FILE, line: &mut Vec<u8>) -> bool {
+    line.clear();
+    let mut buf = [0_u8; 256];
+    while !read_string(file, &mut buf).is_null() {
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        line.extend_from_slice(&buf[..len]);
+        if line.ends_with(b"\n") {
+            break;
+        }
+    }
+    !line.is_empty()
+}
+
 pub fn read_file(file: *mut FILE, buf: &mut [u8]) -> usize {
     let b = buf as *mut _ as *mut c_void;
     unsafe { fread(b, 1, buf.len(), file) }
@@ -0,0 +1,39 @@
+use crate::syscall::{SyscallMakeDirectory, SyscallRemoveDirectory, SyscallRename, SyscallUnlink};
+use crate::{ByteBuffer, SyscallError};
+
+/// Removes a file. Directories are not removed.
+pub fn remove_file(path: &str) -> Result<(), SyscallError> {
+    let mut buf = ByteBuffer::new();
+    buf.write_str_with_nul(path);
+    unsafe { SyscallUnlink(buf.as_ptr_c_char()) }
+        .to_result()
+        .map(|_| ())
+}
+
+pub fn create_dir(path: &str) -> Result<(), SyscallError> {
+    let mut buf = ByteBuffer::new();
+    buf.write_str_with_nul(path);
+    unsafe { SyscallMakeDirectory(buf.as_ptr_c_char()) }
+        .to_result()
+        .map(|_| ())
+}
+
+/// Removes an empty directory.
+pub fn remove_dir(path: &str) -> Result<(), SyscallError> {
+    let mut buf = ByteBuffer::new();
+    buf.write_str_with_nul(path);
+    unsafe { SyscallRemoveDirectory(buf.as_ptr_c_char()) }
+        .to_result()
+        .map(|_| ())
+}
+
+/// Moves `from` to `to`, replacing the file `to` if it exists.
+pub fn rename(from: &str, to: &str) -> Result<(), SyscallError> {
+    let mut from_buf = ByteBuffer::new();
+    from_buf.write_str_with_nul(from);
+    let mut to_buf = ByteBuffer::new();
+    to_buf.write_str_with_nul(to);
+    unsafe { SyscallRename(from_buf.as_ptr_c_char(), to_buf.as_ptr_c_char()) }
+        .to_result()
+        .map(|_| ())
+}
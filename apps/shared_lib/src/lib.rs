@@ -2,6 +2,8 @@
 #![feature(alloc_error_handler)]
 #![no_std]
 
+extern crate alloc;
+
 use crate::app_event::AppEvent;
 use crate::byte_buffer::ByteBuffer;
 use crate::newlib_support::write;
@@ -17,13 +19,35 @@ mod allocator;
 pub mod app_event;
 pub mod args;
 mod byte_buffer;
+pub mod dir;
+pub mod env;
 pub mod file;
+pub mod fs;
 pub mod libc;
 pub mod newlib_support;
+pub mod process;
+pub use regex;
 pub mod rust_official;
 mod syscall;
+pub mod terminal;
 pub mod window;
 
+extern "C" {
+    fn main(argc: i32, argv: *const *const c_char);
+}
+
+/// The entry point of the apps, which loads the environment variables following `argv`
+/// before calling `main`.
+///
+/// # Safety
+///
+/// The terminal passes `argc` and `argv` with the environment variables following it.
+#[no_mangle]
+pub unsafe extern "C" fn _start(argc: i32, argv: *const *const c_char) {
+    env::init(argv.add(argc as usize + 1));
+    main(argc, argv);
+}
+
 pub fn print(s: &str) {
     write(1, s.as_ptr() as *const c_void, s.as_bytes().len());
 }
@@ -1,21 +1,65 @@
 use crate::c_char;
-use crate::syscall::{SyscallDemandPages, SyscallOpenFile, SyscallPutString, SyscallReadFile};
+use crate::syscall::{
+    FileStat, SyscallCloseFile, SyscallDemandPages, SyscallFileStat, SyscallOpenFile,
+    SyscallPutString, SyscallReadFile, SyscallSeekFile, S_IFCHR, S_IFMT,
+};
 use core::ffi::c_void;
 
 static mut ERRNO: i32 = 0;
 
 pub enum FILE {}
 
+/// `struct stat` of newlib
+#[repr(C)]
+pub struct Stat {
+    st_dev: i16,
+    st_ino: u16,
+    st_mode: u32,
+    st_nlink: u16,
+    st_uid: u16,
+    st_gid: u16,
+    st_rdev: i16,
+    st_size: i64,
+    st_atim: [i64; 2],
+    st_mtim: [i64; 2],
+    st_ctim: [i64; 2],
+    st_blksize: i64,
+    st_blocks: i64,
+    st_spare4: [i64; 2],
+}
+
 #[no_mangle]
-pub extern "C" fn close(_fd: i32) -> i32 {
-    unsafe { ERRNO = 9 }; // EBADF
-    -1
+pub extern "C" fn close(fd: i32) -> i32 {
+    let res = unsafe { SyscallCloseFile(fd) };
+    if res.is_ok() {
+        0
+    } else {
+        unsafe { ERRNO = res.error };
+        -1
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn fstat(_fd: i32, _buf: *const c_void) -> i32 {
-    unsafe { ERRNO = 9 }; // EBADF
-    -1
+pub extern "C" fn fstat(fd: i32, buf: *mut Stat) -> i32 {
+    let mut file_stat = FileStat::default();
+    let res = unsafe { SyscallFileStat(fd, &mut file_stat) };
+    if !res.is_ok() {
+        unsafe { ERRNO = res.error };
+        return -1;
+    }
+
+    let buf = match unsafe { buf.as_mut() } {
+        None => {
+            unsafe { ERRNO = 14 }; // EFAULT
+            return -1;
+        }
+        Some(b) => b,
+    };
+    unsafe { core::ptr::write_bytes(buf as *mut Stat, 0, 1) };
+    buf.st_mode = file_stat.mode;
+    buf.st_size = file_stat.size as i64;
+    buf.st_nlink = 1;
+    0
 }
 
 #[no_mangle]
@@ -24,15 +68,31 @@ pub extern "C" fn getpid() -> i32 {
 }
 
 #[no_mangle]
-pub extern "C" fn isatty(_fd: i32) -> i32 {
-    unsafe { ERRNO = 9 }; // EBADF
-    -1
+pub extern "C" fn isatty(fd: i32) -> i32 {
+    let mut file_stat = FileStat::default();
+    let res = unsafe { SyscallFileStat(fd, &mut file_stat) };
+    if !res.is_ok() {
+        unsafe { ERRNO = res.error };
+        return 0;
+    }
+
+    if file_stat.mode & S_IFMT == S_IFCHR {
+        1
+    } else {
+        unsafe { ERRNO = 25 }; // ENOTTY
+        0
+    }
 }
 
 #[no_mangle]
-pub extern "C" fn lseek(_fd: i32, _offset: i64, _whence: i32) -> i32 {
-    unsafe { ERRNO = 9 }; // EBADF
-    -1
+pub extern "C" fn lseek(fd: i32, offset: i64, whence: i32) -> i64 {
+    let res = unsafe { SyscallSeekFile(fd, offset, whence) };
+    if res.is_ok() {
+        res.value as i64
+    } else {
+        unsafe { ERRNO = res.error };
+        -1
+    }
 }
 
 #[no_mangle]
@@ -0,0 +1,70 @@
+use crate::c_char;
+use crate::env;
+use crate::syscall::{SyscallSpawn, SyscallWaitTask};
+use crate::SyscallError;
+use alloc::format;
+use alloc::vec::Vec;
+use core::ptr::null;
+
+/// Identifies a child task started by `spawn`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TaskID(u64);
+
+impl TaskID {
+    pub fn value(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Starts the app at `path` with `argv`, which should start with the app name.
+///
+/// `fds` are file descriptors of the caller to be stdin, stdout and stderr of the child.
+/// The caller's 0, 1 and 2 are inherited if `None`. The child inherits the environment variables.
+///
+/// `sh` starts the shell of the terminal, which reads commands from stdin without a script.
+pub fn spawn(path: &str, argv: &[&str], fds: Option<[i32; 3]>) -> Result<TaskID, SyscallError> {
+    let path = c_string(path);
+    let args = argv.iter().map(|&a| c_string(a)).collect::<Vec<_>>();
+    let mut arg_ptrs = args
+        .iter()
+        .map(|a| a.as_ptr() as *const c_char)
+        .collect::<Vec<_>>();
+    arg_ptrs.push(null());
+    let envs = env::vars()
+        .iter()
+        .map(|(name, value)| c_string(&format!("{}={}", name, value)))
+        .collect::<Vec<_>>();
+    let mut env_ptrs = envs
+        .iter()
+        .map(|e| e.as_ptr() as *const c_char)
+        .collect::<Vec<_>>();
+    env_ptrs.push(null());
+
+    let fds_ptr = match fds.as_ref() {
+        Some(fds) => fds as *const [i32; 3],
+        None => null(),
+    };
+    let result = unsafe {
+        SyscallSpawn(
+            path.as_ptr() as *const c_char,
+            arg_ptrs.as_ptr(),
+            fds_ptr,
+            env_ptrs.as_ptr(),
+        )
+    };
+    result.to_result().map(TaskID)
+}
+
+/// Waits for the child to exit and returns its exit code.
+pub fn wait(task_id: TaskID) -> Result<i32, SyscallError> {
+    unsafe { SyscallWaitTask(task_id.0) }
+        .to_result()
+        .map(|ec| ec as i32)
+}
+
+fn c_string(s: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(s.len() + 1);
+    bytes.extend_from_slice(s.as_bytes());
+    bytes.push(0);
+    bytes
+}
@@ -1,3 +1,4 @@
+use crate::dir::DirEntry;
 use crate::rust_official::cstr::CStr;
 use crate::{c_char, libc};
 use core::ffi::c_void;
@@ -63,6 +64,49 @@ extern "C" {
     pub(crate) fn SyscallDemandPages(num_pages: usize, flags: i32) -> SyscallResult;
 
     pub(crate) fn SyscallMapFile(fd: i32, file_size: *mut usize, flags: i32) -> SyscallResult;
+
+    pub(crate) fn SyscallCloseFile(fd: i32) -> SyscallResult;
+
+    pub(crate) fn SyscallSeekFile(fd: i32, offset: i64, whence: i32) -> SyscallResult;
+
+    pub(crate) fn SyscallFileStat(fd: i32, stat: *mut FileStat) -> SyscallResult;
+
+    pub(crate) fn SyscallOpenDirectory(path: *const c_char) -> SyscallResult;
+
+    pub(crate) fn SyscallReadDirectory(fd: i32, entry: *mut DirEntry) -> SyscallResult;
+
+    pub(crate) fn SyscallSpawn(
+        path: *const c_char,
+        argv: *const *const c_char,
+        fds: *const [i32; 3],
+        envp: *const *const c_char,
+    ) -> SyscallResult;
+
+    pub(crate) fn SyscallWaitTask(task_id: u64) -> SyscallResult;
+
+    pub(crate) fn SyscallUnlink(path: *const c_char) -> SyscallResult;
+
+    pub(crate) fn SyscallMakeDirectory(path: *const c_char) -> SyscallResult;
+
+    pub(crate) fn SyscallRemoveDirectory(path: *const c_char) -> SyscallResult;
+
+    pub(crate) fn SyscallRename(old_path: *const c_char, new_path: *const c_char) -> SyscallResult;
+
+    pub(crate) fn SyscallIoControl(fd: i32, request: u64, arg: *mut c_void) -> SyscallResult;
+
+    pub(crate) fn SyscallOpenPty(fds: *mut [i32; 2], flags: u64) -> SyscallResult;
+}
+
+// the same values as `st_mode` of POSIX
+pub(crate) const S_IFMT: u32 = 0o170000;
+pub(crate) const S_IFCHR: u32 = 0o020000;
+
+/// Filled by `SyscallFileStat`; the layout is shared with the kernel.
+#[repr(C)]
+#[derive(Default)]
+pub(crate) struct FileStat {
+    pub(crate) size: u64,
+    pub(crate) mode: u32,
 }
 
 #[repr(C)]
@@ -0,0 +1,110 @@
+//! Controls how the terminal passes typed keys to the app, like `tcgetattr` and `tcsetattr`,
+//! and opens pseudo-terminals.
+//!
+//! The terminal starts each command in the canonical mode with echo, and goes back to it when
+//! the command finishes.
+
+use crate::syscall::{SyscallIoControl, SyscallOpenPty};
+use crate::SyscallError;
+use core::ffi::c_void;
+
+/// Reads return whole lines, which can be edited with Backspace and Ctrl-U before Enter.
+/// Ctrl-D at the start of a line makes a read return 0.
+pub const MODE_CANONICAL: u32 = 1;
+/// Typed characters are shown.
+pub const MODE_ECHO: u32 = 2;
+/// Reads return 0 instead of waiting if no input is ready.
+pub const MODE_NONBLOCK: u32 = 4;
+
+// requests of `SyscallIoControl`
+const TC_GET_MODE: u64 = 0;
+const TC_SET_MODE: u64 = 1;
+const TC_GET_WINDOW_SIZE: u64 = 2;
+const TC_SET_WINDOW_SIZE: u64 = 3;
+
+/// The layout is shared with the kernel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[repr(C)]
+pub struct Mode {
+    pub flags: u32,
+}
+
+impl Mode {
+    /// Returns the mode passing each key as it is typed, whose cursor keys are escape
+    /// sequences such as `ESC [ A`.
+    pub fn raw() -> Mode {
+        Mode { flags: 0 }
+    }
+
+    pub fn has(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub fn with(self, flag: u32) -> Mode {
+        Mode {
+            flags: self.flags | flag,
+        }
+    }
+
+    pub fn without(self, flag: u32) -> Mode {
+        Mode {
+            flags: self.flags & !flag,
+        }
+    }
+}
+
+/// Passed to and from `SyscallIoControl`; the layout is shared with the kernel.
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct WindowSize {
+    pub columns: u32,
+    pub rows: u32,
+}
+
+/// Returns the mode of the terminal `fd`. It fails with ENOTTY if `fd` is not a terminal.
+pub fn get_mode(fd: i32) -> Result<Mode, SyscallError> {
+    let mut mode = Mode::default();
+    let arg = &mut mode as *mut Mode as *mut c_void;
+    unsafe { SyscallIoControl(fd, TC_GET_MODE, arg) }.to_result()?;
+    Ok(mode)
+}
+
+pub fn set_mode(fd: i32, mode: Mode) -> Result<(), SyscallError> {
+    let mut mode = mode;
+    let arg = &mut mode as *mut Mode as *mut c_void;
+    unsafe { SyscallIoControl(fd, TC_SET_MODE, arg) }
+        .to_result()
+        .map(|_| ())
+}
+
+pub fn window_size(fd: i32) -> Result<WindowSize, SyscallError> {
+    let mut size = WindowSize::default();
+    let arg = &mut size as *mut WindowSize as *mut c_void;
+    unsafe { SyscallIoControl(fd, TC_GET_WINDOW_SIZE, arg) }.to_result()?;
+    Ok(size)
+}
+
+/// Sets the size which the apps on the slave side of a pseudo-terminal get.
+pub fn set_window_size(fd: i32, size: WindowSize) -> Result<(), SyscallError> {
+    let mut size = size;
+    let arg = &mut size as *mut WindowSize as *mut c_void;
+    unsafe { SyscallIoControl(fd, TC_SET_WINDOW_SIZE, arg) }
+        .to_result()
+        .map(|_| ())
+}
+
+/// Opens a pseudo-terminal and returns the descriptors of its master and slave sides.
+///
+/// What is written to the master side is typed into the slave side, and what the slave side
+/// shows is read from the master side. The slave side is passed to `process::spawn` as the
+/// terminal of the child, such as `sh` reading commands from it, and should be closed by the
+/// caller after that so that the master side reads 0 when the child exits. Ctrl-C (0x03)
+/// written to the master side interrupts the child.
+///
+/// Reads of the master side return 0 instead of waiting if `nonblocking` is true.
+pub fn open_pty(nonblocking: bool) -> Result<(i32, i32), SyscallError> {
+    let mut fds = [0; 2];
+    let flags = if nonblocking { MODE_NONBLOCK as u64 } else { 0 };
+    unsafe { SyscallOpenPty(&mut fds, flags) }.to_result()?;
+    Ok((fds[0], fds[1]))
+}
@@ -26,3 +26,16 @@ define_syscall OpenFile,         0x8000000c
 define_syscall ReadFile,         0x8000000d
 define_syscall DemandPages,      0x8000000e
 define_syscall MapFile,          0x8000000f
+define_syscall CloseFile,        0x80000010
+define_syscall SeekFile,         0x80000011
+define_syscall FileStat,         0x80000012
+define_syscall OpenDirectory,    0x80000013
+define_syscall ReadDirectory,    0x80000014
+define_syscall Spawn,            0x80000015
+define_syscall WaitTask,         0x80000016
+define_syscall Unlink,           0x80000017
+define_syscall MakeDirectory,    0x80000018
+define_syscall RemoveDirectory,  0x80000019
+define_syscall Rename,           0x8000001a
+define_syscall IoControl,        0x8000001b
+define_syscall OpenPty,          0x8000001c
@@ -18,7 +18,7 @@ const HEIGHT: i32 = 100;
 
 #[no_mangle]
 pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
     let mut window = match Window::open((WIDTH, HEIGHT), (10, 10), "stars") {
         Ok(w) => w,
         Err(e) => exit(e.error_number()),
@@ -17,7 +17,7 @@ pub extern "C" fn main(argc: i32, argv: *const *const c_char) {
         println!("Usage: timer <msec>");
         exit(1);
     }
-    let args = Args::new(argc, argv);
+    let args = unsafe { Args::new(argc, argv) };
 
     let duration_ms = u64::from_str(args.get(1)).unwrap();
     let timeout = match create_timer(TimerType::OneshotRel, 1, duration_ms) {
@@ -19,6 +19,34 @@ IoIn32:
     in eax, dx
     ret
 
+global IoOut16  ; void IoOut16(uint16_t addr, uint16_t data);
+IoOut16:
+    mov dx, di    ; dx = addr
+    mov ax, si    ; ax = data
+    out dx, ax
+    ret
+
+global IoIn16  ; uint16_t IoIn16(uint16_t addr);
+IoIn16:
+    mov dx, di    ; dx = addr
+    xor eax, eax
+    in ax, dx
+    ret
+
+global IoOut8  ; void IoOut8(uint16_t addr, uint8_t data);
+IoOut8:
+    mov dx, di    ; dx = addr
+    mov ax, si    ; al = data
+    out dx, al
+    ret
+
+global IoIn8  ; uint8_t IoIn8(uint16_t addr);
+IoIn8:
+    mov dx, di    ; dx = addr
+    xor eax, eax
+    in al, dx
+    ret
+
 global GetCS  ; uint16_t GetCS(void);
 GetCS:
     xor eax, eax  ; also clears upper 32 bits of rax
@@ -190,21 +218,22 @@ RestoreContext:  ; void RestoreContext(void* task_context);
     o64 iret
 
 global CallApp
-CallApp:  ; int CallApp(int argc, char** argv, uint16_t ss,
+CallApp:  ; int CallApp(int argc, char** argv, char** envp, uint16_t ss,
           ;             uint64_t rip, uint64_t rsp, uint64_t* os_stack_ptr);
+    mov rax, [rsp + 8] ; os_stack_ptr
     push rbx
     push rbp
     push r12
     push r13
     push r14
     push r15
-    mov [r9], rsp ; OS 用のスタックポインタを保存
+    mov [rax], rsp ; OS 用のスタックポインタを保存
 
-    push rdx  ; SS
-    push r8   ; RSP
-    add rdx, 8
-    push rdx  ; CS
-    push rcx  ; RIP
+    push rcx  ; SS
+    push r9   ; RSP
+    add rcx, 8
+    push rcx  ; CS
+    push r8   ; RIP
     o64 retf
     ; アプリケーションが終了してもここには来ない
 
@@ -290,6 +319,7 @@ WriteMSR:  ; void WriteMSR(uint32_t msr, uint64_t value);
 
 extern GetCurrentTaskOSStackPointer
 extern syscall_table
+extern ExitAppIfInterrupted
 global SyscallEntry
 SyscallEntry:  ; void SyscallEntry(void);
     push rbp
@@ -323,6 +353,13 @@ SyscallEntry:  ; void SyscallEntry(void);
     ; rbx, r12-r15 は callee-saved なので呼び出し側で保存しない
     ; rax は戻り値用なので呼び出し側で保存しない
 
+    ; Ctrl-C で割り込まれたアプリはここで終了する
+    push rax
+    push rdx
+    call ExitAppIfInterrupted
+    pop rdx
+    pop rax
+
     mov rsp, rbp
 
     pop rsi  ; システムコール番号を復帰
@@ -357,3 +394,16 @@ global InvalidateTLB  ; void InvalidateTLB(uint64_t addr);
 InvalidateTLB:
     invlpg [rdi]
     ret
+
+global CopyUserMemory  ; int CopyUserMemory(void* dst, const void* src, size_t len);
+global CopyUserMemoryFaultable
+global CopyUserMemoryFixup
+CopyUserMemory:
+    mov rcx, rdx
+CopyUserMemoryFaultable:  ; a fault here resumes at CopyUserMemoryFixup
+    rep movsb
+    xor eax, eax
+    ret
+CopyUserMemoryFixup:
+    mov eax, 1
+    ret
@@ -15,6 +15,8 @@ pub enum AppEventType {
     MouseButton,
     TimerTimeout,
     KeyPush,
+    /// The terminal the app runs in has been resized.
+    TerminalResize,
 }
 
 #[derive(Copy, Clone)]
@@ -24,6 +26,7 @@ pub union AppEventArg {
     pub mouse_button: MouseButton,
     pub timer_timeout: TimerTimeout,
     pub key_push: KeyPush,
+    pub terminal_resize: TerminalResize,
     pub empty: (),
 }
 
@@ -65,6 +68,13 @@ pub struct KeyPush {
     pub press: bool,
 }
 
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct TerminalResize {
+    pub columns: u32,
+    pub rows: u32,
+}
+
 impl Default for AppEvent {
     fn default() -> Self {
         AppEvent {
@@ -6,6 +6,10 @@ pub mod global {
     extern "C" {
         fn IoOut32(addr: u16, data: u32);
         fn IoIn32(addr: u16) -> u32;
+        fn IoOut16(addr: u16, data: u16);
+        fn IoIn16(addr: u16) -> u16;
+        fn IoOut8(addr: u16, data: u8);
+        fn IoIn8(addr: u16) -> u8;
         fn GetCS() -> u16;
         fn LoadIDT(limit: u16, offset: u64);
         fn LoadGDT(limit: u16, offset: u64);
@@ -22,6 +26,7 @@ pub mod global {
         pub fn CallApp(
             argc: i32,
             argv: *const *const c_char,
+            envp: *const *const c_char,
             ss: u16,
             rip: u64,
             rsp: u64,
@@ -32,6 +37,9 @@ pub mod global {
         pub fn SyscallEntry();
         fn ExitApp(rsp: u64, ret_val: i32);
         fn InvalidateTLB(addr: u64);
+        fn CopyUserMemory(dst: *mut c_void, src: *const c_void, len: usize) -> i32;
+        pub fn CopyUserMemoryFaultable();
+        pub fn CopyUserMemoryFixup();
     }
 
     pub fn io_out_32(addr: u16, data: u32) {
@@ -42,6 +50,22 @@ pub mod global {
         unsafe { IoIn32(addr) }
     }
 
+    pub fn io_out_16(addr: u16, data: u16) {
+        unsafe { IoOut16(addr, data) };
+    }
+
+    pub fn io_in_16(addr: u16) -> u16 {
+        unsafe { IoIn16(addr) }
+    }
+
+    pub fn io_out_8(addr: u16, data: u8) {
+        unsafe { IoOut8(addr, data) };
+    }
+
+    pub fn io_in_8(addr: u16) -> u8 {
+        unsafe { IoIn8(addr) }
+    }
+
     pub fn get_code_segment() -> u16 {
         unsafe { GetCS() }
     }
@@ -104,12 +128,13 @@ pub mod global {
     pub fn call_app(
         argc: i32,
         argv: *const *const c_char,
+        envp: *const *const c_char,
         ss: u16,
         rip: u64,
         rsp: u64,
         os_stack_ptr: *const u64,
     ) -> i32 {
-        unsafe { CallApp(argc, argv, ss, rip, rsp, os_stack_ptr) }
+        unsafe { CallApp(argc, argv, envp, ss, rip, rsp, os_stack_ptr) }
     }
 
     pub fn write_msr(msr: u32, value: u64) {
@@ -123,4 +148,10 @@ pub mod global {
     pub fn invalidate_tlb(addr: u64) {
         unsafe { InvalidateTLB(addr) }
     }
+
+    /// Copies `len` bytes and returns false if a fault occurred while copying.
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    pub fn copy_user_memory(dst: *mut u8, src: *const u8, len: usize) -> bool {
+        unsafe { CopyUserMemory(dst as *mut c_void, src as *const c_void, len) == 0 }
+    }
 }
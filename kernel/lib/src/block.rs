@@ -0,0 +1,28 @@
+//! Block devices, which read and write data in units of blocks.
+
+use crate::error::Error;
+use alloc::boxed::Box;
+
+pub mod virtio_blk;
+
+pub trait BlockDevice {
+    /// Bytes per block, such as 512.
+    fn block_size(&self) -> usize;
+
+    fn num_blocks(&self) -> u64;
+
+    /// Reads `buf.len() / block_size()` blocks from `lba`. `buf.len()` must be a multiple of the block size.
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Error>;
+
+    /// Writes `buf.len() / block_size()` blocks to `lba`. `buf.len()` must be a multiple of the block size.
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), Error>;
+
+    /// Makes the written blocks persistent if the device has a volatile write cache.
+    fn flush(&mut self) -> Result<(), Error>;
+}
+
+/// Returns the first block device found on the PCI bus.
+pub fn find_device() -> Option<Result<Box<dyn BlockDevice + Send>, Error>> {
+    let device = virtio_blk::find_device()?;
+    Some(virtio_blk::VirtioBlock::new(device).map(|d| Box::new(d) as Box<dyn BlockDevice + Send>))
+}
@@ -0,0 +1,328 @@
+//! Driver of virtio block devices through the legacy interface, whose registers are in the I/O space.
+//!
+//! Requests are processed one at a time by polling the used ring, so no interrupt is needed.
+//!
+//! ref: Virtual I/O Device (VIRTIO) Version 1.1, 2.6 Split Virtqueues, 4.1.4.8 Legacy Interfaces
+//! and 5.2 Block Device
+
+use crate::asm::global::{io_in_16, io_in_32, io_in_8, io_out_16, io_out_32, io_out_8};
+use crate::block::BlockDevice;
+use crate::error::{Code, Error};
+use crate::make_error;
+use crate::pci;
+use crate::pci::Device;
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp;
+use core::hint::spin_loop;
+use core::mem::size_of;
+use core::ptr::{addr_of, addr_of_mut, read_volatile, write_volatile};
+use core::sync::atomic::{fence, Ordering};
+
+const VENDOR_ID: u16 = 0x1af4;
+/// The device ID of transitional block devices, which offer the legacy interface.
+const DEVICE_ID: u16 = 0x1001;
+
+// offsets of the legacy registers from BAR0
+const DEVICE_FEATURES: u16 = 0x00;
+const DRIVER_FEATURES: u16 = 0x04;
+const QUEUE_ADDRESS: u16 = 0x08;
+const QUEUE_SIZE: u16 = 0x0c;
+const QUEUE_SELECT: u16 = 0x0e;
+const QUEUE_NOTIFY: u16 = 0x10;
+const DEVICE_STATUS: u16 = 0x12;
+const ISR_STATUS: u16 = 0x13;
+/// The device specific configuration follows the registers while MSI-X is disabled.
+const CONFIG_CAPACITY: u16 = 0x14;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FAILED: u8 = 0x80;
+
+/// The device has a write cache which is flushed by `T_FLUSH` requests.
+const F_FLUSH: u32 = 1 << 9;
+
+const T_IN: u32 = 0;
+const T_OUT: u32 = 1;
+const T_FLUSH: u32 = 4;
+
+const S_OK: u8 = 0;
+
+const DESC_F_NEXT: u16 = 1;
+const DESC_F_WRITE: u16 = 2;
+const AVAIL_F_NO_INTERRUPT: u16 = 1;
+
+/// The capacity and the sector of requests are in units of 512 bytes regardless of the device.
+const SECTOR_SIZE: usize = 512;
+/// Maximum bytes a request transfers.
+const MAX_TRANSFER_SIZE: usize = 64 * 1024;
+/// Legacy devices take the address of a queue in units of pages and expect the used ring on a page boundary.
+const QUEUE_ALIGN: usize = 4096;
+
+pub fn find_device() -> Option<&'static Device> {
+    pci::devices()
+        .iter()
+        .find(|d| d.vendor_id() == VENDOR_ID && d.device_id() == DEVICE_ID)
+}
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct RequestHeader {
+    request_type: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// The parts of a request read or written by the device besides the data.
+#[repr(C)]
+struct Request {
+    header: RequestHeader,
+    status: u8,
+}
+
+#[derive(Clone, Copy)]
+#[repr(C, align(4096))]
+struct QueuePage([u8; QUEUE_ALIGN]);
+
+pub struct VirtioBlock {
+    io_base: u16,
+    features: u32,
+    capacity: u64,
+    queue: Vec<QueuePage>,
+    queue_size: u16,
+    avail_offset: usize,
+    used_offset: usize,
+    /// The index of the available ring the next request is put at.
+    next_avail: u16,
+    request: Box<Request>,
+}
+
+impl VirtioBlock {
+    /// Resets the device and sets its request queue up.
+    ///
+    /// The kernel maps the physical memory to the same virtual addresses,
+    /// so the addresses of the queue and the buffers are passed to the device as they are.
+    pub fn new(device: &Device) -> Result<Self, Error> {
+        let bar = pci::read_bar(device, 0)?;
+        if bar & 1 == 0 {
+            // not a transitional device, whose BAR0 is in the I/O space
+            return Err(make_error!(Code::UnknownDevice));
+        }
+        let io_base = (bar & !0b11) as u16;
+        device.enable_io_and_bus_master();
+
+        io_out_8(io_base + DEVICE_STATUS, 0);
+        io_out_8(io_base + DEVICE_STATUS, STATUS_ACKNOWLEDGE);
+        io_out_8(io_base + DEVICE_STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        let features = io_in_32(io_base + DEVICE_FEATURES) & F_FLUSH;
+        io_out_32(io_base + DRIVER_FEATURES, features);
+
+        io_out_16(io_base + QUEUE_SELECT, 0);
+        let queue_size = io_in_16(io_base + QUEUE_SIZE);
+        if queue_size < 3 {
+            // a request needs 3 descriptors
+            io_out_8(io_base + DEVICE_STATUS, STATUS_FAILED);
+            return Err(make_error!(Code::InvalidDescriptor));
+        }
+        let (avail_offset, used_offset, queue_bytes) = queue_layout(queue_size as usize);
+        let queue = vec![QueuePage([0; QUEUE_ALIGN]); queue_bytes / QUEUE_ALIGN];
+        io_out_32(
+            io_base + QUEUE_ADDRESS,
+            (queue.as_ptr() as usize / QUEUE_ALIGN) as u32,
+        );
+        io_out_8(
+            io_base + DEVICE_STATUS,
+            STATUS_ACKNOWLEDGE | STATUS_DRIVER | STATUS_DRIVER_OK,
+        );
+
+        let capacity = io_in_32(io_base + CONFIG_CAPACITY) as u64
+            | (io_in_32(io_base + CONFIG_CAPACITY + 4) as u64) << 32;
+
+        let mut block = Self {
+            io_base,
+            features,
+            capacity,
+            queue,
+            queue_size,
+            avail_offset,
+            used_offset,
+            next_avail: 0,
+            request: Box::new(Request {
+                header: RequestHeader {
+                    request_type: 0,
+                    reserved: 0,
+                    sector: 0,
+                },
+                status: 0,
+            }),
+        };
+        unsafe { write_volatile(block.avail_ring(0), AVAIL_F_NO_INTERRUPT) };
+        Ok(block)
+    }
+
+    fn queue_ptr(&mut self) -> *mut u8 {
+        self.queue.as_mut_ptr() as *mut u8
+    }
+
+    fn descriptor(&mut self, index: usize) -> *mut Descriptor {
+        unsafe { (self.queue_ptr() as *mut Descriptor).add(index) }
+    }
+
+    /// The `i`-th u16 of the available ring; flags, idx and then the ring.
+    fn avail_ring(&mut self, i: usize) -> *mut u16 {
+        let offset = self.avail_offset;
+        unsafe { (self.queue_ptr().add(offset) as *mut u16).add(i) }
+    }
+
+    fn used_idx(&mut self) -> *const u16 {
+        let offset = self.used_offset;
+        unsafe { (self.queue_ptr().add(offset) as *const u16).add(1) }
+    }
+
+    /// Sends a request and waits for its completion.
+    ///
+    /// `data` is the buffer to transfer and whether the device writes to it.
+    fn request(
+        &mut self,
+        request_type: u32,
+        sector: u64,
+        data: Option<(u64, usize, bool)>,
+    ) -> Result<(), Error> {
+        self.request.header = RequestHeader {
+            request_type,
+            reserved: 0,
+            sector,
+        };
+        self.request.status = u8::MAX;
+        let header_addr = addr_of!(self.request.header) as u64;
+        let status_addr = addr_of_mut!(self.request.status);
+
+        let mut descriptors = Vec::with_capacity(3);
+        descriptors.push((header_addr, size_of::<RequestHeader>(), 0));
+        if let Some((addr, len, device_writes)) = data {
+            let flags = if device_writes { DESC_F_WRITE } else { 0 };
+            descriptors.push((addr, len, flags));
+        }
+        descriptors.push((status_addr as u64, 1, DESC_F_WRITE));
+
+        let last = descriptors.len() - 1;
+        for (i, &(addr, len, flags)) in descriptors.iter().enumerate() {
+            let next = if i < last { DESC_F_NEXT } else { 0 };
+            let descriptor = Descriptor {
+                addr,
+                len: len as u32,
+                flags: flags | next,
+                next: (i + 1) as u16,
+            };
+            unsafe { write_volatile(self.descriptor(i), descriptor) };
+        }
+
+        let slot = (self.next_avail % self.queue_size) as usize;
+        unsafe { write_volatile(self.avail_ring(2 + slot), 0) };
+        self.next_avail = self.next_avail.wrapping_add(1);
+        fence(Ordering::SeqCst);
+        let next_avail = self.next_avail;
+        unsafe { write_volatile(self.avail_ring(1), next_avail) };
+        fence(Ordering::SeqCst);
+        io_out_16(self.io_base + QUEUE_NOTIFY, 0);
+
+        while unsafe { read_volatile(self.used_idx()) } != self.next_avail {
+            spin_loop();
+        }
+        fence(Ordering::SeqCst);
+        // the device may raise an interrupt regardless of AVAIL_F_NO_INTERRUPT; reading ISR deasserts it
+        io_in_8(self.io_base + ISR_STATUS);
+
+        if unsafe { read_volatile(status_addr) } == S_OK {
+            Ok(())
+        } else {
+            Err(make_error!(Code::TransferFailed))
+        }
+    }
+
+    fn check_range(&self, lba: u64, len: usize) -> Result<(), Error> {
+        let end = lba.checked_add((len / SECTOR_SIZE) as u64);
+        if len % SECTOR_SIZE != 0 || end.map_or(true, |end| end > self.capacity) {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+        Ok(())
+    }
+}
+
+impl BlockDevice for VirtioBlock {
+    fn block_size(&self) -> usize {
+        SECTOR_SIZE
+    }
+
+    fn num_blocks(&self) -> u64 {
+        self.capacity
+    }
+
+    fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+        self.check_range(lba, buf.len())?;
+        let mut offset = 0;
+        while offset < buf.len() {
+            let len = cmp::min(buf.len() - offset, MAX_TRANSFER_SIZE);
+            let addr = buf[offset..].as_mut_ptr() as u64;
+            let sector = lba + (offset / SECTOR_SIZE) as u64;
+            self.request(T_IN, sector, Some((addr, len, true)))?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), Error> {
+        self.check_range(lba, buf.len())?;
+        let mut offset = 0;
+        while offset < buf.len() {
+            let len = cmp::min(buf.len() - offset, MAX_TRANSFER_SIZE);
+            let addr = buf[offset..].as_ptr() as u64;
+            let sector = lba + (offset / SECTOR_SIZE) as u64;
+            self.request(T_OUT, sector, Some((addr, len, false)))?;
+            offset += len;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.features & F_FLUSH == 0 {
+            // the device writes through
+            return Ok(());
+        }
+        self.request(T_FLUSH, 0, None)
+    }
+}
+
+/// Returns the offsets of the available ring and the used ring of a legacy queue with `size` entries,
+/// and the bytes of the whole queue.
+fn queue_layout(size: usize) -> (usize, usize, usize) {
+    let align_up = |n: usize| (n + QUEUE_ALIGN - 1) / QUEUE_ALIGN * QUEUE_ALIGN;
+    let avail_offset = size_of::<Descriptor>() * size;
+    // flags, idx, ring and used_event
+    let used_offset = align_up(avail_offset + 2 * (3 + size));
+    // flags, idx, ring of (id: u32, len: u32) and avail_event
+    let queue_bytes = used_offset + align_up(2 * 3 + 8 * size);
+    (avail_offset, used_offset, queue_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queue_layout_of_legacy_queue() {
+        assert_eq!(queue_layout(256), (4096, 8192, 12288));
+        assert_eq!(queue_layout(128), (2048, 4096, 8192));
+        assert_eq!(size_of::<Descriptor>(), 16);
+        assert_eq!(size_of::<RequestHeader>(), 16);
+    }
+}
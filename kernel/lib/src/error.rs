@@ -55,6 +55,16 @@ pub enum Code {
     IsDirectory,
     NoSuchEntry,
     FreeTypeError,
+    BadAddress,
+    NotSeekable,
+    NotDirectory,
+    FileExists,
+    DirectoryNotEmpty,
+    NameTooLong,
+    ReadOnly,
+    Busy,
+    CrossDevice,
+    NotTerminal,
     LastOfCode,
 }
 
@@ -1,16 +1,34 @@
 use crate::error::{Code, Error};
+use crate::fat::long_name::{
+    LongNameBuilder, LongNameEntry, LAST_LONG_ENTRY, NTRES_LOWER_BASE, NTRES_LOWER_EXT,
+};
 use crate::libc::memset;
 use crate::make_error;
+use crate::sync::Mutex;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::vec::Vec;
 use core::ffi::c_void;
 use core::fmt::{Display, Formatter};
 use core::mem::size_of;
+use core::ops::Range;
 use core::{cmp, slice};
 
+mod long_name;
+mod writeback;
+
 pub mod global {
+    use crate::block::BlockDevice;
     use crate::error::{Code, Error};
-    use crate::fat::{next_path_element, Bpb, DirectoryEntry, END_OF_CLUSTER_CHAIN};
+    use crate::fat::writeback::Writeback;
+    use crate::fat::{next_path_element, Bpb, DirectoryEntry, FatDirectoryDescriptor, FoundEntry};
     use crate::make_error;
+    use alloc::boxed::Box;
+    use alloc::vec;
+    use alloc::vec::Vec;
     use core::ptr::null_mut;
+    use core::{cmp, slice};
+    use spin::Once;
 
     static mut BOOT_VOLUME_IMAGE: *mut Bpb = null_mut();
     pub fn boot_volume_image() -> &'static Bpb {
@@ -34,7 +52,95 @@ pub mod global {
         unsafe { BYTES_PER_CLUSTER = bytes_per_cluster }
     }
 
-    pub fn find_file(path: &str, mut directory_cluster: u64) -> (Option<&DirectoryEntry>, bool) {
+    /// The loader reads at most this many bytes of the boot volume.
+    const BOOT_IMAGE_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+    static WRITEBACK: Once<Writeback> = Once::new();
+
+    /// Replaces the volume handed over by the loader with the one on `device`,
+    /// to which modified sectors are written back by [sync].
+    ///
+    /// A blank device, whose first block is filled with zeros, receives a copy of the boot volume.
+    pub fn attach_block_device(mut device: Box<dyn BlockDevice + Send>) -> Result<(), Error> {
+        if WRITEBACK.is_completed() {
+            return Err(make_error!(Code::AlreadyAllocated));
+        }
+
+        let block_size = device.block_size();
+        let mut first_block = vec![0; block_size];
+        device.read_blocks(0, &mut first_block)?;
+        let blank = first_block.iter().all(|&b| b == 0);
+        let bpb = if blank {
+            boot_volume_image()
+        } else {
+            unsafe { &*(first_block.as_ptr() as *const Bpb) }
+        };
+        let bytes_per_sector = bpb.bytes_per_sector as usize;
+        if !bpb.is_fat32() || bytes_per_sector % block_size != 0 {
+            return Err(make_error!(Code::InvalidFormat));
+        }
+        let volume_bytes = bpb.volume_bytes();
+        if volume_bytes as u64 > device.num_blocks() * block_size as u64 {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+
+        let mut volume = Vec::new();
+        volume
+            .try_reserve_exact(volume_bytes)
+            .map_err(|_| make_error!(Code::NoEnoughMemory))?;
+        volume.resize(volume_bytes, 0);
+        let copied = if blank {
+            // clusters beyond what the loader read have been inaccessible, so they stay zeros
+            let copied = cmp::min(volume_bytes, BOOT_IMAGE_MAX_BYTES);
+            let boot_image =
+                unsafe { slice::from_raw_parts(BOOT_VOLUME_IMAGE as *const u8, copied) };
+            volume[..copied].copy_from_slice(boot_image);
+            copied
+        } else {
+            device.read_blocks(0, &mut volume)?;
+            0
+        };
+
+        let writeback = Writeback::new(
+            device,
+            volume_bytes / bytes_per_sector,
+            bytes_per_sector,
+            bpb.fat_sectors(),
+            bpb.num_fats as usize,
+        );
+        writeback.mark_dirty(0, copied);
+        initialize(volume.leak().as_mut_ptr());
+        WRITEBACK.call_once(|| writeback);
+        sync()
+    }
+
+    /// Writes the sectors modified since the last call back to the attached block device, if any.
+    pub fn sync() -> Result<(), Error> {
+        match WRITEBACK.get() {
+            Some(writeback) => {
+                let volume = unsafe {
+                    slice::from_raw_parts(
+                        BOOT_VOLUME_IMAGE as *const u8,
+                        boot_volume_image().volume_bytes(),
+                    )
+                };
+                writeback.sync(volume)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Marks the sectors holding `len` bytes from `offset` of the volume as modified.
+    pub(super) fn mark_dirty(offset: usize, len: usize) {
+        if let Some(writeback) = WRITEBACK.get() {
+            writeback.mark_dirty(offset, len);
+        }
+    }
+
+    pub fn find_file(
+        path: &str,
+        mut directory_cluster: u64,
+    ) -> (Option<&'static DirectoryEntry>, bool) {
         let mut path = path;
         if path.starts_with('/') {
             directory_cluster = boot_volume_image().root_cluster as u64;
@@ -49,62 +155,152 @@ pub mod global {
         };
         let path_last = next_path.is_empty();
 
-        while directory_cluster != END_OF_CLUSTER_CHAIN {
-            let dirs =
-                boot_volume_image().get_sector_by_cluster::<DirectoryEntry>(directory_cluster);
-            for dir in dirs {
-                if dir.name[0] == 0x00 {
-                    return (None, post_slash);
-                } else if !dir.name_is_equal(path_elem) {
-                    continue;
-                }
-
-                return if dir.is_directory() && !path_last {
-                    find_file(next_path, dir.first_cluster() as u64)
-                } else {
-                    (Some(dir), post_slash)
-                };
+        let mut dir = FatDirectoryDescriptor::new(directory_cluster);
+        while let Some(found) = dir.next_entry(boot_volume_image()) {
+            if !found.name_matches(path_elem) {
+                continue;
             }
-            directory_cluster = boot_volume_image().next_cluster(directory_cluster);
+
+            return if found.entry.is_directory() && !path_last {
+                find_file(next_path, found.entry.first_cluster() as u64)
+            } else {
+                (Some(found.entry), post_slash)
+            };
         }
 
         (None, post_slash)
     }
 
-    pub fn create_file(path: &str) -> Result<&DirectoryEntry, Error> {
-        let mut parent_dir_cluster = boot_volume_image().root_cluster;
+    /// Returns the first cluster of the directory at `path`.
+    pub fn find_directory(path: &str) -> Result<u64, Error> {
+        let root_cluster = boot_volume_image().root_cluster as u64;
+        if path.trim_matches('/').is_empty() {
+            return Ok(root_cluster);
+        }
+
+        match find_file(path, root_cluster) {
+            (None, _) => Err(make_error!(Code::NoSuchEntry)),
+            (Some(dir), _) if !dir.is_directory() => Err(make_error!(Code::NotDirectory)),
+            // ".." of a directory just under the root has cluster 0
+            (Some(dir), _) if dir.first_cluster() == 0 => Ok(root_cluster),
+            (Some(dir), _) => Ok(dir.first_cluster() as u64),
+        }
+    }
+
+    pub fn create_file(path: &str) -> Result<&'static DirectoryEntry, Error> {
+        let (parent_dir_cluster, file_name) = find_parent(path)?;
+        let dir = boot_volume_image_mut().allocate_named_entry(parent_dir_cluster, file_name)?;
+        Ok(dir)
+    }
+
+    pub fn make_directory(path: &str) -> Result<&'static DirectoryEntry, Error> {
+        let path = path.trim_end_matches('/');
+        if path.is_empty()
+            || find_file(path, boot_volume_image().root_cluster as u64)
+                .0
+                .is_some()
+        {
+            return Err(make_error!(Code::FileExists));
+        }
+
+        let (parent_dir_cluster, dir_name) = find_parent(path)?;
+        let dir = boot_volume_image_mut().make_directory(parent_dir_cluster, dir_name)?;
+        Ok(dir)
+    }
 
-        let file_name = if let Some(slash_pos) = path.rfind('/') {
-            let filename = &path[slash_pos + 1..];
-            if filename.is_empty() {
-                return Err(make_error!(Code::IsDirectory));
+    /// Removes the file at `path` and frees its clusters. Directories are not removed.
+    pub fn remove_file(path: &str) -> Result<(), Error> {
+        let found = find_entry(path)?;
+        if found.entry.is_directory() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        boot_volume_image_mut().remove_entry(&found)
+    }
+
+    /// Removes the empty directory at `path`.
+    pub fn remove_directory(path: &str) -> Result<(), Error> {
+        let path = path.trim_end_matches('/');
+        if path.is_empty() {
+            return Err(make_error!(Code::InvalidFile));
+        }
+
+        let found = find_entry(path)?;
+        if !found.entry.is_directory() {
+            return Err(make_error!(Code::NotDirectory));
+        }
+        if !boot_volume_image().is_empty_directory(found.entry.first_cluster() as u64) {
+            return Err(make_error!(Code::DirectoryNotEmpty));
+        }
+        boot_volume_image_mut().remove_entry(&found)
+    }
+
+    /// Moves the entry at `old_path` to `new_path`, replacing the file at `new_path` if any.
+    pub fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
+        let old_path = old_path.trim_end_matches('/');
+        let new_path = new_path.trim_end_matches('/');
+        if old_path.is_empty() || new_path.is_empty() {
+            return Err(make_error!(Code::InvalidFile));
+        }
+
+        let found = find_entry(old_path)?;
+        if found.entry.is_directory()
+            && new_path
+                .strip_prefix(old_path)
+                .map_or(false, |rest| rest.starts_with('/'))
+        {
+            // a directory cannot be moved into itself
+            return Err(make_error!(Code::InvalidFile));
+        }
+
+        // `dest` is `found` itself if only the case of the name changes
+        let dest = find_entry(new_path)
+            .ok()
+            .filter(|dest| dest.position != found.position);
+        if let Some(dest) = &dest {
+            if dest.entry.is_directory() || found.entry.is_directory() {
+                return Err(make_error!(Code::FileExists));
             }
+        }
 
-            let parent_dir_name = &path[..slash_pos];
-            if !parent_dir_name.is_empty() {
-                let (parent_dir, _) =
-                    find_file(parent_dir_name, boot_volume_image().root_cluster as u64);
-                if let Some(parent) = parent_dir {
-                    parent_dir_cluster = parent.first_cluster();
-                } else {
-                    return Err(make_error!(Code::NoSuchEntry));
-                }
+        let (parent_dir_cluster, name) = find_parent(new_path)?;
+        boot_volume_image_mut().move_entry(&found, dest.as_ref(), parent_dir_cluster, name)
+    }
+
+    /// Looks up the entry at `path` along with its long name.
+    pub(crate) fn find_entry(path: &str) -> Result<FoundEntry, Error> {
+        let (dir_cluster, name) = find_parent(path.trim_end_matches('/'))?;
+        let mut dir = FatDirectoryDescriptor::new(dir_cluster);
+        while let Some(found) = dir.next_entry(boot_volume_image()) {
+            if !found.name_matches(name) {
+                continue;
             }
 
-            filename
-        } else {
-            path
-        };
+            return if path.ends_with('/') && !found.entry.is_directory() {
+                Err(make_error!(Code::NotDirectory))
+            } else {
+                Ok(found)
+            };
+        }
+        Err(make_error!(Code::NoSuchEntry))
+    }
 
-        let dir = boot_volume_image_mut().allocate_entry(parent_dir_cluster as u64)?;
-        dir.set_file_name(file_name);
-        dir.file_size = 0;
-        Ok(dir)
+    /// Splits `path` into the first cluster of the parent directory and the last element.
+    fn find_parent(path: &str) -> Result<(u64, &str), Error> {
+        let (parent_dir_name, name) = match path.rfind('/') {
+            Some(slash_pos) => (&path[..slash_pos], &path[slash_pos + 1..]),
+            None => ("", path),
+        };
+        if name.is_empty() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        Ok((find_directory(parent_dir_name)?, name))
     }
 }
 
 pub const END_OF_CLUSTER_CHAIN: u64 = 0x0fffffff;
 
+const ATTR_DIRECTORY: u8 = 0x10;
+
 #[repr(packed)]
 pub struct Bpb {
     jump_boot: [u8; 3],
@@ -150,6 +346,45 @@ impl Bpb {
         self.root_cluster
     }
 
+    fn is_fat32(&self) -> bool {
+        self.bytes_per_sector.is_power_of_two()
+            && self.bytes_per_sector >= 512
+            && self.sectors_per_cluster != 0
+            && self.num_fats != 0
+            && self.fat_size_16 == 0
+            && self.fat_size_32 != 0
+    }
+
+    fn volume_bytes(&self) -> usize {
+        let total_sectors = if self.total_sectors_16 != 0 {
+            self.total_sectors_16 as usize
+        } else {
+            self.total_sectors_32 as usize
+        };
+        total_sectors * self.bytes_per_sector as usize
+    }
+
+    /// Sectors of the first FAT.
+    fn fat_sectors(&self) -> Range<usize> {
+        let begin = self.reserved_sector_count as usize;
+        begin..begin + self.fat_size_32 as usize
+    }
+
+    /// Marks the sectors holding `len` bytes from `addr` in the volume as modified.
+    fn mark_dirty(&self, addr: *const u8, len: usize) {
+        let offset = addr as usize - self as *const _ as usize;
+        global::mark_dirty(offset, len);
+    }
+
+    fn mark_entry_dirty(&self, entry: &DirectoryEntry) {
+        self.mark_dirty(entry as *const _ as *const u8, size_of::<DirectoryEntry>());
+    }
+
+    fn mark_fat_dirty(&self, cluster: u64) {
+        let fat_entry = unsafe { self.get_fat().add(cluster as usize) };
+        self.mark_dirty(fat_entry as *const u8, size_of::<u32>());
+    }
+
     fn get_entries_per_cluster(&self) -> usize {
         self.bytes_per_sector as usize / size_of::<DirectoryEntry>()
             * self.sectors_per_cluster as usize
@@ -192,8 +427,10 @@ impl Bpb {
         unsafe { slice::from_raw_parts(data.cast(), size) }
     }
 
+    /// Returns the cluster to be modified, marking it dirty.
     pub fn get_sector_by_cluster_mut<T>(&self, cluster: u64) -> &'static mut [T] {
         let data = self.get_cluster_addr(cluster) as *mut u8;
+        self.mark_dirty(data, self.bytes_per_cluster() as usize);
         let size = self.bytes_per_cluster() as usize / size_of::<T>();
         unsafe { slice::from_raw_parts_mut(data.cast(), size) }
     }
@@ -218,39 +455,271 @@ impl Bpb {
             if fat_value_at(candidate) == 0 {
                 // candidate cluster is free
                 unsafe { *fat_at(current as usize) = candidate as u32 };
+                self.mark_fat_dirty(current);
                 current = candidate as u64;
                 num_allocated += 1;
             }
             candidate += 1;
         }
         unsafe { *fat_at(current as usize) = END_OF_CLUSTER_CHAIN as u32 };
+        self.mark_fat_dirty(current);
 
         current
     }
 
-    fn allocate_entry(&mut self, mut dir_cluster: u64) -> Result<&mut DirectoryEntry, Error> {
+    fn entry(&self, position: EntryPosition) -> &'static DirectoryEntry {
+        &self.get_sector_by_cluster::<DirectoryEntry>(position.cluster)[position.index]
+    }
+
+    /// Returns the entry to be modified, marking only its sector dirty.
+    fn entry_mut(&self, position: EntryPosition) -> &'static mut DirectoryEntry {
+        let entries = self.get_cluster_addr(position.cluster) as *mut DirectoryEntry;
+        let entry = unsafe { &mut *entries.add(position.index) };
+        self.mark_entry_dirty(entry);
+        entry
+    }
+
+    /// Returns the position of the entry next to `position` in the directory, if any.
+    fn next_position(&self, position: EntryPosition) -> Option<EntryPosition> {
+        if position.index + 1 < self.get_entries_per_cluster() {
+            return Some(EntryPosition::new(position.cluster, position.index + 1));
+        }
+        match self.next_cluster(position.cluster) {
+            END_OF_CLUSTER_CHAIN => None,
+            next => Some(EntryPosition::new(next, 0)),
+        }
+    }
+
+    /// Finds `n` consecutive free entries in the directory, extending it if there are not.
+    fn allocate_entries(&mut self, dir_cluster: u64, n: usize) -> EntryPosition {
+        let mut position = EntryPosition::new(dir_cluster, 0);
+        let mut run_begin = position;
+        let mut run_len = 0;
         loop {
-            let dirs = self.get_sector_by_cluster_mut::<DirectoryEntry>(dir_cluster);
-            for dir in dirs {
-                if dir.name[0] == 0 || dir.name[0] == 0xe5 {
-                    return Ok(dir);
+            let entry = self.entry(position);
+            if entry.is_free() || entry.is_free_and_no_more_allocated_after_this() {
+                if run_len == 0 {
+                    run_begin = position;
+                }
+                run_len += 1;
+                if run_len == n {
+                    return run_begin;
+                }
+            } else {
+                run_len = 0;
+            }
+
+            match self.next_position(position) {
+                Some(next) => position = next,
+                None => break,
+            }
+        }
+
+        let entries_per_cluster = self.get_entries_per_cluster();
+        let num_clusters = (n - run_len + entries_per_cluster - 1) / entries_per_cluster;
+        self.extend_cluster(position.cluster, num_clusters);
+        let mut cluster = self.next_cluster(position.cluster);
+        if run_len == 0 {
+            run_begin = EntryPosition::new(cluster, 0);
+        }
+        while cluster != END_OF_CLUSTER_CHAIN {
+            self.clear_cluster(cluster);
+            cluster = self.next_cluster(cluster);
+        }
+        run_begin
+    }
+
+    /// Allocates an empty entry named `name` in the directory `dir_cluster`.
+    ///
+    /// Long name entries are placed before the entry unless `name` is a valid 8.3 name.
+    fn allocate_named_entry(
+        &mut self,
+        dir_cluster: u64,
+        name: &str,
+    ) -> Result<&'static mut DirectoryEntry, Error> {
+        let (short_name, ntres, long_name) = match long_name::to_short_name(name) {
+            Some((short_name, ntres)) => (short_name, ntres, Vec::new()),
+            None => {
+                let long_name = long_name::encode(name)?;
+                (self.unique_short_name(dir_cluster, name), 0, long_name)
+            }
+        };
+
+        let mut position = self.allocate_entries(dir_cluster, long_name.len() + 1);
+        let checksum = long_name::checksum(&short_name);
+        for (i, chars) in long_name.iter().enumerate() {
+            let mut ord = (long_name.len() - i) as u8;
+            if i == 0 {
+                ord |= LAST_LONG_ENTRY;
+            }
+            *self.entry_mut(position).as_long_name_mut() = LongNameEntry::new(ord, checksum, chars);
+            position = self
+                .next_position(position)
+                .expect("allocated entries must be consecutive");
+        }
+
+        let entry = self.entry_mut(position);
+        entry.name = short_name;
+        entry.ntres = ntres;
+        entry.init(0, 0);
+        Ok(entry)
+    }
+
+    fn unique_short_name(&self, dir_cluster: u64, name: &str) -> [u8; 11] {
+        let exists = |short_name: &[u8; 11]| {
+            let mut dir = FatDirectoryDescriptor::new(dir_cluster);
+            while let Some(found) = dir.next_entry(self) {
+                if &found.entry.name == short_name {
+                    return true;
                 }
             }
+            false
+        };
 
-            let next = self.next_cluster(dir_cluster);
-            if next == END_OF_CLUSTER_CHAIN {
+        (1..)
+            .map(|n| long_name::numbered_short_name(name, n))
+            .find(|short_name| !exists(short_name))
+            .unwrap()
+    }
+
+    /// Returns all clusters of the chain starting at `first_cluster` to the FAT.
+    fn free_cluster_chain(&mut self, first_cluster: u64) {
+        let fat = self.get_fat_mut();
+        let mut cluster = first_cluster;
+        while cluster >= 2 && !is_end_of_cluster_chain(cluster) {
+            let fat_at = unsafe { fat.add(cluster as usize) };
+            self.mark_fat_dirty(cluster);
+            cluster = unsafe { *fat_at } as u64;
+            unsafe { *fat_at = 0 };
+        }
+    }
+
+    fn clear_cluster(&self, cluster: u64) {
+        let data = self.get_cluster_addr(cluster) as *mut u8;
+        unsafe { memset(data as *mut c_void, 0, self.bytes_per_cluster() as usize) };
+        self.mark_dirty(data, self.bytes_per_cluster() as usize);
+    }
+
+    /// Frees the clusters of the entry and marks it and its long name entries as free.
+    ///
+    /// Fails if a file descriptor has the entry open, which would read freed clusters.
+    pub(crate) fn remove_entry(&mut self, found: &FoundEntry) -> Result<(), Error> {
+        if is_open(found.entry) {
+            return Err(make_error!(Code::Busy));
+        }
+        self.free_cluster_chain(found.entry.first_cluster() as u64);
+        self.free_entries(found);
+        Ok(())
+    }
+
+    /// Frees the clusters of the file and makes it empty.
+    ///
+    /// Fails if a file descriptor has the entry open, which would write to freed clusters.
+    pub(crate) fn truncate_entry(&mut self, found: &FoundEntry) -> Result<(), Error> {
+        if found.entry.is_directory() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        if is_open(found.entry) {
+            return Err(make_error!(Code::Busy));
+        }
+        self.free_cluster_chain(found.entry.first_cluster() as u64);
+        let entry = self.entry_mut(found.position);
+        entry.set_first_cluster(0);
+        entry.file_size = 0;
+        Ok(())
+    }
+
+    fn free_entries(&mut self, found: &FoundEntry) {
+        let mut position = found.first;
+        loop {
+            self.entry_mut(position).name[0] = 0xe5;
+            if position == found.position {
                 break;
             }
-            dir_cluster = next;
+            position = self
+                .next_position(position)
+                .expect("long name entries must precede their entry");
         }
+    }
 
-        dir_cluster = self.extend_cluster(dir_cluster, 1);
+    /// Creates an empty directory, which has only "." and "..", in the directory `parent_cluster`.
+    pub fn make_directory(
+        &mut self,
+        parent_cluster: u64,
+        name: &str,
+    ) -> Result<&'static mut DirectoryEntry, Error> {
+        let dir = self.allocate_named_entry(parent_cluster, name)?;
 
-        let data = self.get_cluster_addr(dir_cluster) as *mut u8;
-        unsafe { memset(data as *mut c_void, 0, self.bytes_per_sector as usize) };
+        let dir_cluster = self.allocate_cluster_chain(1);
+        self.clear_cluster(dir_cluster);
         let dirs = self.get_sector_by_cluster_mut::<DirectoryEntry>(dir_cluster);
-        dirs.get_mut(0)
-            .ok_or_else(|| make_error!(Code::NoEnoughMemory))
+        dirs[0].name = *b".          ";
+        dirs[0].init(ATTR_DIRECTORY, dir_cluster);
+        dirs[1].name = *b"..         ";
+        dirs[1].init(ATTR_DIRECTORY, self.dot_dot_cluster(parent_cluster));
+
+        dir.init(ATTR_DIRECTORY, dir_cluster);
+        Ok(dir)
+    }
+
+    /// Moves the entry to the directory `parent_cluster` as `name`, removing `replaced` which
+    /// has that name if any.
+    ///
+    /// Fails without changing anything if a file descriptor has either entry open, which would
+    /// keep the old entry, or if `name` cannot be stored.
+    pub(crate) fn move_entry(
+        &mut self,
+        found: &FoundEntry,
+        replaced: Option<&FoundEntry>,
+        parent_cluster: u64,
+        name: &str,
+    ) -> Result<(), Error> {
+        if is_open(found.entry) || replaced.map_or(false, |replaced| is_open(replaced.entry)) {
+            return Err(make_error!(Code::Busy));
+        }
+        if long_name::to_short_name(name).is_none() {
+            long_name::encode(name)?;
+        }
+        if let Some(replaced) = replaced {
+            self.remove_entry(replaced)?;
+        }
+
+        let entry = *found.entry;
+        let moved = self.allocate_named_entry(parent_cluster, name)?;
+        let (short_name, ntres) = (moved.name, moved.ntres);
+        *moved = entry;
+        moved.name = short_name;
+        moved.ntres = ntres;
+        self.free_entries(found);
+
+        if entry.is_directory() {
+            let dot_dot_cluster = self.dot_dot_cluster(parent_cluster);
+            let position = EntryPosition::new(entry.first_cluster().into(), 1);
+            if self.entry(position).name.starts_with(b"..") {
+                self.entry_mut(position).set_first_cluster(dot_dot_cluster);
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns true if the directory has no entries other than "." and "..".
+    pub fn is_empty_directory(&self, dir_cluster: u64) -> bool {
+        let mut dir = FatDirectoryDescriptor::new(dir_cluster);
+        while let Some(found) = dir.next_entry(self) {
+            if !found.entry.name.starts_with(b".") {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// ".." refers to the root directory by cluster 0.
+    fn dot_dot_cluster(&self, parent_cluster: u64) -> u64 {
+        if parent_cluster == self.root_cluster as u64 {
+            0
+        } else {
+            parent_cluster
+        }
     }
 
     fn allocate_cluster_chain(&mut self, n: usize) -> u64 {
@@ -261,6 +730,7 @@ impl Bpb {
             unsafe {
                 if *x == 0 {
                     *x = END_OF_CLUSTER_CHAIN as u32;
+                    self.mark_fat_dirty(first_cluster);
                     break;
                 }
             }
@@ -326,6 +796,7 @@ impl Display for TryFromAttributeError {
 }
 
 /// See 27 page of https://download.microsoft.com/download/1/6/1/161ba512-40e2-4cc9-843a-923143f3456c/fatgen103.doc
+#[derive(Clone, Copy)]
 #[repr(packed)]
 pub struct DirectoryEntry {
     pub name: [u8; 11],
@@ -351,6 +822,11 @@ impl DirectoryEntry {
         self.first_cluster_low as u32 | ((self.first_cluster_high as u32) << 16)
     }
 
+    /// The attribute byte as is, which may combine several `Attribute`s.
+    pub fn raw_attr(&self) -> u8 {
+        self.attr
+    }
+
     pub fn attr(&self) -> Option<Attribute> {
         self.attr.try_into().ok()
     }
@@ -382,6 +858,25 @@ impl DirectoryEntry {
         base
     }
 
+    /// The 8.3 name such as "readme.txt", lowercased as the NT flags tell.
+    pub fn short_name(&self) -> String {
+        let mut name = String::new();
+        let base = self.basename();
+        let ext = self.extension();
+        let push = |name: &mut String, part: &[u8], lower: bool| {
+            for &c in part.iter().take_while(|&&c| c != 0) {
+                name.push(if lower { c.to_ascii_lowercase() } else { c } as char);
+            }
+        };
+
+        push(&mut name, &base, self.ntres & NTRES_LOWER_BASE != 0);
+        if ext[0] != 0 {
+            name.push('.');
+            push(&mut name, &ext, self.ntres & NTRES_LOWER_EXT != 0);
+        }
+        name
+    }
+
     pub fn formatted_name(&self) -> [u8; 12] {
         let mut dest = [0_u8; 12];
         let base = self.basename();
@@ -438,6 +933,29 @@ impl DirectoryEntry {
         self.name == name83
     }
 
+    /// Clears all fields but the name and its NT flags.
+    fn init(&mut self, attr: u8, first_cluster: u64) {
+        let (name, ntres) = (self.name, self.ntres);
+        unsafe { memset(self as *mut _ as *mut c_void, 0, size_of::<Self>()) };
+        self.name = name;
+        self.ntres = ntres;
+        self.attr = attr;
+        self.set_first_cluster(first_cluster);
+    }
+
+    fn as_long_name(&self) -> &LongNameEntry {
+        unsafe { &*(self as *const _ as *const LongNameEntry) }
+    }
+
+    fn as_long_name_mut(&mut self) -> &mut LongNameEntry {
+        unsafe { &mut *(self as *mut _ as *mut LongNameEntry) }
+    }
+
+    fn set_first_cluster(&mut self, cluster: u64) {
+        self.first_cluster_low = (cluster & 0xffff) as u16;
+        self.first_cluster_high = ((cluster >> 16) & 0xffff) as u16;
+    }
+
     pub fn load_file(&self, buf: &mut [u8], bpb: &Bpb) -> usize {
         FatFileDescriptor::new(self).read(buf, bpb)
     }
@@ -483,6 +1001,15 @@ impl<'a> PathElements<'a> {
     }
 }
 
+/// The numbers of the file descriptors opening the entries, keyed by the addresses of the entries.
+static OPEN_ENTRIES: Mutex<BTreeMap<usize, usize>> = Mutex::new(BTreeMap::new());
+
+fn is_open(entry: &DirectoryEntry) -> bool {
+    OPEN_ENTRIES
+        .lock()
+        .contains_key(&(entry as *const _ as usize))
+}
+
 pub(crate) struct FatFileDescriptor {
     // hold a raw pointer to avoid adding lifetime parameters to many structs...
     fat_entry: *const DirectoryEntry,
@@ -496,6 +1023,10 @@ pub(crate) struct FatFileDescriptor {
 
 impl FatFileDescriptor {
     pub(crate) fn new(fat_entry: &DirectoryEntry) -> Self {
+        *OPEN_ENTRIES
+            .lock()
+            .entry(fat_entry as *const _ as usize)
+            .or_insert(0) += 1;
         Self {
             fat_entry: fat_entry as *const _,
             rd_off: 0,
@@ -517,7 +1048,10 @@ impl FatFileDescriptor {
             self.rd_cluster = fat_entry.first_cluster() as u64;
         }
 
-        let len = cmp::min(buf.len(), fat_entry.file_size as usize - self.rd_off);
+        let len = cmp::min(
+            buf.len(),
+            (fat_entry.file_size as usize).saturating_sub(self.rd_off),
+        );
 
         let mut total = 0;
         let bytes_per_cluster = bpb.bytes_per_cluster();
@@ -555,8 +1089,7 @@ impl FatFileDescriptor {
                 self.wr_cluster = fat_entry.first_cluster() as u64;
             } else {
                 self.wr_cluster = bpb.allocate_cluster_chain(num_cluster(buf.len()) as usize);
-                fat_entry.first_cluster_low = (self.wr_cluster & 0xffff) as u16;
-                fat_entry.first_cluster_high = ((self.wr_cluster >> 16) & 0xffff) as u16;
+                fat_entry.set_first_cluster(self.wr_cluster);
             }
         }
 
@@ -575,20 +1108,66 @@ impl FatFileDescriptor {
 
             let sec = bpb.get_sector_by_cluster_mut::<u8>(self.wr_cluster);
             let n = cmp::min(
-                buf.len(),
+                buf.len() - total,
                 (bytes_per_cluster - self.wr_cluster_off as u64) as usize,
             );
-            sec[self.wr_cluster_off..self.wr_cluster_off + n].copy_from_slice(&buf[..n]);
+            sec[self.wr_cluster_off..self.wr_cluster_off + n]
+                .copy_from_slice(&buf[total..total + n]);
             total += n;
 
             self.wr_cluster_off += n;
         }
 
         self.wr_off += total;
-        fat_entry.file_size = self.wr_off as u32;
+        fat_entry.file_size = cmp::max(fat_entry.file_size, self.wr_off as u32);
+        bpb.mark_entry_dirty(fat_entry);
         total
     }
 
+    /// Moves both the read and the write offset to `offset`, which must not exceed the file size.
+    pub fn seek(&mut self, offset: usize, bpb: &Bpb) -> Result<usize, Error> {
+        if offset > self.size() {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+        let first_cluster = unsafe { (*self.fat_entry).first_cluster() } as u64;
+        let bytes_per_cluster = bpb.bytes_per_cluster() as usize;
+
+        // cluster 0 makes read/write start from the first cluster
+        let mut cluster = 0;
+        let mut cluster_off = offset;
+        if offset > 0 {
+            cluster = first_cluster;
+            // stop at the cluster holding the last byte before `offset`,
+            // as write extends the chain only when it needs the next cluster
+            while cluster_off > bytes_per_cluster {
+                cluster_off -= bytes_per_cluster;
+                cluster = bpb.next_cluster(cluster);
+            }
+        }
+
+        self.wr_off = offset;
+        self.wr_cluster = cluster;
+        self.wr_cluster_off = cluster_off;
+
+        self.rd_off = offset;
+        self.rd_cluster = cluster;
+        self.rd_cluster_off = cluster_off;
+        if cluster_off == bytes_per_cluster {
+            self.rd_cluster = bpb.next_cluster(cluster);
+            self.rd_cluster_off = 0;
+        }
+        Ok(offset)
+    }
+
+    /// The offset `SEEK_CUR` is relative to; the furthest of the read and the write offsets.
+    pub fn position(&self) -> usize {
+        cmp::max(self.rd_off, self.wr_off)
+    }
+
+    pub fn is_directory(&self) -> bool {
+        unsafe { (*self.fat_entry).is_directory() }
+    }
+
     pub fn load(&mut self, buf: &mut [u8], mut offset: usize, bpb: &Bpb) -> usize {
         let bytes_per_cluster = bpb.bytes_per_cluster();
         let mut fd = match unsafe { (self.fat_entry as *mut DirectoryEntry).as_mut() } {
@@ -614,6 +1193,109 @@ impl FatFileDescriptor {
     }
 }
 
+impl Drop for FatFileDescriptor {
+    fn drop(&mut self) {
+        let mut open_entries = OPEN_ENTRIES.lock();
+        let key = self.fat_entry as usize;
+        if let Some(count) = open_entries.get_mut(&key) {
+            *count -= 1;
+            if *count == 0 {
+                open_entries.remove(&key);
+            }
+        }
+    }
+}
+
+/// Position of a directory entry: the cluster of the directory and the index in it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct EntryPosition {
+    cluster: u64,
+    index: usize,
+}
+
+impl EntryPosition {
+    fn new(cluster: u64, index: usize) -> Self {
+        Self { cluster, index }
+    }
+}
+
+/// A file or directory returned by `FatDirectoryDescriptor::next_entry`.
+pub(crate) struct FoundEntry {
+    pub(crate) entry: &'static DirectoryEntry,
+    /// The long name if the entry has one, the 8.3 name otherwise.
+    pub(crate) name: String,
+    /// The first long name entry, or the entry itself if it has no long name.
+    first: EntryPosition,
+    position: EntryPosition,
+}
+
+impl FoundEntry {
+    /// Names are compared case-insensitively, and the 8.3 alias of a long name also matches.
+    pub(crate) fn name_matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || self.entry.short_name().eq_ignore_ascii_case(name)
+    }
+}
+
+/// Walks entries of a directory, used for listing it.
+pub(crate) struct FatDirectoryDescriptor {
+    cluster: u64,
+    index: usize,
+}
+
+impl FatDirectoryDescriptor {
+    pub(crate) fn new(dir_cluster: u64) -> Self {
+        Self {
+            cluster: dir_cluster,
+            index: 0,
+        }
+    }
+
+    /// Returns the next entry with its name, skipping free entries and long name entries.
+    pub fn next_entry(&mut self, bpb: &Bpb) -> Option<FoundEntry> {
+        let mut long_name = LongNameBuilder::default();
+        let mut first = None;
+        while self.cluster != END_OF_CLUSTER_CHAIN {
+            let dirs = bpb.get_sector_by_cluster::<DirectoryEntry>(self.cluster);
+            if self.index >= dirs.len() {
+                self.cluster = bpb.next_cluster(self.cluster);
+                self.index = 0;
+                continue;
+            }
+
+            let position = EntryPosition::new(self.cluster, self.index);
+            let dir = &dirs[self.index];
+            if dir.is_free_and_no_more_allocated_after_this() {
+                self.cluster = END_OF_CLUSTER_CHAIN;
+                break;
+            }
+            self.index += 1;
+            if dir.is_free() {
+                long_name.clear();
+                continue;
+            }
+            if dir.attr() == Some(Attribute::LongName) {
+                long_name.push(dir.as_long_name());
+                if dir.name[0] & LAST_LONG_ENTRY != 0 && !long_name.is_empty() {
+                    first = Some(position);
+                }
+                continue;
+            }
+
+            let (name, first) = match (long_name.take(&dir.name), first) {
+                (Some(name), Some(first)) => (name, first),
+                _ => (dir.short_name(), position),
+            };
+            return Some(FoundEntry {
+                entry: dir,
+                name,
+                first,
+                position,
+            });
+        }
+        None
+    }
+}
+
 fn next_path_element(path: &str) -> Option<PathElements> {
     path.find('/').map(|first_slash_index| {
         let path_before_slash = &path[..first_slash_index];
@@ -622,6 +1304,30 @@ fn next_path_element(path: &str) -> Option<PathElements> {
     })
 }
 
+/// Makes `path` absolute by joining it to `cwd` and removes "." and ".." elements.
+///
+/// A trailing slash of `path` is kept so that callers can still tell "file/" from "file".
+pub fn resolve_path(cwd: &str, path: &str) -> String {
+    let mut elems = Vec::new();
+    let base = if path.starts_with('/') { "" } else { cwd };
+    for elem in base.split('/').chain(path.split('/')) {
+        match elem {
+            "" | "." => {}
+            ".." => {
+                elems.pop();
+            }
+            _ => elems.push(elem),
+        }
+    }
+
+    let mut resolved = String::from("/");
+    resolved.push_str(&elems.join("/"));
+    if path.ends_with('/') && !elems.is_empty() {
+        resolved.push('/');
+    }
+    resolved
+}
+
 fn is_end_of_cluster_chain(cluster: u64) -> bool {
     cluster >= 0x0ffffff8
 }
@@ -629,6 +1335,7 @@ fn is_end_of_cluster_chain(cluster: u64) -> bool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use alloc::vec;
 
     #[test]
     fn test_next_path_element() {
@@ -649,6 +1356,25 @@ mod tests {
         );
     }
 
+    #[test]
+    fn resolve_path_relative_to_cwd() {
+        assert_eq!(resolve_path("/", "abc"), "/abc");
+        assert_eq!(resolve_path("/apps", "abc"), "/apps/abc");
+        assert_eq!(resolve_path("/apps", "/abc"), "/abc");
+        assert_eq!(resolve_path("/apps", ""), "/apps");
+        assert_eq!(resolve_path("/apps", "abc/"), "/apps/abc/");
+    }
+
+    #[test]
+    fn resolve_path_dots() {
+        assert_eq!(resolve_path("/apps", "."), "/apps");
+        assert_eq!(resolve_path("/apps", ".."), "/");
+        assert_eq!(resolve_path("/", ".."), "/");
+        assert_eq!(resolve_path("/a/b", "../c/./d"), "/a/c/d");
+        assert_eq!(resolve_path("/a", "../../.."), "/");
+        assert_eq!(resolve_path("/", "a//b/"), "/a/b/");
+    }
+
     #[test]
     fn directory_entry_set_name() {
         let mut dir = directory_entry();
@@ -684,6 +1410,228 @@ mod tests {
         assert_eq!(&dir.name, expected);
     }
 
+    #[test]
+    fn make_directory_has_dot_entries() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        let dir_cluster = bpb
+            .make_directory(root_cluster, "sub")
+            .unwrap()
+            .first_cluster() as u64;
+        let sub_cluster = bpb
+            .make_directory(dir_cluster, "sub2")
+            .unwrap()
+            .first_cluster() as u64;
+
+        let dirs = bpb.get_sector_by_cluster::<DirectoryEntry>(sub_cluster);
+        assert_eq!(&dirs[0].name, b".          ");
+        assert_eq!(dirs[0].first_cluster() as u64, sub_cluster);
+        assert_eq!(&dirs[1].name, b"..         ");
+        assert_eq!(dirs[1].first_cluster() as u64, dir_cluster);
+
+        assert!(bpb.is_empty_directory(sub_cluster));
+        assert!(!bpb.is_empty_directory(dir_cluster));
+        // ".." of a directory just under the root is 0
+        let dirs = bpb.get_sector_by_cluster::<DirectoryEntry>(dir_cluster);
+        assert_eq!(dirs[1].first_cluster(), 0);
+    }
+
+    #[test]
+    fn remove_entry_frees_clusters() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        let dir_cluster = bpb
+            .make_directory(root_cluster, "sub")
+            .unwrap()
+            .first_cluster();
+        let found = find_in(bpb, root_cluster, "sub").unwrap();
+        bpb.remove_entry(&found).unwrap();
+
+        assert!(found.entry.is_free());
+        assert_eq!(bpb.allocate_cluster_chain(1), dir_cluster as u64);
+    }
+
+    #[test]
+    fn truncate_entry_frees_clusters() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        let entry = bpb.allocate_named_entry(root_cluster, "log.txt").unwrap();
+        let mut fd = FatFileDescriptor::new(entry);
+        fd.write(b"hello", bpb);
+        let cluster = entry.first_cluster();
+        let found = find_in(bpb, root_cluster, "log.txt").unwrap();
+        assert!(matches!(bpb.truncate_entry(&found), Err(e) if matches!(e.code, Code::Busy)));
+        assert_eq!(found.entry.file_size(), 5);
+
+        drop(fd);
+        bpb.truncate_entry(&found).unwrap();
+        assert_eq!(found.entry.file_size(), 0);
+        assert_eq!(found.entry.first_cluster(), 0);
+        assert_eq!(bpb.allocate_cluster_chain(1), cluster as u64);
+    }
+
+    #[test]
+    fn move_entry_updates_dot_dot() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        let a_cluster = bpb
+            .make_directory(root_cluster, "a")
+            .unwrap()
+            .first_cluster() as u64;
+        let b_cluster = bpb
+            .make_directory(root_cluster, "b")
+            .unwrap()
+            .first_cluster() as u64;
+        let c_cluster = bpb.make_directory(a_cluster, "c").unwrap().first_cluster() as u64;
+
+        let c = find_in(bpb, a_cluster, "c").unwrap();
+        bpb.move_entry(&c, None, b_cluster, "d").unwrap();
+
+        assert!(c.entry.is_free());
+        assert!(bpb.is_empty_directory(a_cluster));
+        let moved = find_in(bpb, b_cluster, "d").unwrap().entry;
+        assert_eq!(moved.first_cluster() as u64, c_cluster);
+        let dirs = bpb.get_sector_by_cluster::<DirectoryEntry>(c_cluster);
+        assert_eq!(dirs[1].first_cluster() as u64, b_cluster);
+    }
+
+    #[test]
+    fn long_names_round_trip() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        // 16 entries fit in a cluster, so the last name spans two clusters
+        let long = "x".repeat(200);
+        let names = ["hello_world.txt", "Readme.md", "readme.txt", &long];
+        for name in names {
+            bpb.allocate_named_entry(root_cluster, name).unwrap();
+        }
+
+        let mut dir = FatDirectoryDescriptor::new(root_cluster);
+        for name in names {
+            assert_eq!(dir.next_entry(bpb).unwrap().name, name);
+        }
+        assert!(dir.next_entry(bpb).is_none());
+
+        let found = find_in(bpb, root_cluster, "HELLO_WORLD.TXT").unwrap();
+        assert_eq!(&found.entry.name, b"HELLO_~1TXT");
+        assert!(find_in(bpb, root_cluster, "hello_~1.txt").is_some());
+        let found = find_in(bpb, root_cluster, "readme.md").unwrap();
+        assert_eq!(&found.entry.name, b"README~1MD ");
+        let found = find_in(bpb, root_cluster, "README.TXT").unwrap();
+        assert_eq!(&found.entry.name, b"README  TXT");
+    }
+
+    #[test]
+    fn remove_entry_frees_long_name_entries() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        bpb.allocate_named_entry(root_cluster, "a long name")
+            .unwrap();
+        let found = find_in(bpb, root_cluster, "a long name").unwrap();
+        bpb.remove_entry(&found).unwrap();
+
+        let dirs = bpb.get_sector_by_cluster::<DirectoryEntry>(root_cluster);
+        assert!(dirs[0].is_free() && dirs[1].is_free());
+        assert!(FatDirectoryDescriptor::new(root_cluster)
+            .next_entry(bpb)
+            .is_none());
+    }
+
+    #[test]
+    fn open_entry_is_not_removed_or_moved() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        bpb.allocate_named_entry(root_cluster, "open.txt").unwrap();
+        let found = find_in(bpb, root_cluster, "open.txt").unwrap();
+        let fd = FatFileDescriptor::new(found.entry);
+        let fd2 = FatFileDescriptor::new(found.entry);
+        assert!(matches!(bpb.remove_entry(&found), Err(e) if matches!(e.code, Code::Busy)));
+        assert!(bpb
+            .move_entry(&found, None, root_cluster, "moved.txt")
+            .is_err());
+        drop(fd);
+        assert!(bpb.remove_entry(&found).is_err());
+        drop(fd2);
+        bpb.remove_entry(&found).unwrap();
+        assert!(found.entry.is_free());
+    }
+
+    #[test]
+    fn failed_move_keeps_replaced_entry() {
+        let mut image = volume_image();
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        let root_cluster = bpb.get_root_cluster() as u64;
+
+        bpb.allocate_named_entry(root_cluster, "a.txt").unwrap();
+        bpb.allocate_named_entry(root_cluster, "b.txt").unwrap();
+        let a = find_in(bpb, root_cluster, "a.txt").unwrap();
+        let b = find_in(bpb, root_cluster, "b.txt").unwrap();
+
+        // when the source is open,
+        let fd = FatFileDescriptor::new(a.entry);
+        let result = bpb.move_entry(&a, Some(&b), root_cluster, "b.txt");
+        assert!(matches!(result, Err(e) if matches!(e.code, Code::Busy)));
+        drop(fd);
+        // when the name cannot be stored,
+        let long = "x".repeat(300);
+        let result = bpb.move_entry(&a, Some(&b), root_cluster, &long);
+        assert!(matches!(result, Err(e) if matches!(e.code, Code::NameTooLong)));
+
+        // then neither entry is changed
+        assert!(find_in(bpb, root_cluster, "a.txt").is_some());
+        assert!(find_in(bpb, root_cluster, "b.txt").is_some());
+
+        bpb.move_entry(&a, Some(&b), root_cluster, "b.txt").unwrap();
+        assert!(find_in(bpb, root_cluster, "a.txt").is_none());
+        let mut dir = FatDirectoryDescriptor::new(root_cluster);
+        assert_eq!(dir.next_entry(bpb).unwrap().name, "b.txt");
+        assert!(dir.next_entry(bpb).is_none());
+    }
+
+    fn find_in(bpb: &Bpb, dir_cluster: u64, name: &str) -> Option<FoundEntry> {
+        let mut dir = FatDirectoryDescriptor::new(dir_cluster);
+        while let Some(found) = dir.next_entry(bpb) {
+            if found.name_matches(name) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    /// A FAT32 volume with 512-byte clusters 2 to 17, whose root directory is cluster 2.
+    fn volume_image() -> Vec<u32> {
+        const SECTOR_SIZE: usize = 512;
+        // a reserved sector, a FAT sector and 16 clusters
+        let mut image = vec![0_u32; 18 * SECTOR_SIZE / 4];
+        let bpb = unsafe { &mut *(image.as_mut_ptr() as *mut Bpb) };
+        bpb.bytes_per_sector = SECTOR_SIZE as u16;
+        bpb.sectors_per_cluster = 1;
+        bpb.reserved_sector_count = 1;
+        bpb.num_fats = 1;
+        bpb.fat_size_32 = 1;
+        bpb.root_cluster = 2;
+
+        let fat = &mut image[SECTOR_SIZE / 4..];
+        fat[0] = 0x0ffffff8;
+        fat[1] = END_OF_CLUSTER_CHAIN as u32;
+        fat[2] = END_OF_CLUSTER_CHAIN as u32;
+        image
+    }
+
     fn directory_entry() -> DirectoryEntry {
         DirectoryEntry {
             name: [0; 11],
@@ -0,0 +1,282 @@
+//! VFAT long file names.
+//!
+//! A long name is stored in UTF-16 across entries with the `LongName` attribute, which are placed
+//! just before the 8.3 entry in reverse order and carry the checksum of its 8.3 name.
+
+use crate::error::{Code, Error};
+use crate::make_error;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::char::REPLACEMENT_CHARACTER;
+use core::mem;
+
+pub(super) const CHARS_PER_ENTRY: usize = 13;
+/// Set to the order of the first physical entry, which holds the last part of the name.
+pub(super) const LAST_LONG_ENTRY: u8 = 0x40;
+const MAX_NAME_LEN: usize = 255;
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// NT flags of `DirectoryEntry::ntres` telling that the base name or the extension is lowercase.
+pub(super) const NTRES_LOWER_BASE: u8 = 0x08;
+pub(super) const NTRES_LOWER_EXT: u8 = 0x10;
+
+#[repr(packed)]
+pub(super) struct LongNameEntry {
+    ord: u8,
+    name1: [u16; 5],
+    attr: u8,
+    entry_type: u8,
+    checksum: u8,
+    name2: [u16; 6],
+    first_cluster_low: u16,
+    name3: [u16; 2],
+}
+
+impl LongNameEntry {
+    pub(super) fn new(ord: u8, checksum: u8, chars: &[u16; CHARS_PER_ENTRY]) -> Self {
+        Self {
+            ord,
+            name1: chars[..5].try_into().unwrap(),
+            attr: ATTR_LONG_NAME,
+            entry_type: 0,
+            checksum,
+            name2: chars[5..11].try_into().unwrap(),
+            first_cluster_low: 0,
+            name3: chars[11..].try_into().unwrap(),
+        }
+    }
+
+    fn chars(&self) -> [u16; CHARS_PER_ENTRY] {
+        let mut chars = [0; CHARS_PER_ENTRY];
+        chars[..5].copy_from_slice(&{ self.name1 });
+        chars[5..11].copy_from_slice(&{ self.name2 });
+        chars[11..].copy_from_slice(&{ self.name3 });
+        chars
+    }
+}
+
+/// The checksum of an 8.3 name which every long name entry of the file holds.
+pub(super) fn checksum(short_name: &[u8; 11]) -> u8 {
+    short_name
+        .iter()
+        .fold(0_u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+/// Collects long name entries, in the order they are stored, up to the 8.3 entry.
+#[derive(Default)]
+pub(super) struct LongNameBuilder {
+    units: Vec<u16>,
+    next_ord: u8,
+    checksum: u8,
+}
+
+impl LongNameBuilder {
+    pub(super) fn push(&mut self, entry: &LongNameEntry) {
+        let ord = entry.ord & !LAST_LONG_ENTRY;
+        if entry.ord & LAST_LONG_ENTRY != 0 {
+            self.units = vec![0; ord as usize * CHARS_PER_ENTRY];
+            self.next_ord = ord;
+            self.checksum = entry.checksum;
+        }
+        if ord == 0 || ord != self.next_ord || entry.checksum != self.checksum {
+            // an orphaned entry, whose 8.3 entry may have been removed by an old driver
+            self.clear();
+            return;
+        }
+
+        let begin = (ord as usize - 1) * CHARS_PER_ENTRY;
+        self.units[begin..begin + CHARS_PER_ENTRY].copy_from_slice(&entry.chars());
+        self.next_ord -= 1;
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.units.clear();
+        self.next_ord = 0;
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.units.is_empty()
+    }
+
+    /// Returns the long name if the collected entries are complete and belong to `short_name`.
+    pub(super) fn take(&mut self, short_name: &[u8; 11]) -> Option<String> {
+        let units = mem::take(&mut self.units);
+        let complete = self.next_ord == 0 && self.checksum == checksum(short_name);
+        self.clear();
+        if units.is_empty() || !complete {
+            return None;
+        }
+
+        let len = units.iter().position(|&u| u == 0).unwrap_or(units.len());
+        Some(
+            char::decode_utf16(units[..len].iter().copied())
+                .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+                .collect(),
+        )
+    }
+}
+
+/// Splits `name` into the characters of long name entries in the order they are stored.
+pub(super) fn encode(name: &str) -> Result<Vec<[u16; CHARS_PER_ENTRY]>, Error> {
+    let units = name.encode_utf16().collect::<Vec<_>>();
+    if units.is_empty() {
+        return Err(make_error!(Code::InvalidFormat));
+    }
+    if units.len() > MAX_NAME_LEN {
+        return Err(make_error!(Code::NameTooLong));
+    }
+
+    let mut entries = units
+        .chunks(CHARS_PER_ENTRY)
+        .map(|chunk| {
+            // the name is terminated by NUL and padded with 0xffff unless it fills the entry
+            let mut chars = [0xffff; CHARS_PER_ENTRY];
+            chars[..chunk.len()].copy_from_slice(chunk);
+            if chunk.len() < CHARS_PER_ENTRY {
+                chars[chunk.len()] = 0;
+            }
+            chars
+        })
+        .collect::<Vec<_>>();
+    entries.reverse();
+    Ok(entries)
+}
+
+/// Returns the 8.3 name and the NT lowercase flags if `name` can be stored without a long name.
+///
+/// Names mixing cases in the base name or the extension, such as "Readme.txt", need a long name.
+pub(super) fn to_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let valid = |s: &str, max_len: usize| s.len() <= max_len && s.bytes().all(is_short_name_char);
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) || name.ends_with('.') {
+        return None;
+    }
+
+    let mut ntres = 0;
+    for (part, flag) in [(base, NTRES_LOWER_BASE), (ext, NTRES_LOWER_EXT)] {
+        let has_lower = part.bytes().any(|c| c.is_ascii_lowercase());
+        let has_upper = part.bytes().any(|c| c.is_ascii_uppercase());
+        match (has_lower, has_upper) {
+            (true, true) => return None,
+            (true, false) => ntres |= flag,
+            _ => {}
+        }
+    }
+
+    let mut short_name = [b' '; 11];
+    short_name[..base.len()].copy_from_slice(base.as_bytes());
+    short_name[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    short_name.make_ascii_uppercase();
+    Some((short_name, ntres))
+}
+
+/// Makes the `n`-th candidate of the 8.3 alias of a long name, such as "HELLOW~1TXT".
+pub(super) fn numbered_short_name(name: &str, n: usize) -> [u8; 11] {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let to_short_chars = |s: &str| {
+        s.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| match u8::try_from(c) {
+                Ok(c) if is_short_name_char(c) => c.to_ascii_uppercase(),
+                _ => b'_',
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let tail = alloc::format!("~{}", n);
+    let base = to_short_chars(base);
+    let base_len = base.len().min(8 - tail.len());
+    let ext = to_short_chars(ext);
+
+    let mut short_name = [b' '; 11];
+    short_name[..base_len].copy_from_slice(&base[..base_len]);
+    short_name[base_len..base_len + tail.len()].copy_from_slice(tail.as_bytes());
+    let ext_len = ext.len().min(3);
+    short_name[8..8 + ext_len].copy_from_slice(&ext[..ext_len]);
+    short_name
+}
+
+fn is_short_name_char(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || b"$%'-_@~`!(){}^#&".contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::size_of;
+
+    #[test]
+    fn long_name_entry_is_as_large_as_directory_entry() {
+        assert_eq!(size_of::<LongNameEntry>(), 32);
+    }
+
+    #[test]
+    fn checksum_of_short_name() {
+        assert_eq!(checksum(b"HELLOW~1TXT"), 0x1b);
+        assert_eq!(checksum(b"           "), 0xf7);
+    }
+
+    #[test]
+    fn encode_and_build_round_trip() {
+        let name = "a long file name.txt";
+        let short_name = numbered_short_name(name, 1);
+        let checksum = checksum(&short_name);
+
+        let entries = encode(name).unwrap();
+        assert_eq!(entries.len(), 2);
+        let mut builder = LongNameBuilder::default();
+        for (i, chars) in entries.iter().enumerate() {
+            let ord = (entries.len() - i) as u8 | if i == 0 { LAST_LONG_ENTRY } else { 0 };
+            builder.push(&LongNameEntry::new(ord, checksum, chars));
+        }
+        assert_eq!(builder.take(&short_name).as_deref(), Some(name));
+    }
+
+    #[test]
+    fn build_rejects_mismatched_checksum() {
+        let entries = encode("hello_world.txt").unwrap();
+        let mut builder = LongNameBuilder::default();
+        builder.push(&LongNameEntry::new(2 | LAST_LONG_ENTRY, 0, &entries[0]));
+        builder.push(&LongNameEntry::new(1, 0, &entries[1]));
+        assert_eq!(builder.take(b"HELLO_~1TXT"), None);
+        assert!(builder.is_empty());
+    }
+
+    #[test]
+    fn encode_pads_last_entry() {
+        let entries = encode("ab").unwrap();
+        assert_eq!(entries[0][..4], [b'a' as u16, b'b' as u16, 0, 0xffff]);
+        assert!(encode("").is_err());
+        assert!(encode(&"a".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn short_name_of_8_3_names() {
+        assert_eq!(to_short_name("README.TXT"), Some((*b"README  TXT", 0)));
+        assert_eq!(
+            to_short_name("readme.txt"),
+            Some((*b"README  TXT", NTRES_LOWER_BASE | NTRES_LOWER_EXT))
+        );
+        assert_eq!(to_short_name("Makefile"), None);
+        assert_eq!(to_short_name("hello_world.txt"), None);
+        assert_eq!(to_short_name("a.b.c"), None);
+        assert_eq!(to_short_name(".profile"), None);
+        assert_eq!(to_short_name("a b"), None);
+    }
+
+    #[test]
+    fn numbered_short_names() {
+        assert_eq!(&numbered_short_name("hello world.txt", 1), b"HELLOW~1TXT");
+        assert_eq!(&numbered_short_name("hello world.txt", 12), b"HELLO~12TXT");
+        assert_eq!(&numbered_short_name(".profile", 1), b"PROFIL~1   ");
+        assert_eq!(&numbered_short_name("a+b.jpeg", 1), b"A_B~1   JPE");
+    }
+}
@@ -0,0 +1,208 @@
+//! Writes sectors of the volume modified in memory back to the block device the volume was read from.
+//!
+//! The whole volume stays in memory and modifications only mark sectors dirty.
+//! Dirty sectors are written back by `global::sync`, which is called when an application closes
+//! a file, after a syscall modifies a directory, after each command line of a terminal,
+//! and by the `sync` command.
+
+use crate::block::BlockDevice;
+use crate::error::Error;
+use crate::sync::Mutex;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::ops::Range;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+pub(super) struct Writeback {
+    device: Mutex<Box<dyn BlockDevice + Send>>,
+    dirty: DirtySectors,
+    bytes_per_sector: usize,
+    /// Sectors of the first FAT, which are also written to the other FATs.
+    fat: Range<usize>,
+    num_fats: usize,
+}
+
+impl Writeback {
+    /// `bytes_per_sector` must be a multiple of the block size of `device`.
+    pub(super) fn new(
+        device: Box<dyn BlockDevice + Send>,
+        num_sectors: usize,
+        bytes_per_sector: usize,
+        fat: Range<usize>,
+        num_fats: usize,
+    ) -> Self {
+        Self {
+            device: Mutex::new(device),
+            dirty: DirtySectors::new(num_sectors),
+            bytes_per_sector,
+            fat,
+            num_fats,
+        }
+    }
+
+    /// Marks the sectors holding `len` bytes from `offset` of the volume dirty.
+    pub(super) fn mark_dirty(&self, offset: usize, len: usize) {
+        if len == 0 {
+            return;
+        }
+        let first = offset / self.bytes_per_sector;
+        let last = (offset + len - 1) / self.bytes_per_sector;
+        self.dirty.mark(first..last + 1);
+    }
+
+    /// Writes the dirty sectors of `volume` to the device.
+    ///
+    /// Sectors failed to be written stay dirty.
+    pub(super) fn sync(&self, volume: &[u8]) -> Result<(), Error> {
+        let runs = self.dirty.take();
+        if runs.is_empty() {
+            return Ok(());
+        }
+        let mut device = self.device.lock();
+        for (i, run) in runs.iter().enumerate() {
+            if let Err(e) = self.write_run(device.as_mut(), volume, run.clone()) {
+                runs[i..]
+                    .iter()
+                    .for_each(|run| self.dirty.mark(run.clone()));
+                return Err(e);
+            }
+        }
+        device.flush()
+    }
+
+    fn write_run(
+        &self,
+        device: &mut dyn BlockDevice,
+        volume: &[u8],
+        sectors: Range<usize>,
+    ) -> Result<(), Error> {
+        let bytes =
+            &volume[sectors.start * self.bytes_per_sector..sectors.end * self.bytes_per_sector];
+        device.write_blocks(self.lba(device, sectors.start), bytes)?;
+
+        // keep the other FATs the same as the first one, which is the only one the driver updates
+        let fat_sectors = sectors.start.max(self.fat.start)..sectors.end.min(self.fat.end);
+        if fat_sectors.is_empty() {
+            return Ok(());
+        }
+        let fat_bytes = &volume
+            [fat_sectors.start * self.bytes_per_sector..fat_sectors.end * self.bytes_per_sector];
+        for i in 1..self.num_fats {
+            let sector = fat_sectors.start + i * self.fat.len();
+            device.write_blocks(self.lba(device, sector), fat_bytes)?;
+        }
+        Ok(())
+    }
+
+    fn lba(&self, device: &dyn BlockDevice, sector: usize) -> u64 {
+        (sector * self.bytes_per_sector / device.block_size()) as u64
+    }
+}
+
+/// A bitmap of sectors, which may be marked by any task without a lock.
+struct DirtySectors {
+    bits: Vec<AtomicU64>,
+}
+
+impl DirtySectors {
+    fn new(num_sectors: usize) -> Self {
+        Self {
+            bits: (0..(num_sectors + 63) / 64)
+                .map(|_| AtomicU64::new(0))
+                .collect(),
+        }
+    }
+
+    fn mark(&self, sectors: Range<usize>) {
+        for sector in sectors {
+            if let Some(bits) = self.bits.get(sector / 64) {
+                bits.fetch_or(1 << (sector % 64), Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Clears the bitmap and returns the dirty sectors as ranges of consecutive ones.
+    fn take(&self) -> Vec<Range<usize>> {
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        for (i, bits) in self.bits.iter().enumerate() {
+            let mut bits = bits.swap(0, Ordering::SeqCst);
+            while bits != 0 {
+                let sector = i * 64 + bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                match runs.last_mut() {
+                    Some(run) if run.end == sector => run.end += 1,
+                    _ => runs.push(sector..sector + 1),
+                }
+            }
+        }
+        runs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn dirty_sectors_are_taken_as_runs() {
+        let dirty = DirtySectors::new(200);
+        dirty.mark(3..5);
+        dirty.mark(4..6);
+        dirty.mark(62..66);
+        dirty.mark(199..200);
+        assert_eq!(dirty.take(), vec![3..6, 62..66, 199..200]);
+        assert!(dirty.take().is_empty());
+    }
+
+    #[test]
+    fn sync_writes_dirty_sectors_and_mirrors_fat() {
+        // sector 0: reserved, sectors 1-2: FAT 1, sectors 3-4: FAT 2, sectors 5-7: data
+        let volume = (0..8 * 512)
+            .map(|i| (i / 512) as u8 + 1)
+            .collect::<Vec<_>>();
+        let writeback = Writeback::new(Box::new(RamDisk(vec![0; 8 * 512])), 8, 512, 1..3, 2);
+
+        writeback.mark_dirty(512 + 10, 4);
+        writeback.mark_dirty(6 * 512 - 1, 2);
+        writeback.sync(&volume).unwrap();
+
+        let mut disk = writeback.device.lock();
+        let mut sectors = [0; 8];
+        for (i, sector) in sectors.iter_mut().enumerate() {
+            let mut buf = [0; 512];
+            disk.read_blocks(i as u64, &mut buf).unwrap();
+            *sector = buf[0];
+        }
+        // sector 3 receives the mirror of sector 1
+        assert_eq!(sectors, [0, 2, 0, 2, 0, 6, 7, 0]);
+    }
+
+    struct RamDisk(Vec<u8>);
+
+    impl BlockDevice for RamDisk {
+        fn block_size(&self) -> usize {
+            512
+        }
+
+        fn num_blocks(&self) -> u64 {
+            (self.0.len() / 512) as u64
+        }
+
+        fn read_blocks(&mut self, lba: u64, buf: &mut [u8]) -> Result<(), Error> {
+            let offset = lba as usize * 512;
+            buf.copy_from_slice(&self.0[offset..offset + buf.len()]);
+            Ok(())
+        }
+
+        fn write_blocks(&mut self, lba: u64, buf: &[u8]) -> Result<(), Error> {
+            let offset = lba as usize * 512;
+            self.0[offset..offset + buf.len()].copy_from_slice(buf);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+}
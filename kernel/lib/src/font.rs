@@ -148,15 +148,15 @@ pub fn convert_utf8_to_u32(bytes: &[u8]) -> u32 {
 extern "C" {
     static _binary_hankaku_bin_start: u8;
     static _binary_hankaku_bin_end: u8;
-    static _binary_hankaku_bin_size: u8;
 }
 
 unsafe fn get_font(c: char) -> Option<*mut u8> {
     let index = 16 * c as usize;
-    let size = (&_binary_hankaku_bin_size as *const u8) as usize;
+    let start = (&_binary_hankaku_bin_start as *const u8) as *mut u8;
+    // the end rather than the absolute `_binary_hankaku_bin_size`, which host tests cannot link
+    let size = (&_binary_hankaku_bin_end as *const u8) as usize - start as usize;
 
     if index < size {
-        let start = (&_binary_hankaku_bin_start as *const u8) as *mut u8;
         Some(start.add(index))
     } else {
         None
@@ -1,11 +1,15 @@
 use crate::segment::KERNEL_CS;
 use crate::x86_descriptor::SystemDescriptorType;
 use bit_field::BitField;
+use core::sync::atomic::{AtomicU64, Ordering};
 
 pub mod global {
-    use super::{InterruptDescriptor, InterruptDescriptorAttribute, InterruptVectorNumber};
+    use super::{
+        count_interrupt, InterruptDescriptor, InterruptDescriptorAttribute, InterruptVectorNumber,
+    };
     use crate::asm::global::{
-        exit_app, get_cr2, load_interrupt_descriptor_table, IntHandlerLAPICTimer,
+        exit_app, get_cr2, load_interrupt_descriptor_table, CopyUserMemoryFaultable,
+        CopyUserMemoryFixup, IntHandlerLAPICTimer,
     };
     use crate::font::{write_ascii, write_string};
     use crate::graphics::global::pixel_writer;
@@ -79,6 +83,7 @@ pub mod global {
     }
 
     extern "x86-interrupt" fn int_handler_xhci(_: InterruptStackFrame) {
+        count_interrupt(InterruptVectorNumber::XHCI as u8);
         task_manager()
             .send_message(main_task_id(), Message::new(MessageType::InterruptXhci))
             .unwrap();
@@ -86,64 +91,82 @@ pub mod global {
     }
 
     extern "x86-interrupt" fn int_handler_de(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#DE", &frame.value);
+        _fault_handler_no_error(0, "#DE", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_db(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#db", &frame.value);
+        _fault_handler_no_error(1, "#db", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_bp(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#BP", &frame.value);
+        _fault_handler_no_error(3, "#BP", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_of(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#OF", &frame.value);
+        _fault_handler_no_error(4, "#OF", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_br(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#BR", &frame.value);
+        _fault_handler_no_error(5, "#BR", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_ud(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#UD", &frame.value);
+        _fault_handler_no_error(6, "#UD", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_nm(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#NM", &frame.value);
+        _fault_handler_no_error(7, "#NM", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_df(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#DF", &frame.value, error_code);
+        _fault_handler_with_error(8, "#DF", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_ts(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#TS", &frame.value, error_code);
+        _fault_handler_with_error(10, "#TS", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_np(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#NP", &frame.value, error_code);
+        _fault_handler_with_error(11, "#NP", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_ss(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#SS", &frame.value, error_code);
+        _fault_handler_with_error(12, "#SS", &frame.value, error_code);
     }
-    extern "x86-interrupt" fn int_handler_gp(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#GP", &frame.value, error_code);
+    extern "x86-interrupt" fn int_handler_gp(mut frame: InterruptStackFrame, error_code: u64) {
+        if fixup_user_access(&mut frame) {
+            count_interrupt(13);
+            return;
+        }
+        _fault_handler_with_error(13, "#GP", &frame.value, error_code);
     }
-    extern "x86-interrupt" fn int_handler_pf(frame: InterruptStackFrame, error_code: u64) {
-        if handle_page_fault(error_code, get_cr2()).is_ok() {
+    extern "x86-interrupt" fn int_handler_pf(mut frame: InterruptStackFrame, error_code: u64) {
+        if handle_page_fault(error_code, get_cr2()).is_ok() || fixup_user_access(&mut frame) {
+            count_interrupt(14);
             return;
         }
-        _fault_handler_with_error("#PF", &frame.value, error_code);
+        _fault_handler_with_error(14, "#PF", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_mf(frame: InterruptStackFrame) {
-        _fault_handler_no_error("#MF", &frame.value);
+        _fault_handler_no_error(16, "#MF", &frame.value);
     }
     extern "x86-interrupt" fn int_handler_ac(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#AC", &frame.value, error_code);
+        _fault_handler_with_error(17, "#AC", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_mc(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#MC", &frame.value, error_code);
+        _fault_handler_with_error(18, "#MC", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_xm(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#XM", &frame.value, error_code);
+        _fault_handler_with_error(19, "#XM", &frame.value, error_code);
     }
     extern "x86-interrupt" fn int_handler_ve(frame: InterruptStackFrame, error_code: u64) {
-        _fault_handler_with_error("#VE", &frame.value, error_code);
+        _fault_handler_with_error(20, "#VE", &frame.value, error_code);
+    }
+
+    /// Makes a faulting copy between the kernel and an application return an error
+    /// instead of halting the kernel.
+    fn fixup_user_access(frame: &mut InterruptStackFrame) -> bool {
+        let cpl = frame.value.cs & 0x3;
+        if cpl != 0 || frame.value.rip != CopyUserMemoryFaultable as usize as u64 {
+            return false;
+        }
+
+        unsafe { frame.set_rip(CopyUserMemoryFixup as usize as u64) };
+        true
     }
 
-    fn _fault_handler_with_error(name: &str, frame: &InterruptFrame, error_code: u64) {
+    fn _fault_handler_with_error(vector: u8, name: &str, frame: &InterruptFrame, error_code: u64) {
+        count_interrupt(vector);
         kill_app(frame);
         print_frame(frame, name);
         write_string(pixel_writer(), 500, 16 * 4, "ERR", &COLOR_BLACK);
@@ -153,7 +176,8 @@ pub mod global {
         }
     }
 
-    fn _fault_handler_no_error(name: &str, frame: &InterruptFrame) {
+    fn _fault_handler_no_error(vector: u8, name: &str, frame: &InterruptFrame) {
+        count_interrupt(vector);
         kill_app(frame);
         print_frame(frame, name);
         loop {
@@ -215,6 +239,17 @@ pub struct InterruptStackFrame {
     value: InterruptFrame,
 }
 
+impl InterruptStackFrame {
+    /// Changes the address the handler returns to.
+    ///
+    /// # Safety
+    /// This relies on the x86-interrupt calling convention passing the frame by reference,
+    /// as `InterruptStackFrame::as_mut` of x86_64 crate does.
+    unsafe fn set_rip(&mut self, rip: u64) {
+        core::ptr::write_volatile(&mut self.value.rip, rip);
+    }
+}
+
 #[repr(C)]
 pub struct InterruptFrame {
     rip: u64,
@@ -283,3 +318,40 @@ pub enum InterruptVectorNumber {
     XHCI = 0x40,
     LAPICTimer = 0x41,
 }
+
+/// Vectors the IDT has handlers for, and their names.
+pub(crate) const HANDLED_VECTORS: [(u8, &str); 20] = [
+    (0, "#DE divide error"),
+    (1, "#DB debug"),
+    (3, "#BP breakpoint"),
+    (4, "#OF overflow"),
+    (5, "#BR bound range exceeded"),
+    (6, "#UD invalid opcode"),
+    (7, "#NM device not available"),
+    (8, "#DF double fault"),
+    (10, "#TS invalid TSS"),
+    (11, "#NP segment not present"),
+    (12, "#SS stack-segment fault"),
+    (13, "#GP general protection"),
+    (14, "#PF page fault"),
+    (16, "#MF x87 floating-point exception"),
+    (17, "#AC alignment check"),
+    (18, "#MC machine check"),
+    (19, "#XM SIMD floating-point exception"),
+    (20, "#VE virtualization exception"),
+    (InterruptVectorNumber::XHCI as u8, "xHCI"),
+    (InterruptVectorNumber::LAPICTimer as u8, "LAPIC timer"),
+];
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU64 = AtomicU64::new(0);
+/// How many times each vector has occurred since boot.
+static INTERRUPT_COUNTS: [AtomicU64; 256] = [ZERO; 256];
+
+pub(crate) fn count_interrupt(vector: u8) {
+    INTERRUPT_COUNTS[vector as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn interrupt_count(vector: u8) -> u64 {
+    INTERRUPT_COUNTS[vector as usize].load(Ordering::Relaxed)
+}
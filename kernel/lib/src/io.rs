@@ -1,16 +1,69 @@
+use crate::error::{Code, Error};
 use crate::fat::global::{boot_volume_image, boot_volume_image_mut};
 use crate::fat::FatFileDescriptor;
+use crate::make_error;
 use crate::terminal::file_descriptor::{PipeDescriptor, TerminalFileDescriptor};
+use crate::terminal::line_discipline::TerminalMode;
+use crate::terminal::pty::PtyDescriptor;
+use crate::vfs::devfs::{FrameBufferDescriptor, RandomDescriptor};
+use crate::vfs::procfs::ProcFileDescriptor;
+use crate::vfs::ramfs::RamFileDescriptor;
+use crate::vfs::DirectoryDescriptor;
 use core::fmt::Write;
 
 pub(crate) const STD_IN: usize = 0;
 pub(crate) const STD_OUT: usize = 1;
 pub(crate) const STD_ERR: usize = 2;
 
+// file type bits of `FileStat::mode`, the same values as `st_mode` of POSIX
+pub(crate) const S_IFIFO: u32 = 0o010000;
+pub(crate) const S_IFCHR: u32 = 0o020000;
+pub(crate) const S_IFDIR: u32 = 0o040000;
+pub(crate) const S_IFREG: u32 = 0o100000;
+
+/// Passed to applications by the `FileStat` syscall.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub(crate) struct FileStat {
+    pub(crate) size: u64,
+    pub(crate) mode: u32,
+}
+
+/// Maximum length of a file name excluding the terminating NUL.
+pub(crate) const NAME_MAX: usize = 255;
+
+/// `DirEntry::attr` of directories, the same value as the FAT attribute.
+pub(crate) const ATTR_DIRECTORY: u8 = 0x10;
+
+/// Passed to applications by the `ReadDirectory` syscall.
+#[repr(C)]
+pub(crate) struct DirEntry {
+    pub(crate) name: [u8; NAME_MAX + 1],
+    pub(crate) size: u32,
+    pub(crate) attr: u8,
+}
+
+pub(crate) enum SeekFrom {
+    Start(u64),
+    Current(i64),
+    End(i64),
+}
+
 pub(crate) enum FileDescriptor {
     Fat(FatFileDescriptor),
     Terminal(TerminalFileDescriptor),
     Pipe(PipeDescriptor),
+    Directory(DirectoryDescriptor),
+    Ram(RamFileDescriptor),
+    Proc(ProcFileDescriptor),
+    /// Discards writes and reads nothing.
+    Null,
+    /// Discards writes and reads zeros.
+    Zero,
+    Random(RandomDescriptor),
+    FrameBuffer(FrameBufferDescriptor),
+    /// Either side of a pseudo-terminal.
+    Pty(PtyDescriptor),
 }
 
 impl FileDescriptor {
@@ -19,6 +72,17 @@ impl FileDescriptor {
             FileDescriptor::Fat(fd) => fd.read(buf, boot_volume_image()),
             FileDescriptor::Terminal(fd) => fd.read(buf),
             FileDescriptor::Pipe(fd) => fd.read(buf),
+            FileDescriptor::Directory(_) => 0,
+            FileDescriptor::Ram(fd) => fd.read(buf),
+            FileDescriptor::Proc(fd) => fd.read(buf),
+            FileDescriptor::Null => 0,
+            FileDescriptor::Zero => {
+                buf.fill(0);
+                buf.len()
+            }
+            FileDescriptor::Random(fd) => fd.read(buf),
+            FileDescriptor::FrameBuffer(fd) => fd.read(buf),
+            FileDescriptor::Pty(fd) => fd.read(buf),
         }
     }
 
@@ -27,6 +91,12 @@ impl FileDescriptor {
             FileDescriptor::Fat(fd) => fd.write(buf, boot_volume_image_mut()),
             FileDescriptor::Terminal(fd) => fd.write(buf),
             FileDescriptor::Pipe(fd) => fd.write(buf),
+            FileDescriptor::Directory(_) => 0,
+            FileDescriptor::Ram(fd) => fd.write(buf),
+            FileDescriptor::Proc(_) => 0,
+            FileDescriptor::Null | FileDescriptor::Zero | FileDescriptor::Random(_) => buf.len(),
+            FileDescriptor::FrameBuffer(fd) => fd.write(buf),
+            FileDescriptor::Pty(fd) => fd.write(buf),
         }
     }
 
@@ -35,6 +105,17 @@ impl FileDescriptor {
             FileDescriptor::Fat(fd) => fd.load(buf, offset, boot_volume_image_mut()),
             FileDescriptor::Terminal(fd) => fd.load(buf, offset),
             FileDescriptor::Pipe(fd) => fd.load(buf, offset),
+            FileDescriptor::Directory(_) => 0,
+            FileDescriptor::Ram(fd) => fd.load(buf, offset),
+            FileDescriptor::Proc(fd) => fd.load(buf, offset),
+            FileDescriptor::Null => 0,
+            FileDescriptor::Zero => {
+                buf.fill(0);
+                buf.len()
+            }
+            FileDescriptor::Random(fd) => fd.read(buf),
+            FileDescriptor::FrameBuffer(fd) => fd.load(buf, offset),
+            FileDescriptor::Pty(fd) => fd.load(buf, offset),
         }
     }
 
@@ -43,7 +124,106 @@ impl FileDescriptor {
             FileDescriptor::Fat(fd) => fd.size(),
             FileDescriptor::Terminal(fd) => fd.size(),
             FileDescriptor::Pipe(fd) => fd.size(),
+            FileDescriptor::Directory(_) => 0,
+            FileDescriptor::Ram(fd) => fd.size(),
+            FileDescriptor::Proc(fd) => fd.size(),
+            FileDescriptor::Null | FileDescriptor::Zero | FileDescriptor::Random(_) => 0,
+            FileDescriptor::FrameBuffer(fd) => fd.size(),
+            FileDescriptor::Pty(fd) => fd.size(),
+        }
+    }
+
+    /// Only regular files and the frame buffer are seekable.
+    pub(crate) fn seek(&mut self, pos: SeekFrom) -> Result<usize, Error> {
+        let position = match self {
+            FileDescriptor::Fat(fd) => fd.position(),
+            FileDescriptor::Ram(fd) => fd.position(),
+            FileDescriptor::Proc(fd) => fd.position(),
+            FileDescriptor::FrameBuffer(fd) => fd.position(),
+            _ => return Err(make_error!(Code::NotSeekable)),
+        };
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(offset) => (position as i64, offset),
+            SeekFrom::End(offset) => (self.size() as i64, offset),
+        };
+        let new_offset = base
+            .checked_add(offset)
+            .filter(|&o| o >= 0)
+            .ok_or_else(|| make_error!(Code::IndexOutOfRange))?;
+        match self {
+            FileDescriptor::Fat(fd) => fd.seek(new_offset as usize, boot_volume_image()),
+            FileDescriptor::Ram(fd) => fd.seek(new_offset as usize),
+            FileDescriptor::Proc(fd) => fd.seek(new_offset as usize),
+            FileDescriptor::FrameBuffer(fd) => fd.seek(new_offset as usize),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn stat(&self) -> FileStat {
+        let mode = match self {
+            FileDescriptor::Fat(fd) if fd.is_directory() => S_IFDIR,
+            FileDescriptor::Fat(_) => S_IFREG,
+            FileDescriptor::Terminal(_) => S_IFCHR,
+            FileDescriptor::Pipe(_) => S_IFIFO,
+            FileDescriptor::Directory(_) => S_IFDIR,
+            FileDescriptor::Ram(_) | FileDescriptor::Proc(_) => S_IFREG,
+            FileDescriptor::Null
+            | FileDescriptor::Zero
+            | FileDescriptor::Random(_)
+            | FileDescriptor::FrameBuffer(_)
+            | FileDescriptor::Pty(_) => S_IFCHR,
+        };
+        FileStat {
+            size: self.size() as u64,
+            mode,
+        }
+    }
+
+    /// Returns the physical page to map at `offset` instead of a copy of the content,
+    /// for a file of device memory.
+    pub(crate) fn physical_page(&self, offset: usize) -> Option<u64> {
+        match self {
+            FileDescriptor::FrameBuffer(fd) => fd.physical_page(offset),
+            _ => None,
+        }
+    }
+
+    /// Returns how the terminal passes typed keys to the apps reading it.
+    pub(crate) fn terminal_mode(&self) -> Result<TerminalMode, Error> {
+        match self {
+            FileDescriptor::Terminal(fd) => Ok(fd.mode()),
+            FileDescriptor::Pty(fd) => Ok(fd.mode()),
+            _ => Err(make_error!(Code::NotTerminal)),
+        }
+    }
+
+    pub(crate) fn set_terminal_mode(&mut self, mode: TerminalMode) -> Result<(), Error> {
+        match self {
+            FileDescriptor::Terminal(fd) => fd.set_mode(mode),
+            FileDescriptor::Pty(fd) => fd.set_mode(mode),
+            _ => return Err(make_error!(Code::NotTerminal)),
         }
+        Ok(())
+    }
+
+    /// Returns the numbers of columns and rows of the terminal.
+    pub(crate) fn window_size(&self) -> Result<(usize, usize), Error> {
+        match self {
+            FileDescriptor::Terminal(fd) => Ok(fd.window_size()),
+            FileDescriptor::Pty(fd) => Ok(fd.window_size()),
+            _ => Err(make_error!(Code::NotTerminal)),
+        }
+    }
+
+    /// Only a pseudo-terminal can be resized this way. A terminal window is resized by its border.
+    pub(crate) fn set_window_size(&mut self, columns: usize, rows: usize) -> Result<(), Error> {
+        match self {
+            FileDescriptor::Pty(fd) => fd.set_window_size(columns, rows),
+            FileDescriptor::Terminal(_) => return Err(make_error!(Code::NotImplemented)),
+            _ => return Err(make_error!(Code::NotTerminal)),
+        }
+        Ok(())
     }
 
     pub(crate) fn read_delim(&mut self, delim: u8, buf: &mut [u8]) -> usize {
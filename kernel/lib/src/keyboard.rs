@@ -72,9 +72,19 @@ const KEYCODE_MAP_SHIFT: [char; 256] = [
     '\0', '\0', '\0', '\0', '\0', '\0', '\0', '\0', // 248
 ];
 
+pub const KEY_C: u8 = 6;
 pub const KEY_D: u8 = 7;
 pub const KEY_Q: u8 = 20;
 pub const KEY_F2: u8 = 59;
+pub const KEY_HOME: u8 = 74;
+pub const KEY_PAGE_UP: u8 = 75;
+pub const KEY_DELETE: u8 = 76;
+pub const KEY_END: u8 = 77;
+pub const KEY_PAGE_DOWN: u8 = 78;
+pub const KEY_RIGHT: u8 = 79;
+pub const KEY_LEFT: u8 = 80;
+pub const KEY_DOWN: u8 = 81;
+pub const KEY_UP: u8 = 82;
 pub const L_CONTROL_BIT_MASK: u8 = 0b00000001;
 pub const L_SHIFT_BIT_MASK: u8 = 0b00000010;
 pub const L_ALT_BIT_MASK: u8 = 0b00000100;
@@ -107,6 +117,6 @@ pub(crate) fn is_shift_key_inputted(modifier: u8) -> bool {
     (modifier & (L_SHIFT_BIT_MASK | R_SHIFT_BIT_MASK)) != 0
 }
 
-pub(crate) fn is_control_key_inputted(modifier: u8) -> bool {
+pub fn is_control_key_inputted(modifier: u8) -> bool {
     (modifier & (L_CONTROL_BIT_MASK | R_CONTROL_BIT_MASK)) != 0
 }
@@ -82,6 +82,7 @@ pub struct Layer {
     position: Vector2D<i32>,
     window: Arc<Mutex<Window>>,
     draggable: bool,
+    resizable: bool,
 }
 
 impl Layer {
@@ -91,6 +92,7 @@ impl Layer {
             position: Vector2D::new(0, 0),
             window,
             draggable: false,
+            resizable: false,
         }
     }
 
@@ -119,6 +121,17 @@ impl Layer {
         self.draggable
     }
 
+    /// Lets the window be resized by dragging its border. The task of the layer is sent
+    /// `MessageType::WindowResize` and resizes the window itself.
+    pub fn set_resizable(&mut self, resizable: bool) -> &mut Layer {
+        self.resizable = resizable;
+        self
+    }
+
+    pub fn is_resizable(&self) -> bool {
+        self.resizable
+    }
+
     pub fn move_(&mut self, pos: Vector2D<i32>) -> &mut Layer {
         self.position = pos;
         self
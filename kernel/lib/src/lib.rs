@@ -12,6 +12,7 @@ use core::str::Utf8Error;
 pub mod acpi;
 mod app_event;
 pub mod asm;
+pub mod block;
 pub mod console;
 mod elf;
 pub mod error;
@@ -38,6 +39,8 @@ pub mod syscall;
 pub mod task;
 pub mod terminal;
 pub mod timer;
+mod user_access;
+pub mod vfs;
 pub mod window;
 mod x86_descriptor;
 
@@ -17,15 +17,24 @@ impl Message {
 #[derive(Debug, PartialEq, Eq)]
 pub enum MessageType {
     InterruptXhci,
-    TimerTimeout { timeout: u64, value: i32 },
+    TimerTimeout {
+        timeout: u64,
+        value: i32,
+    },
     KeyPush(KeyPushMessage),
     Layer(LayerMessage),
     LayerFinish,
     MouseMove(MouseMoveMessage),
     MouseButton(MouseButtonMessage),
+    MouseWheel(MouseWheelMessage),
     WindowActive(WindowActiveMode),
     WindowClose(WindowCloseMessage),
+    WindowResize(WindowResizeMessage),
     Pipe(PipeMessage),
+    /// Sent by `TaskManager::interrupt` to wake up the interrupted task.
+    Interrupt,
+    /// Wakes up the task waiting to read a pseudo-terminal, which the other side has changed.
+    PtyReady,
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -70,6 +79,14 @@ pub struct MouseButtonMessage {
     pub button: i32,
 }
 
+/// `delta` is positive when the wheel is turned away from the user.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MouseWheelMessage {
+    pub x: i32,
+    pub y: i32,
+    pub delta: i32,
+}
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum WindowActiveMode {
     Activate,
@@ -89,6 +106,17 @@ pub struct WindowCloseMessage {
     pub layer_id: LayerID,
 }
 
+/// Sent when the border of a resizable window has been dragged.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WindowResizeMessage {
+    pub layer_id: LayerID,
+    /// how much the mouse has been dragged
+    pub diff: Vector2D<i32>,
+    /// the dragged edges, which are -1 for the left or the top edge and 1 for the right or the
+    /// bottom edge
+    pub edges: Vector2D<i32>,
+}
+
 // This trait is defined here because the app crate also uses app_event::MouseMove.
 impl From<KeyPushMessage> for app_event::KeyPush {
     fn from(m: KeyPushMessage) -> Self {
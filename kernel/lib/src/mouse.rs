@@ -2,7 +2,8 @@ use crate::graphics::{PixelColor, PixelWriter, Vector2D, COLOR_BLACK, COLOR_WHIT
 use crate::layer::global::layer_manager;
 use crate::layer::LayerID;
 use crate::message::{
-    Message, MessageType, MouseButtonMessage, MouseMoveMessage, WindowCloseMessage,
+    Message, MessageType, MouseButtonMessage, MouseMoveMessage, MouseWheelMessage,
+    WindowCloseMessage, WindowResizeMessage,
 };
 use crate::task::{TaskID, TaskManager};
 use crate::window::WindowRegion;
@@ -69,6 +70,8 @@ pub struct Mouse {
     layer_id: LayerID,
     position: Vector2D<i32>,
     drag_layer_id: Option<LayerID>,
+    /// the window whose border is being dragged, which is resized when the button is released
+    resize: Option<WindowResizeMessage>,
     previous_buttons: u8,
 }
 
@@ -78,6 +81,7 @@ impl Mouse {
             layer_id,
             position: Vector2D::new(0, 0),
             drag_layer_id: None,
+            resize: None,
             previous_buttons: 0,
         }
     }
@@ -92,6 +96,7 @@ impl Mouse {
         buttons: u8,
         displacement_x: i8,
         displacement_y: i8,
+        wheel: i8,
         screen_size: Vector2D<i32>,
         task_manager: &mut TaskManager,
     ) {
@@ -121,7 +126,14 @@ impl Mouse {
                 {
                     WindowRegion::TitleBar => self.drag_layer_id = Some(layer.id()),
                     WindowRegion::CloseButton => close_layer_id = Some(layer.id()),
-                    WindowRegion::Border => {}
+                    WindowRegion::Border(edges) if layer.is_resizable() => {
+                        self.resize = Some(WindowResizeMessage {
+                            layer_id: layer.id(),
+                            diff: Vector2D::new(0, 0),
+                            edges,
+                        })
+                    }
+                    WindowRegion::Border(_) => {}
                     WindowRegion::Other => {}
                 }
             }
@@ -133,11 +145,17 @@ impl Mouse {
                     .lock()
                     .move_relative(drag_layer_id, pos_diff);
             }
+            if let Some(resize) = &mut self.resize {
+                resize.diff += pos_diff;
+            }
         } else if previous_left_pressed && !left_pressed {
             self.drag_layer_id = None;
+            if let Some(resize) = self.resize.take() {
+                send_resize_message(resize, task_manager);
+            }
         }
 
-        if self.drag_layer_id == None {
+        if self.drag_layer_id == None && self.resize.is_none() {
             if close_layer_id.is_some() {
                 send_close_message(task_manager);
             } else {
@@ -148,6 +166,9 @@ impl Mouse {
                     self.previous_buttons,
                     task_manager,
                 );
+                if wheel != 0 {
+                    send_wheel_message(new_pos, wheel, task_manager);
+                }
             }
         }
 
@@ -230,6 +251,24 @@ pub fn send_mouse_message(
     }
 }
 
+fn send_wheel_message(newpos: Vector2D<i32>, wheel: i8, task_manager: &mut TaskManager) {
+    let (layer_id, task_id) = match find_active_layer_task() {
+        None => return,
+        Some(pair) => pair,
+    };
+    let relpos = match layer_manager().lock().get_layer(layer_id) {
+        None => return,
+        Some(l) => newpos - l.position(),
+    };
+
+    let msg = Message::new(MessageType::MouseWheel(MouseWheelMessage {
+        x: relpos.x,
+        y: relpos.y,
+        delta: wheel as i32,
+    }));
+    let _ = task_manager.send_message(task_id, msg);
+}
+
 fn send_close_message(task_manager: &mut TaskManager) {
     let (layer_id, task_id) = match find_active_layer_task() {
         None => return,
@@ -240,6 +279,21 @@ fn send_close_message(task_manager: &mut TaskManager) {
     let _ = task_manager.send_message(task_id, message);
 }
 
+fn send_resize_message(resize: WindowResizeMessage, task_manager: &mut TaskManager) {
+    if resize.diff.x * resize.edges.x == 0 && resize.diff.y * resize.edges.y == 0 {
+        return;
+    }
+    let task_id = match layer_manager()
+        .lock()
+        .get_task_id_by_layer_id(resize.layer_id)
+    {
+        None => return,
+        Some(&id) => id,
+    };
+    let message = Message::new(MessageType::WindowResize(resize));
+    let _ = task_manager.send_message(task_id, message);
+}
+
 pub fn new_mouse_cursor_window(pixel_format: PixelFormat) -> Window {
     let mut window = Window::new(
         MOUSE_CURSOR_SHAPE[0].len(),
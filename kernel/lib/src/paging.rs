@@ -1,5 +1,6 @@
 use crate::asm::global::invalidate_tlb;
-use crate::error::Error;
+use crate::error::{Code, Error};
+use crate::make_error;
 use crate::memory_manager::global::MEMORY_MANAGER;
 use crate::memory_manager::{FrameID, BYTES_PER_FRAME};
 use bit_field::BitField;
@@ -93,9 +94,14 @@ impl PageMapEntry {
     }
 
     // uint64_t : 3;
-    // pub fn _a(&self) {
-    //     self.0.get_bits(9..12) as u64
-    // }
+    /// Uses the first bit available to software to mark a page of device memory,
+    /// whose frame is not owned by the memory manager.
+    pub fn device(&self) -> bool {
+        self.0.get_bit(9)
+    }
+    pub fn set_device(&mut self, device: bool) {
+        self.0.set_bit(9, device);
+    }
 
     // uint64_t addr : 40;
     pub fn addr(&self) -> u64 {
@@ -219,6 +225,46 @@ impl PageMapEntry {
         Ok(e)
     }
 
+    /// Returns the level 1 entry of `addr` if it is mapped and accessible from user mode.
+    pub(crate) fn find_user_page<'a>(
+        addr: LinearAddress4Level,
+        cr_3: u64,
+    ) -> Option<&'a mut PageMapEntry> {
+        let mut page_map = cr_3 as *mut u64 as *mut PageMapEntry;
+        for level in (1..=4).rev() {
+            let entry = unsafe { page_map.add(addr.part(level) as usize).as_mut() }?;
+            if entry.present() == 0 || entry.user() == 0 {
+                return None;
+            }
+            if level == 1 {
+                return Some(entry);
+            }
+            if entry.huge_page() != 0 {
+                return None;
+            }
+            page_map = entry.pointer();
+        }
+        None
+    }
+
+    /// Maps the page of `addr` to the device memory at `phys_addr` for applications.
+    pub(crate) fn map_device_page(
+        addr: LinearAddress4Level,
+        phys_addr: u64,
+        cr_3: u64,
+    ) -> Result<(), Error> {
+        Self::setup_page_maps(addr, 1, true, cr_3)?;
+        let entry =
+            Self::find_user_page(addr, cr_3).ok_or_else(|| make_error!(Code::IndexOutOfRange))?;
+        // release the frame allocated by setup_page_maps
+        let frame = FrameID::new(entry.pointer() as usize / BYTES_PER_FRAME);
+        MEMORY_MANAGER.lock().free(frame, 1)?;
+        entry.set_pointer(phys_addr as *const PageMapEntry);
+        entry.set_device(true);
+        invalidate_tlb(addr.value());
+        Ok(())
+    }
+
     pub fn clean_page_maps(addr: LinearAddress4Level, cr3: u64) -> Result<(), Error> {
         let pm4_table = cr3 as *mut u64 as *mut PageMapEntry;
         Self::clean_page_map(pm4_table, 4, addr)
@@ -239,7 +285,7 @@ impl PageMapEntry {
                 Self::clean_page_map(entry.pointer(), page_map_level - 1, addr)?;
             }
 
-            if entry.writable() {
+            if entry.writable() && !entry.device() {
                 let entry_addr = entry.pointer() as usize;
                 let map_frame = FrameID::new(entry_addr / BYTES_PER_FRAME);
                 MEMORY_MANAGER.lock().free(map_frame, 1)?;
@@ -363,7 +409,7 @@ pub mod global {
     use crate::paging::{set_page_content, LinearAddress4Level, PageMapEntry};
     use crate::sync::Mutex;
     use crate::task::global::task_manager;
-    use crate::task::{FileMapping, Task};
+    use crate::task::FileMapping;
     use core::ffi::c_void;
     use core::slice;
 
@@ -398,8 +444,13 @@ pub mod global {
     }
 
     pub(crate) fn reset_cr3() {
+        set_cr3(kernel_cr3())
+    }
+
+    /// The PML4 table shared by tasks that do not run applications
+    pub(crate) fn kernel_cr3() -> u64 {
         let pm4_table = PML4_TABLE.lock();
-        set_cr3(&pm4_table.0[0] as *const _ as u64)
+        &pm4_table.0[0] as *const _ as u64
     }
 
     pub(crate) fn handle_page_fault(error_code: u64, causal_addr: u64) -> Result<(), Error> {
@@ -418,7 +469,7 @@ pub mod global {
             PageMapEntry::setup_page_maps(LinearAddress4Level::new(causal_addr), 1, true, get_cr3())
         } else if let Some(fm) = task.find_file_mapping(causal_addr) {
             let fm = fm.clone();
-            prepare_page_cache(task, fm, causal_addr)
+            prepare_page_cache(fm, causal_addr)
         } else {
             Err(make_error!(Code::IndexOutOfRange))
         }
@@ -430,26 +481,22 @@ pub mod global {
         MEMORY_MANAGER.lock().free(frame_id, 1)
     }
 
-    pub(crate) fn prepare_page_cache(
-        task: &Task,
-        fm: FileMapping,
-        causal_vaddr: u64,
-    ) -> Result<(), Error> {
+    pub(crate) fn prepare_page_cache(fm: FileMapping, causal_vaddr: u64) -> Result<(), Error> {
         let mut page_vaddr = LinearAddress4Level::new(causal_vaddr);
         page_vaddr.set_offset(0);
+        let file_offset = page_vaddr.value() - fm.vaddr_begin;
+        if let Some(phys_addr) = fm.file.lock().physical_page(file_offset as usize) {
+            return PageMapEntry::map_device_page(page_vaddr, phys_addr, get_cr3());
+        }
         PageMapEntry::setup_page_maps(page_vaddr, 1, true, get_cr3())?;
 
-        let file_offset = page_vaddr.value() - fm.vaddr_begin;
         let page_cache =
             unsafe { slice::from_raw_parts_mut(page_vaddr.value() as *mut u64 as *mut u8, 4096) };
-        task.get_file(fm.fd)
-            .unwrap()
-            .lock()
-            .load(page_cache, file_offset as usize);
+        fm.file.lock().load(page_cache, file_offset as usize);
         Ok(())
     }
 
-    fn copy_on_page(causal_addr: u64) -> Result<(), Error> {
+    pub(crate) fn copy_on_page(causal_addr: u64) -> Result<(), Error> {
         let p = PageMapEntry::new_page_map()?;
         let aligned_addr = causal_addr & 0xffff_ffff_ffff_f000;
         unsafe { memcpy(p as *mut c_void, aligned_addr as *const c_void, 4096) };
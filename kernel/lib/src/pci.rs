@@ -54,10 +54,21 @@ impl Device {
         }
     }
 
-    fn vendor_id(&self) -> u16 {
+    pub fn vendor_id(&self) -> u16 {
         read_vendor_id(self.bus, self.device, self.function)
     }
 
+    pub fn device_id(&self) -> u16 {
+        read_device_id(self.bus, self.device, self.function)
+    }
+
+    /// Lets the device respond to I/O space accesses and initiate DMA.
+    pub fn enable_io_and_bus_master(&self) {
+        // the upper half is the status register, whose bits are cleared by writing 1
+        let command = read_conf_reg(self, 0x04) & 0xffff;
+        write_conf_reg(self, 0x04, command | 0b101);
+    }
+
     pub fn is_xhc(&self) -> bool {
         self.class_code.is_match_all(0x0c, 0x03, 0x30)
     }
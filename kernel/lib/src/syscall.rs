@@ -1,28 +1,38 @@
-use crate::app_event::{AppEvent, AppEventArg, AppEventType, TimerTimeout};
-use crate::asm::global::{write_msr, SyscallEntry};
-use crate::error::Code;
-use crate::fat::global::{boot_volume_image, find_file};
-use crate::fat::{DirectoryEntry, FatFileDescriptor};
+use crate::app_event::{AppEvent, AppEventArg, AppEventType, TerminalResize, TimerTimeout};
+use crate::asm::global::{exit_app, write_msr, SyscallEntry};
+use crate::error::{Code, Error};
+use crate::fat::resolve_path;
 use crate::font::write_string;
 use crate::graphics::global::frame_buffer_config;
 use crate::graphics::{fill_rectangle, PixelColor, PixelWriter, Vector2D};
-use crate::io::FileDescriptor;
+use crate::io::{DirEntry, FileDescriptor, SeekFrom, ATTR_DIRECTORY, NAME_MAX, S_IFDIR};
 use crate::keyboard::{is_control_key_inputted, KEY_Q};
 use crate::layer::global::layer_manager;
 use crate::layer::LayerID;
+use crate::make_error;
 use crate::message::MessageType;
 use crate::msr::{IA32_EFFR, IA32_FMASK, IA32_LSTAR, IA32_STAR};
-use crate::rust_official::c_str::CStr;
-use crate::rust_official::cchar::c_char;
 use crate::sync::{Mutex, MutexGuard};
 use crate::task::global::task_manager;
-use crate::task::FileMapping;
+use crate::task::{FileMapping, TaskID};
+use crate::terminal::lib::{resize_terminal, spawn_app};
+use crate::terminal::line_discipline::{TerminalMode, MODE_NONBLOCK};
+use crate::terminal::pty;
 use crate::timer::global::{current_tick, do_with_timer_manager};
 use crate::timer::{Timer, TIMER_FREQ};
+use crate::user_access::{
+    copy_from_user, copy_to_user, copy_value_to_user, strncpy_from_user, verify_user_range,
+};
+use crate::vfs;
+use crate::vfs::DirectoryDescriptor;
 use crate::Window;
+use alloc::collections::BTreeMap;
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::arch::asm;
-use core::{mem, slice};
+use core::{cmp, mem};
 use log::{debug, log, Level};
 
 type SyscallFuncType = fn(u64, u64, u64, u64, u64, u64) -> SyscallResult;
@@ -32,16 +42,50 @@ const EPERM: i32 = 1; // Operation not permitted (POSIX.1-2001).
 const ENOENT: i32 = 2; // No such file or directory
 const E2BIG: i32 = 7; // Argument list too long
 const EBADF: i32 = 9; // Bad file descriptor
+const ECHILD: i32 = 10; // No child processes
+const ENOMEM: i32 = 12; // Cannot allocate memory
 const EFAULT: i32 = 14; // Bad address
+const EBUSY: i32 = 16; // Device or resource busy
+const EEXIST: i32 = 17; // File exists
+const EXDEV: i32 = 18; // Invalid cross-device link
+const ENOTDIR: i32 = 20; // Not a directory
 const EISDIR: i32 = 21; // Is a directory
 const EINVAL: i32 = 22; // Invalid argument
+const ENOTTY: i32 = 25; // Inappropriate I/O control operation
 const ENOSPC: i32 = 28; // No space left on device
+const ESPIPE: i32 = 29; // Illegal seek
+const EROFS: i32 = 30; // Read-only file system
+const ENOTEMPTY: i32 = 90; // Directory not empty (the value of newlib, not of Linux)
+const ENAMETOOLONG: i32 = 91; // File name too long (the value of newlib, not of Linux)
+
+pub(crate) const SIGINT: i32 = 2; // Interrupt from keyboard
 
 const O_RDONLY: i32 = 0x0000; /* open for reading only */
 const O_WRONLY: i32 = 0x0001; /* open for writing only */
 const O_RDWR: i32 = 0x0002; /* open for reading and writing */
 const O_ACCMODE: i32 = 0x0003; /* mask for above modes */
 const O_CREAT: i32 = 0x00000200; /* create if nonexistant */
+const O_TRUNC: i32 = 0x00000400; /* truncate to zero length */
+
+const SEEK_SET: u64 = 0;
+const SEEK_CUR: u64 = 1;
+const SEEK_END: u64 = 2;
+
+// requests of `io_control`
+const TC_GET_MODE: u64 = 0;
+const TC_SET_MODE: u64 = 1;
+const TC_GET_WINDOW_SIZE: u64 = 2;
+const TC_SET_WINDOW_SIZE: u64 = 3;
+
+/// Size of the kernel buffer `read_file` reads into at a time.
+const READ_CHUNK_SIZE: usize = 4096;
+
+/// Maximum total size of the arguments and the environment passed to `spawn`,
+/// including their pointers.
+const ARG_MAX: usize = 128 * 1024;
+
+/// Maximum length of strings passed from applications, excluding the terminating NUL.
+const MAX_STRING_LEN: usize = 1024;
 
 #[repr(C)]
 struct SyscallResult {
@@ -77,14 +121,12 @@ fn log_string(log_level: u64, s: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) ->
         _ => return SyscallResult::err(0, EPERM),
     };
 
-    let c_str = unsafe { c_str_from(s) };
-    let len = c_str.to_bytes().len();
-    if len > 1024 {
-        return SyscallResult::err(0, E2BIG);
-    }
-    let str = str_from(c_str.to_bytes());
+    let str = match string_from_user(s) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
     log!(log_level, "{}", str);
-    SyscallResult::ok(len as u64)
+    SyscallResult::ok(str.len() as u64)
 }
 
 fn put_string(fd: u64, buf: u64, count: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
@@ -99,9 +141,12 @@ fn put_string(fd: u64, buf: u64, count: u64, _a4: u64, _a5: u64, _a6: u64) -> Sy
     match task.get_file(fd as usize) {
         None => SyscallResult::err(0, EBADF),
         Some(fd) => {
-            let buf = buf as *mut u64 as *mut u8;
-            let buf = unsafe { slice::from_raw_parts(buf, count as usize) };
-            let written_size = fd.lock().write(buf);
+            let mut kernel_buf = [0_u8; 1024];
+            let kernel_buf = &mut kernel_buf[..count as usize];
+            if let Err(e) = copy_from_user(kernel_buf, buf) {
+                return SyscallResult::err(0, user_access_errno(e));
+            }
+            let written_size = fd.lock().write(kernel_buf);
             SyscallResult::ok(written_size as u64)
         }
     }
@@ -115,13 +160,15 @@ fn exit(status: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> Syscal
 }
 
 fn open_window(w: u64, h: u64, x: u64, y: u64, title: u64, _a6: u64) -> SyscallResult {
-    let c_str = unsafe { c_str_from(title) };
-    let str = str_from(c_str.to_bytes());
+    let title = match string_from_user(title) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
     let window = Window::new_with_title(
         w as usize,
         h as usize,
         frame_buffer_config().pixel_format,
-        str,
+        &title,
     );
 
     unsafe { asm!("cli") };
@@ -149,15 +196,17 @@ fn win_write_string(
     _a6: u64,
 ) -> SyscallResult {
     let color = PixelColor::from(color as u32);
-    let c_str = unsafe { c_str_from(text) };
-    let str = str_from(c_str.to_bytes());
+    let text = match string_from_user(text) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
 
     do_win_func(layer_id_flags, |mut window| {
         write_string(
             &mut window.normal_window_writer(),
             x as i32,
             y as i32,
-            str,
+            &text,
             &color,
         );
         SyscallResult::ok(0)
@@ -269,11 +318,18 @@ fn close_window(
 }
 
 fn read_event(app_events: u64, len: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
-    if app_events < 0x8000_0000_0000_0000 {
-        return SyscallResult::err(0, EFAULT);
-    }
-    let app_events = app_events as *mut u64 as *mut AppEvent;
     let len = len as usize;
+    let event_size = mem::size_of::<AppEvent>();
+    let verified = len
+        .checked_mul(event_size)
+        .ok_or_else(|| make_error!(Code::BadAddress))
+        .and_then(|size| verify_user_range(app_events, size, true));
+    if let Err(e) = verified {
+        return SyscallResult::err(0, user_access_errno(e));
+    }
+    let write_event = |i: usize, event: AppEvent| {
+        copy_value_to_user(app_events + (i * event_size) as u64, &event)
+    };
 
     unsafe { asm!("cli") };
     let task = task_manager().current_task_mut();
@@ -297,61 +353,78 @@ fn read_event(app_events: u64, len: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64)
             break;
         };
 
-        match msg.m_type {
+        let event = match msg.m_type {
             MessageType::KeyPush(arg) => {
-                let event = unsafe { app_events.add(i).as_mut() }
-                    .expect("failed to convert to AppEvent Ref");
                 if arg.keycode == KEY_Q && is_control_key_inputted(arg.modifier) {
-                    event.type_ = AppEventType::Quit;
-                    i += 1;
+                    Some(AppEvent {
+                        type_: AppEventType::Quit,
+                        ..Default::default()
+                    })
                 } else {
-                    event.type_ = AppEventType::KeyPush;
-                    event.arg = AppEventArg {
-                        key_push: arg.into(),
-                    };
-                    i += 1;
+                    Some(AppEvent {
+                        type_: AppEventType::KeyPush,
+                        arg: AppEventArg {
+                            key_push: arg.into(),
+                        },
+                    })
                 }
             }
-            MessageType::MouseMove(arg) => {
-                let event = unsafe { app_events.add(i).as_mut() }
-                    .expect("failed to convert to AppEvent Ref");
-                event.type_ = AppEventType::MouseMove;
-                event.arg = AppEventArg {
+            MessageType::MouseMove(arg) => Some(AppEvent {
+                type_: AppEventType::MouseMove,
+                arg: AppEventArg {
                     mouse_move: arg.into(),
-                };
-                i += 1;
-            }
-            MessageType::MouseButton(arg) => {
-                let event = unsafe { app_events.add(i).as_mut() }
-                    .expect("failed to convert to AppEvent Ref");
-                event.type_ = AppEventType::MouseButton;
-                event.arg = AppEventArg {
+                },
+            }),
+            MessageType::MouseButton(arg) => Some(AppEvent {
+                type_: AppEventType::MouseButton,
+                arg: AppEventArg {
                     mouse_button: arg.into(),
-                };
-                i += 1;
-            }
+                },
+            }),
             MessageType::TimerTimeout { timeout, value } => {
-                let event = unsafe { app_events.add(i).as_mut() }
-                    .expect("failed to convert to AppEvent Ref");
                 let is_created_by_app = value < 0;
                 if is_created_by_app {
-                    event.type_ = AppEventType::TimerTimeout;
-                    event.arg = AppEventArg {
-                        timer_timeout: TimerTimeout {
-                            timeout,
-                            value: -value,
+                    Some(AppEvent {
+                        type_: AppEventType::TimerTimeout,
+                        arg: AppEventArg {
+                            timer_timeout: TimerTimeout {
+                                timeout,
+                                value: -value,
+                            },
                         },
-                    };
-                    i += 1;
+                    })
+                } else {
+                    None
                 }
             }
-            MessageType::WindowClose(_) => {
-                let event = unsafe { app_events.add(i).as_mut() }
-                    .expect("failed to convert to AppEvent Ref");
-                event.type_ = AppEventType::Quit;
-                i += 1;
+            MessageType::WindowClose(_) => Some(AppEvent {
+                type_: AppEventType::Quit,
+                ..Default::default()
+            }),
+            MessageType::WindowResize(arg) => {
+                resize_terminal(arg).map(|(columns, rows)| AppEvent {
+                    type_: AppEventType::TerminalResize,
+                    arg: AppEventArg {
+                        terminal_resize: TerminalResize {
+                            columns: columns as u32,
+                            rows: rows as u32,
+                        },
+                    },
+                })
+            }
+            // returns to `SyscallEntry`, which makes the app exit
+            MessageType::Interrupt => break,
+            _ => {
+                debug!("uncaught event type: {:?}", msg.m_type);
+                None
+            }
+        };
+
+        if let Some(event) = event {
+            if let Err(e) = write_event(i, event) {
+                return SyscallResult::err(i as u64, user_access_errno(e));
             }
-            _ => debug!("uncaught event type: {:?}", msg.m_type),
+            i += 1;
         }
     }
 
@@ -388,7 +461,10 @@ fn create_timer(
 }
 
 fn open_file(path: u64, flag: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
-    let path = unsafe { c_str_from(path) }.to_str().unwrap();
+    let path = match string_from_user(path) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
     let flags = flag as i32;
     unsafe { asm!("cli") };
     let task = task_manager().current_task_mut();
@@ -398,44 +474,46 @@ fn open_file(path: u64, flag: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> Sy
         return SyscallResult::ok(0);
     }
 
-    let (file, post_slash) = find_file(path, boot_volume_image().get_root_cluster() as u64);
-    let file = match file {
-        Some(f) => {
-            if !f.is_directory() && post_slash {
-                return SyscallResult::err(0, ENOENT);
-            }
-            f
-        }
-        None => {
-            if (flags & O_CREAT) == 0 {
-                return SyscallResult::err(0, ENOENT);
-            }
-            match create_file(path) {
-                Ok(f) => f,
-                Err(e) => return SyscallResult::err(0, e),
-            }
-        }
+    let path = resolve_path(task.cwd(), &path);
+    let file = match vfs::open(&path, (flags & O_CREAT) != 0, (flags & O_TRUNC) != 0) {
+        Ok(f) => f,
+        Err(e) => return SyscallResult::err(0, fs_errno(e)),
     };
 
-    let fd = task.register_file_descriptor(FileDescriptor::Fat(FatFileDescriptor::new(file)));
+    let fd = task.register_file_descriptor(file);
     SyscallResult::ok(fd as u64)
 }
 
 fn read_file(fd: u64, buf: u64, count: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
     let fd = fd as i32;
-    let buf = buf as *mut u64 as *mut u8;
     let count = count as usize;
     unsafe { asm!("cli") };
     let task = task_manager().current_task();
     unsafe { asm!("sti") };
 
-    if let Some(descriptor) = task.get_file(fd as usize) {
-        let buf = unsafe { slice::from_raw_parts_mut(buf, count) };
-        let size = descriptor.lock().read(buf);
-        SyscallResult::ok(size as u64)
-    } else {
-        SyscallResult::err(0, EBADF)
+    let descriptor = match task.get_file(fd as usize) {
+        Some(d) => d,
+        None => return SyscallResult::err(0, EBADF),
+    };
+    if let Err(e) = verify_user_range(buf, count, true) {
+        return SyscallResult::err(0, user_access_errno(e));
+    }
+
+    // read through a bounded kernel buffer so a huge count cannot exhaust the heap
+    let mut kernel_buf = vec![0_u8; cmp::min(count, READ_CHUNK_SIZE)];
+    let mut total = 0;
+    while total < count {
+        let len = cmp::min(count - total, kernel_buf.len());
+        let size = descriptor.lock().read(&mut kernel_buf[..len]);
+        if let Err(e) = copy_to_user(buf + total as u64, &kernel_buf[..size]) {
+            return SyscallResult::err(total as u64, user_access_errno(e));
+        }
+        total += size;
+        if size < len {
+            break;
+        }
     }
+    SyscallResult::ok(total as u64)
 }
 
 fn demand_page(num_pages: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
@@ -444,42 +522,395 @@ fn demand_page(num_pages: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64)
     unsafe { asm!("sti") };
 
     let dp_end = task.dpaging_end;
-    task.dpaging_end = dp_end + 4096 * num_pages;
+    match num_pages
+        .checked_mul(4096)
+        .and_then(|size| dp_end.checked_add(size))
+    {
+        Some(end) => task.dpaging_end = end,
+        None => return SyscallResult::err(0, ENOMEM),
+    }
     SyscallResult::ok(dp_end)
 }
 
 fn map_file(fd: u64, file_size: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
     let fd = fd as usize;
-    let file_size = unsafe { (file_size as *mut usize).as_mut() }.unwrap();
 
     unsafe { asm!("cli") };
     let task = task_manager().current_task_mut();
     unsafe { asm!("sti") };
 
-    let task_file_size = match task.get_file(fd) {
+    let file = match task.get_file(fd) {
         None => return SyscallResult::err(0, EBADF),
-        Some(fd) => fd.lock().size(),
+        Some(f) => Arc::clone(f),
     };
-    *file_size = task_file_size;
+    let task_file_size = file.lock().size();
+    if let Err(e) = copy_value_to_user(file_size, &task_file_size) {
+        return SyscallResult::err(0, user_access_errno(e));
+    }
 
     let vaddr_end = task.file_map_end;
-    let vaddr_begin = (vaddr_end - (*file_size) as u64) & 0xffff_ffff_ffff_f000;
+    let vaddr_begin = (vaddr_end - task_file_size as u64) & 0xffff_ffff_ffff_f000;
     task.file_map_end = vaddr_begin;
-    task.add_file_mapping(FileMapping::new(fd, vaddr_begin, vaddr_end));
+    task.add_file_mapping(FileMapping::new(file, vaddr_begin, vaddr_end));
     SyscallResult::ok(vaddr_begin)
 }
 
-fn create_file(path: &str) -> Result<&DirectoryEntry, i32> {
-    crate::fat::global::create_file(path).map_err(|e| match e.code {
+fn close_file(fd: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task_mut();
+    unsafe { asm!("sti") };
+
+    match task.close_file(fd as usize) {
+        Some(_) => fs_result(Ok(())),
+        None => SyscallResult::err(0, EBADF),
+    }
+}
+
+fn seek_file(fd: u64, offset: u64, whence: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    let pos = match whence {
+        SEEK_SET => SeekFrom::Start(offset),
+        SEEK_CUR => SeekFrom::Current(offset as i64),
+        SEEK_END => SeekFrom::End(offset as i64),
+        _ => return SyscallResult::err(0, EINVAL),
+    };
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+
+    let descriptor = match task.get_file(fd as usize) {
+        Some(d) => d,
+        None => return SyscallResult::err(0, EBADF),
+    };
+    let result = descriptor.lock().seek(pos);
+    match result {
+        Ok(offset) => SyscallResult::ok(offset as u64),
+        Err(e) => match e.code {
+            Code::NotSeekable => SyscallResult::err(0, ESPIPE),
+            _ => SyscallResult::err(0, EINVAL),
+        },
+    }
+}
+
+fn file_stat(fd: u64, stat: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+
+    let file_stat = match task.get_file(fd as usize) {
+        Some(d) => d.lock().stat(),
+        None => return SyscallResult::err(0, EBADF),
+    };
+    match copy_value_to_user(stat, &file_stat) {
+        Ok(_) => SyscallResult::ok(0),
+        Err(e) => SyscallResult::err(0, user_access_errno(e)),
+    }
+}
+
+fn open_directory(path: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    let path = match string_from_user(path) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task_mut();
+    unsafe { asm!("sti") };
+
+    let items = match vfs::read_dir(&resolve_path(task.cwd(), &path)) {
+        Ok(items) => items,
+        Err(e) => return SyscallResult::err(0, fs_errno(e)),
+    };
+
+    let fd =
+        task.register_file_descriptor(FileDescriptor::Directory(DirectoryDescriptor::new(items)));
+    SyscallResult::ok(fd as u64)
+}
+
+/// Stores the next entry of the directory to `entry` and returns 1, or returns 0 at the end.
+fn read_directory(fd: u64, entry: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+
+    let descriptor = match task.get_file(fd as usize) {
+        Some(d) => d,
+        None => return SyscallResult::err(0, EBADF),
+    };
+    let mut descriptor = descriptor.lock();
+    let item = match &mut *descriptor {
+        FileDescriptor::Directory(dir) => dir.next_item(),
+        _ => return SyscallResult::err(0, ENOTDIR),
+    };
+    let item = match item {
+        Some(item) => item,
+        None => return SyscallResult::ok(0),
+    };
+
+    let mut dir_entry = DirEntry {
+        name: [0; NAME_MAX + 1],
+        size: item.stat.size as u32,
+        attr: if item.stat.mode == S_IFDIR {
+            ATTR_DIRECTORY
+        } else {
+            0
+        },
+    };
+    // a long name of 255 UTF-16 characters can be longer than NAME_MAX bytes in UTF-8
+    let mut len = cmp::min(item.name.len(), NAME_MAX);
+    while !item.name.is_char_boundary(len) {
+        len -= 1;
+    }
+    dir_entry.name[..len].copy_from_slice(&item.name.as_bytes()[..len]);
+    drop(descriptor);
+
+    match copy_value_to_user(entry, &dir_entry) {
+        Ok(_) => SyscallResult::ok(1),
+        Err(e) => SyscallResult::err(0, user_access_errno(e)),
+    }
+}
+
+/// Starts the app at `path` as a child task.
+///
+/// `argv` is a NULL-terminated array of strings, `[path]` if it is NULL.
+/// `fds` is an array of 3 file descriptors of the caller which become stdin, stdout and stderr
+/// of the child; the caller's 0, 1 and 2 are inherited if it is NULL.
+/// `envp` is a NULL-terminated array of `NAME=value` strings, and the child has no environment
+/// variables if it is NULL.
+fn spawn(path: u64, argv: u64, fds: u64, envp: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    let path = match string_from_user(path) {
+        Ok(s) => s,
+        Err(e) => return SyscallResult::err(0, e),
+    };
+    let mut arg_max = ARG_MAX;
+    let args = if argv == 0 {
+        vec![path.clone()]
+    } else {
+        match strings_from_user(argv, &mut arg_max) {
+            Ok(a) => a,
+            Err(e) => return SyscallResult::err(0, e),
+        }
+    };
+    let environment = if envp == 0 {
+        BTreeMap::new()
+    } else {
+        match strings_from_user(envp, &mut arg_max) {
+            Ok(envs) => envs
+                .iter()
+                .filter_map(|env| env.split_once('='))
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            Err(e) => return SyscallResult::err(0, e),
+        }
+    };
+
+    let mut fd_numbers = [0_i32, 1, 2];
+    if fds != 0 {
+        let mut buf = [0_u8; mem::size_of::<[i32; 3]>()];
+        if let Err(e) = copy_from_user(&mut buf, fds) {
+            return SyscallResult::err(0, user_access_errno(e));
+        }
+        for (i, fd) in fd_numbers.iter_mut().enumerate() {
+            *fd = i32::from_ne_bytes(buf[i * 4..(i + 1) * 4].try_into().unwrap());
+        }
+    }
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task_mut();
+    unsafe { asm!("sti") };
+
+    let mut files = vec![];
+    for fd in fd_numbers {
+        match task.get_file(fd as usize) {
+            Some(f) => files.push(Arc::clone(f)),
+            None => return SyscallResult::err(0, EBADF),
+        }
+    }
+    let files = [files[0].clone(), files[1].clone(), files[2].clone()];
+
+    match spawn_app(&path, args, files, task.cwd().to_string(), environment) {
+        Ok(child) => {
+            task.add_child(child);
+            SyscallResult::ok(child.value())
+        }
+        Err(e) => match e.code {
+            Code::NoSuchEntry => SyscallResult::err(0, ENOENT),
+            Code::IsDirectory => SyscallResult::err(0, EISDIR),
+            _ => SyscallResult::err(0, ENOMEM),
+        },
+    }
+}
+
+/// Waits for a child started by `spawn` to exit and returns its exit code.
+fn wait_task(task_id: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    let child = TaskID::new(task_id);
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task_mut();
+    if !task.remove_child(child) {
+        unsafe { asm!("sti") };
+        return SyscallResult::err(0, ECHILD);
+    }
+    let exit_code = task_manager().wait_finish(child);
+    unsafe { asm!("sti") };
+
+    SyscallResult::ok(exit_code as i64 as u64)
+}
+
+fn unlink(path: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    match path_from_user(path) {
+        Ok(path) => fs_result(vfs::remove_file(&path)),
+        Err(e) => SyscallResult::err(0, e),
+    }
+}
+
+fn make_directory(path: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    match path_from_user(path) {
+        Ok(path) => fs_result(vfs::make_directory(&path)),
+        Err(e) => SyscallResult::err(0, e),
+    }
+}
+
+fn remove_directory(path: u64, _a2: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    match path_from_user(path) {
+        Ok(path) => fs_result(vfs::remove_directory(&path)),
+        Err(e) => SyscallResult::err(0, e),
+    }
+}
+
+fn rename(old_path: u64, new_path: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    let old_path = match path_from_user(old_path) {
+        Ok(p) => p,
+        Err(e) => return SyscallResult::err(0, e),
+    };
+    let new_path = match path_from_user(new_path) {
+        Ok(p) => p,
+        Err(e) => return SyscallResult::err(0, e),
+    };
+    fs_result(vfs::rename(&old_path, &new_path))
+}
+
+/// Controls the terminal of `fd` like `ioctl`. `arg` points to a `TerminalMode` for
+/// `TC_GET_MODE` and `TC_SET_MODE`, and to a `WindowSize` for the others.
+fn io_control(fd: u64, request: u64, arg: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+
+    let descriptor = match task.get_file(fd as usize) {
+        Some(d) => d,
+        None => return SyscallResult::err(0, EBADF),
+    };
+    match terminal_control(&mut descriptor.lock(), request, arg) {
+        Ok(_) => SyscallResult::ok(0),
+        Err(e) => SyscallResult::err(0, e),
+    }
+}
+
+fn terminal_control(descriptor: &mut FileDescriptor, request: u64, arg: u64) -> Result<(), i32> {
+    let errno = |e: Error| match e.code {
+        Code::NotTerminal => ENOTTY,
+        _ => EINVAL,
+    };
+    match request {
+        TC_GET_MODE => {
+            let mode = descriptor.terminal_mode().map_err(errno)?;
+            copy_value_to_user(arg, &mode).map_err(user_access_errno)
+        }
+        TC_SET_MODE => {
+            let mut flags = [0; 4];
+            copy_from_user(&mut flags, arg).map_err(user_access_errno)?;
+            let mode = TerminalMode {
+                flags: u32::from_ne_bytes(flags),
+            };
+            descriptor.set_terminal_mode(mode).map_err(errno)
+        }
+        TC_GET_WINDOW_SIZE => {
+            let (columns, rows) = descriptor.window_size().map_err(errno)?;
+            let size = WindowSize {
+                columns: columns as u32,
+                rows: rows as u32,
+            };
+            copy_value_to_user(arg, &size).map_err(user_access_errno)
+        }
+        TC_SET_WINDOW_SIZE => {
+            let mut size = [0; mem::size_of::<WindowSize>()];
+            copy_from_user(&mut size, arg).map_err(user_access_errno)?;
+            let columns = u32::from_ne_bytes(size[..4].try_into().unwrap());
+            let rows = u32::from_ne_bytes(size[4..].try_into().unwrap());
+            descriptor
+                .set_window_size(columns as usize, rows as usize)
+                .map_err(errno)
+        }
+        _ => Err(EINVAL),
+    }
+}
+
+/// Passed to and from applications by `io_control`.
+#[repr(C)]
+struct WindowSize {
+    columns: u32,
+    rows: u32,
+}
+
+/// Opens a pseudo-terminal and writes the descriptors of its master and slave sides to `fds`.
+///
+/// `MODE_NONBLOCK` in `flags` makes reads of the master side return 0 instead of waiting.
+fn open_pty(fds: u64, flags: u64, _a3: u64, _a4: u64, _a5: u64, _a6: u64) -> SyscallResult {
+    if let Err(e) = verify_user_range(fds, mem::size_of::<[i32; 2]>(), true) {
+        return SyscallResult::err(0, user_access_errno(e));
+    }
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task_mut();
+    unsafe { asm!("sti") };
+
+    let (master, slave) = pty::open_pty(flags as u32 & MODE_NONBLOCK != 0);
+    let master = task.register_file_descriptor(FileDescriptor::Pty(master));
+    let slave = task.register_file_descriptor(FileDescriptor::Pty(slave));
+    if let Err(e) = copy_value_to_user(fds, &[master as i32, slave as i32]) {
+        task.close_file(master);
+        task.close_file(slave);
+        return SyscallResult::err(0, user_access_errno(e));
+    }
+    SyscallResult::ok(0)
+}
+
+/// Copies a path from the application and resolves it against the current directory.
+fn path_from_user(p: u64) -> Result<String, i32> {
+    let path = string_from_user(p)?;
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+    Ok(resolve_path(task.cwd(), &path))
+}
+
+/// Writes the modification back to the disk if it succeeded.
+fn fs_result(result: Result<(), Error>) -> SyscallResult {
+    match result.and_then(|_| vfs::sync()) {
+        Ok(_) => SyscallResult::ok(0),
+        Err(e) => SyscallResult::err(0, fs_errno(e)),
+    }
+}
+
+fn fs_errno(e: Error) -> i32 {
+    match e.code {
         Code::IsDirectory => EISDIR,
+        Code::NotDirectory => ENOTDIR,
         Code::NoSuchEntry => ENOENT,
+        Code::FileExists => EEXIST,
+        Code::DirectoryNotEmpty => ENOTEMPTY,
+        Code::NameTooLong => ENAMETOOLONG,
         Code::NoEnoughMemory => ENOSPC,
-        _ => 0,
-    })
+        Code::ReadOnly => EROFS,
+        Code::Busy => EBUSY,
+        Code::CrossDevice => EXDEV,
+        _ => EINVAL,
+    }
 }
 
 #[no_mangle]
-static syscall_table: [SyscallFuncType; 16] = [
+static syscall_table: [SyscallFuncType; 29] = [
     log_string,
     put_string,
     exit,
@@ -496,6 +927,19 @@ static syscall_table: [SyscallFuncType; 16] = [
     read_file,
     demand_page,
     map_file,
+    close_file,
+    seek_file,
+    file_stat,
+    open_directory,
+    read_directory,
+    spawn,
+    wait_task,
+    unlink,
+    make_directory,
+    remove_directory,
+    rename,
+    io_control,
+    open_pty,
 ];
 
 pub fn initialize_syscall() {
@@ -505,12 +949,53 @@ pub fn initialize_syscall() {
     write_msr(IA32_FMASK, 0);
 }
 
-unsafe fn c_str_from<'a>(p: u64) -> &'a CStr {
-    CStr::from_ptr(p as *const u64 as *const c_char)
+/// Exits the current app with the status of SIGINT if Ctrl-C has interrupted it.
+///
+/// `SyscallEntry` calls this after every system call, so an app which never calls one cannot be interrupted.
+#[no_mangle]
+pub extern "C" fn ExitAppIfInterrupted() {
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    let (interrupted, os_stack_pointer) = (task.is_interrupted(), *task.os_stack_pointer());
+    unsafe { asm!("sti") };
+    if interrupted {
+        exit_app(os_stack_pointer, 128 + SIGINT);
+    }
+}
+
+/// Copies a NUL-terminated UTF-8 string from the application.
+fn string_from_user(p: u64) -> Result<String, i32> {
+    let bytes = strncpy_from_user(p, MAX_STRING_LEN).map_err(user_access_errno)?;
+    String::from_utf8(bytes).map_err(|_| EINVAL)
 }
 
-fn str_from(bytes: &[u8]) -> &str {
-    core::str::from_utf8(bytes).expect("could not convert to str")
+/// Copies a NULL-terminated array of strings such as `argv` from the application.
+///
+/// The size of the strings and their pointers is subtracted from `limit`.
+fn strings_from_user(p: u64, limit: &mut usize) -> Result<Vec<String>, i32> {
+    let mut strings = vec![];
+    for i in 0.. {
+        *limit = limit.checked_sub(mem::size_of::<u64>()).ok_or(E2BIG)?;
+        let mut ptr = [0_u8; 8];
+        let addr = p.checked_add(i as u64 * 8).ok_or(EFAULT)?;
+        copy_from_user(&mut ptr, addr).map_err(user_access_errno)?;
+        let ptr = u64::from_ne_bytes(ptr);
+        if ptr == 0 {
+            break;
+        }
+        let s = string_from_user(ptr)?;
+        *limit = limit.checked_sub(s.len() + 1).ok_or(E2BIG)?;
+        strings.push(s);
+    }
+    Ok(strings)
+}
+
+fn user_access_errno(e: Error) -> i32 {
+    match e.code {
+        Code::BufferTooSmall => E2BIG,
+        Code::NoEnoughMemory => ENOMEM,
+        _ => EFAULT,
+    }
 }
 
 fn do_win_func<F>(layer_id_flags: u64, f: F) -> SyscallResult
@@ -1,10 +1,11 @@
 use crate::error::{Code, Error};
 use crate::io::FileDescriptor;
 use crate::make_error;
-use crate::message::Message;
+use crate::message::{Message, MessageType};
 use crate::segment::{KERNEL_CS, KERNEL_SS};
 use crate::sync::Mutex;
-use alloc::collections::{BTreeMap, VecDeque};
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec;
 use alloc::vec::Vec;
@@ -45,15 +46,16 @@ pub mod global {
 
 #[derive(Clone)]
 pub(crate) struct FileMapping {
-    pub(crate) fd: usize,
+    // holds the descriptor itself so that the mapping outlives `close`
+    pub(crate) file: Arc<Mutex<FileDescriptor>>,
     pub(crate) vaddr_begin: u64,
     vaddr_end: u64,
 }
 
 impl FileMapping {
-    pub(crate) fn new(fd: usize, vaddr_begin: u64, vaddr_end: u64) -> Self {
+    pub(crate) fn new(file: Arc<Mutex<FileDescriptor>>, vaddr_begin: u64, vaddr_end: u64) -> Self {
         Self {
-            fd,
+            file,
             vaddr_begin,
             vaddr_end,
         }
@@ -67,6 +69,10 @@ impl TaskID {
     pub fn new(v: u64) -> Self {
         Self(v)
     }
+
+    pub fn value(&self) -> u64 {
+        self.0
+    }
 }
 
 impl Add for TaskID {
@@ -91,11 +97,15 @@ pub struct Task {
     messages: VecDeque<Message>,
     level: PriorityLevel,
     is_running: bool,
-    files: Vec<Arc<Mutex<FileDescriptor>>>,
+    files: Vec<Option<Arc<Mutex<FileDescriptor>>>>,
     pub(crate) dpaging_begin: u64,
     pub(crate) dpaging_end: u64,
     pub(crate) file_map_end: u64,
     file_maps: Vec<FileMapping>,
+    children: Vec<TaskID>,
+    cwd: String,
+    group: TaskID,
+    interrupted: bool,
 }
 
 impl Task {
@@ -114,6 +124,10 @@ impl Task {
             dpaging_end: 0,
             file_map_end: 0,
             file_maps: vec![],
+            children: vec![],
+            cwd: "/".to_string(),
+            group: id,
+            interrupted: false,
         }
     }
 
@@ -160,6 +174,15 @@ impl Task {
         self
     }
 
+    pub(crate) fn level(&self) -> PriorityLevel {
+        self.level
+    }
+
+    /// Whether the task is in a run queue rather than sleeping.
+    pub(crate) fn is_running(&self) -> bool {
+        self.is_running
+    }
+
     pub(crate) fn set_cr3(&mut self, cr3: u64) {
         self.context.cr3 = cr3;
     }
@@ -169,17 +192,34 @@ impl Task {
     }
 
     pub(crate) fn get_file(&self, fd: usize) -> Option<&Arc<Mutex<FileDescriptor>>> {
-        self.files.get(fd)
+        self.files.get(fd).and_then(|f| f.as_ref())
     }
 
     pub(crate) fn register_file_descriptor(&mut self, fd: FileDescriptor) -> usize {
-        self.files.push(Arc::new(Mutex::new(fd)));
-        self.files.len() - 1
+        self.register_file_descriptor_arc(Arc::new(Mutex::new(fd)))
     }
 
+    /// Registers `fd` with the lowest unused number.
     pub(crate) fn register_file_descriptor_arc(&mut self, fd: Arc<Mutex<FileDescriptor>>) -> usize {
-        self.files.push(fd);
-        self.files.len() - 1
+        match self.files.iter().position(|f| f.is_none()) {
+            Some(i) => {
+                self.files[i] = Some(fd);
+                i
+            }
+            None => {
+                self.files.push(Some(fd));
+                self.files.len() - 1
+            }
+        }
+    }
+
+    /// Releases the number `fd` so that it can be reused.
+    pub(crate) fn close_file(&mut self, fd: usize) -> Option<Arc<Mutex<FileDescriptor>>> {
+        self.files.get_mut(fd).and_then(|f| f.take())
+    }
+
+    pub(crate) fn num_files(&self) -> usize {
+        self.files.iter().filter(|f| f.is_some()).count()
     }
 
     pub(crate) fn clear_files(&mut self) {
@@ -205,6 +245,51 @@ impl Task {
         self.file_maps.clear();
     }
 
+    pub(crate) fn add_child(&mut self, child: TaskID) {
+        self.children.push(child);
+    }
+
+    /// Forgets `child` so that it is waited only once. Returns false if it is not a child.
+    pub(crate) fn remove_child(&mut self, child: TaskID) -> bool {
+        match self.children.iter().position(|&c| c == child) {
+            Some(i) => {
+                self.children.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The absolute path against which relative paths given to syscalls are resolved.
+    pub(crate) fn cwd(&self) -> &str {
+        &self.cwd
+    }
+
+    pub(crate) fn set_cwd(&mut self, cwd: String) {
+        self.cwd = cwd;
+    }
+
+    /// The ID of the task leading the group which Ctrl-C interrupts together.
+    ///
+    /// A new task leads its own group.
+    pub fn group(&self) -> TaskID {
+        self.group
+    }
+
+    pub fn set_group(&mut self, group: TaskID) -> &mut Task {
+        self.group = group;
+        self
+    }
+
+    pub fn is_interrupted(&self) -> bool {
+        self.interrupted
+    }
+
+    /// Clears the interrupted flag, and returns whether it was set.
+    pub fn take_interrupted(&mut self) -> bool {
+        mem::take(&mut self.interrupted)
+    }
+
     pub fn receive_message(&mut self) -> Option<Message> {
         self.messages.pop_front()
     }
@@ -225,6 +310,8 @@ pub struct TaskManager {
     level_changed: bool,
     finish_tasks: BTreeMap<TaskID, i32>,
     finish_waiter: BTreeMap<TaskID, TaskID>,
+    /// the children whose parents have finished, whose exit codes nobody waits for
+    orphan_tasks: BTreeSet<TaskID>,
     switch_context: unsafe fn(next_ctx: &TaskContext, current_ctx: &TaskContext),
     restore_context: unsafe fn(task_ctx: &TaskContext),
 }
@@ -244,6 +331,7 @@ impl TaskManager {
             level_changed: false,
             finish_tasks: BTreeMap::new(),
             finish_waiter: BTreeMap::new(),
+            orphan_tasks: BTreeSet::new(),
             switch_context,
             restore_context,
         }
@@ -275,6 +363,11 @@ impl TaskManager {
         self.tasks.iter_mut().last().unwrap().1
     }
 
+    /// Iterates over all tasks in ascending order of their IDs.
+    pub(crate) fn tasks(&self) -> impl Iterator<Item = &Task> {
+        self.tasks.values()
+    }
+
     pub fn get_task(&self, task_id: TaskID) -> Option<&Task> {
         self.tasks.get(&task_id)
     }
@@ -379,6 +472,28 @@ impl TaskManager {
         Ok(())
     }
 
+    /// Interrupts every task in the group of `task_id`.
+    ///
+    /// The interrupted tasks are woken up by `MessageType::Interrupt`,
+    /// and an app exits at its next system call.
+    pub fn interrupt(&mut self, task_id: TaskID) -> Result<(), Error> {
+        let group = self
+            .get_task(task_id)
+            .ok_or_else(|| make_error!(Code::NoSuchTask))?
+            .group;
+        let members: Vec<TaskID> = self
+            .tasks
+            .values()
+            .filter(|task| task.group == group)
+            .map(|task| task.id)
+            .collect();
+        for id in members {
+            self.get_task_mut(id).unwrap().interrupted = true;
+            self.send_message(id, Message::new(MessageType::Interrupt))?;
+        }
+        Ok(())
+    }
+
     pub fn rotate_current_run_queue(&mut self, current_sleep: bool) -> TaskID {
         let current_task_id = self
             .current_running_task_ids_mut()
@@ -410,8 +525,8 @@ impl TaskManager {
     pub fn finish(&mut self, exit_code: i32) {
         let current_task_id = self.rotate_current_run_queue(true);
 
-        self.tasks.remove(&current_task_id);
-        self.finish_tasks.insert(current_task_id, exit_code);
+        let task = self.tasks.remove(&current_task_id).unwrap();
+        self.record_exit_code(task, exit_code);
 
         if let Some(waiter_task_id) = self.finish_waiter.remove(&current_task_id) {
             self.wake_up(waiter_task_id)
@@ -421,6 +536,19 @@ impl TaskManager {
         unsafe { (self.restore_context)(&self.current_task().context) }
     }
 
+    /// Keeps the exit code of the finished `task` for a waiter unless it is an orphan, and drops
+    /// the exit codes of its children since nobody waits for them any longer.
+    fn record_exit_code(&mut self, task: Task, exit_code: i32) {
+        for child in task.children {
+            if self.finish_tasks.remove(&child).is_none() {
+                self.orphan_tasks.insert(child);
+            }
+        }
+        if !self.orphan_tasks.remove(&task.id) {
+            self.finish_tasks.insert(task.id, exit_code);
+        }
+    }
+
     pub fn wait_finish(&mut self, task_id: TaskID) -> i32 {
         let current_task_id = self.current_task().id;
         loop {
@@ -433,6 +561,11 @@ impl TaskManager {
         }
     }
 
+    /// Returns the exit code of `task_id` if it has finished, without waiting for it.
+    pub fn try_wait(&mut self, task_id: TaskID) -> Option<i32> {
+        self.finish_tasks.remove(&task_id)
+    }
+
     fn current_running_task_ids(&self) -> &VecDeque<TaskID> {
         self.running_task_ids(self.current_level)
     }
@@ -723,4 +856,84 @@ mod tests {
         // the task specified by the arg should be sleep
         assert_eq!(tm.tasks[&t1_id].is_running, false);
     }
+
+    #[test]
+    fn task_reuses_closed_file_descriptor() {
+        use crate::terminal::file_descriptor::PipeDescriptor;
+        let pipe = || FileDescriptor::Pipe(PipeDescriptor::new(TaskID(0)));
+        let mut task = Task::new(TaskID(2), PriorityLevel::new(1));
+        assert_eq!(task.register_file_descriptor(pipe()), 0);
+        assert_eq!(task.register_file_descriptor(pipe()), 1);
+        assert_eq!(task.register_file_descriptor(pipe()), 2);
+
+        // when a number in the middle is closed,
+        assert!(task.close_file(1).is_some());
+        assert!(task.get_file(1).is_none());
+        assert!(task.close_file(1).is_none());
+
+        // then the lowest unused number should be reused
+        assert_eq!(task.register_file_descriptor(pipe()), 1);
+        assert_eq!(task.register_file_descriptor(pipe()), 3);
+    }
+
+    #[test]
+    fn task_manager_interrupt_group() {
+        let mut tm = TaskManager::new(|_, _| {}, |_| {});
+        tm.initialize(|| 0);
+
+        let leader = tm.new_task().id;
+        let member = tm.new_task().set_group(leader).id;
+        let other = tm.new_task().id;
+
+        // when a member of the group is interrupted,
+        tm.interrupt(member).unwrap();
+
+        // then the whole group should be interrupted and woken up
+        for id in [leader, member] {
+            assert!(tm.tasks[&id].is_interrupted());
+            assert!(tm.tasks[&id].is_running);
+            assert_eq!(
+                tm.get_task_mut(id).unwrap().receive_message(),
+                Some(Message::new(MessageType::Interrupt))
+            );
+        }
+        assert!(!tm.tasks[&other].is_interrupted());
+        assert!(tm.tasks.get_mut(&leader).unwrap().take_interrupted());
+        assert!(!tm.tasks[&leader].is_interrupted());
+        assert!(tm.interrupt(TaskID(100)).is_err());
+    }
+
+    #[test]
+    fn task_manager_drops_exit_codes_of_orphans() {
+        let mut tm = TaskManager::new(|_, _| {}, |_| {});
+        tm.initialize(|| 0);
+
+        let parent = tm.new_task().id;
+        let exited = tm.new_task().id;
+        let running = tm.new_task().id;
+        let waited = tm.new_task().id;
+        for child in [exited, running, waited] {
+            tm.tasks.get_mut(&parent).unwrap().add_child(child);
+        }
+        tm.tasks.get_mut(&parent).unwrap().remove_child(waited);
+
+        let task = tm.tasks.remove(&exited).unwrap();
+        tm.record_exit_code(task, 1);
+        assert_eq!(tm.finish_tasks.get(&exited), Some(&1));
+
+        // when the parent finishes without waiting for its children,
+        let task = tm.tasks.remove(&parent).unwrap();
+        tm.record_exit_code(task, 0);
+        let task = tm.tasks.remove(&running).unwrap();
+        tm.record_exit_code(task, 2);
+        let task = tm.tasks.remove(&waited).unwrap();
+        tm.record_exit_code(task, 3);
+
+        // then only the exit codes which someone may wait for are kept
+        assert_eq!(tm.finish_tasks.get(&exited), None);
+        assert_eq!(tm.finish_tasks.get(&running), None);
+        assert_eq!(tm.finish_tasks.get(&parent), Some(&0));
+        assert_eq!(tm.finish_tasks.get(&waited), Some(&3));
+        assert!(tm.orphan_tasks.is_empty());
+    }
 }
@@ -1,4 +1,10 @@
+mod ansi;
+mod completion;
 pub(crate) mod file_descriptor;
 mod history;
 pub mod lib;
+mod line_buffer;
+pub(crate) mod line_discipline;
+mod parser;
+pub(crate) mod pty;
 mod terminal_writer;
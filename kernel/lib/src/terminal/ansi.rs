@@ -0,0 +1,351 @@
+//! ANSI/VT100 escape sequences in the output to a terminal, which color characters,
+//! move the cursor and erase the screen.
+
+use crate::graphics::{PixelColor, COLOR_BLACK, COLOR_WHITE};
+use alloc::vec::Vec;
+use core::mem;
+
+/// What a character written to the terminal does.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum Control {
+    Print(char),
+    /// SGR, which changes the colors and the attributes of the following characters
+    SetGraphics(Vec<u16>),
+    CursorUp(u16),
+    CursorDown(u16),
+    CursorForward(u16),
+    CursorBack(u16),
+    /// moves the cursor to the row and the column, which start at 0
+    CursorPosition(u16, u16),
+    /// moves the cursor to the column, which starts at 0
+    CursorColumn(u16),
+    EraseInLine(Erase),
+    EraseInDisplay(Erase),
+    SaveCursor,
+    RestoreCursor,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum Erase {
+    /// from the cursor to the end
+    ToEnd,
+    /// from the start to the cursor
+    ToStart,
+    All,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum State {
+    Ground,
+    /// after ESC
+    Escape,
+    /// after ESC [
+    Csi,
+}
+
+/// Splits the output into characters and escape sequences, which may span several writes.
+#[derive(Debug)]
+pub(super) struct Parser {
+    state: State,
+    params: Vec<u16>,
+    param: Option<u16>,
+    /// whether the sequence has a private marker such as `?`, which is not supported
+    private: bool,
+}
+
+impl Parser {
+    pub(super) const fn new() -> Self {
+        Self {
+            state: State::Ground,
+            params: Vec::new(),
+            param: None,
+            private: false,
+        }
+    }
+
+    /// Returns what `c` does, or None if it is in the middle of a sequence or not supported.
+    pub(super) fn feed(&mut self, c: char) -> Option<Control> {
+        match self.state {
+            State::Ground => {
+                if c == '\x1b' {
+                    self.state = State::Escape;
+                    return None;
+                }
+                Some(Control::Print(c))
+            }
+            State::Escape => {
+                self.state = State::Ground;
+                match c {
+                    '[' => {
+                        self.state = State::Csi;
+                        self.params.clear();
+                        self.param = None;
+                        self.private = false;
+                        None
+                    }
+                    '7' => Some(Control::SaveCursor),
+                    '8' => Some(Control::RestoreCursor),
+                    // intermediate bytes such as `(` of `ESC ( B`
+                    ' '..='/' => {
+                        self.state = State::Escape;
+                        None
+                    }
+                    _ => None,
+                }
+            }
+            State::Csi => match c {
+                '0'..='9' => {
+                    let digit = c as u16 - '0' as u16;
+                    let param = self.param.unwrap_or(0);
+                    self.param = Some(param.saturating_mul(10).saturating_add(digit));
+                    None
+                }
+                ';' => {
+                    self.params.push(self.param.take().unwrap_or(0));
+                    None
+                }
+                '<'..='?' => {
+                    self.private = true;
+                    None
+                }
+                // intermediate bytes
+                ' '..='/' => None,
+                '@'..='~' => {
+                    self.state = State::Ground;
+                    if let Some(param) = self.param.take() {
+                        self.params.push(param);
+                    } else if !self.params.is_empty() {
+                        // an omitted last parameter such as `1;`
+                        self.params.push(0);
+                    }
+                    if self.private {
+                        return None;
+                    }
+                    self.dispatch(c)
+                }
+                // a broken sequence is dropped
+                _ => {
+                    self.state = State::Ground;
+                    None
+                }
+            },
+        }
+    }
+
+    fn dispatch(&mut self, final_byte: char) -> Option<Control> {
+        let params = mem::take(&mut self.params);
+        // a count of 0 or omitted means 1
+        let count = |i: usize| params.get(i).copied().filter(|&n| n > 0).unwrap_or(1);
+        let erase = || match params.first() {
+            None | Some(0) => Some(Erase::ToEnd),
+            Some(1) => Some(Erase::ToStart),
+            Some(2) | Some(3) => Some(Erase::All),
+            Some(_) => None,
+        };
+        let control = match final_byte {
+            'A' => Control::CursorUp(count(0)),
+            'B' => Control::CursorDown(count(0)),
+            'C' => Control::CursorForward(count(0)),
+            'D' => Control::CursorBack(count(0)),
+            'G' => Control::CursorColumn(count(0) - 1),
+            'H' | 'f' => Control::CursorPosition(count(0) - 1, count(1) - 1),
+            'J' => Control::EraseInDisplay(erase()?),
+            'K' => Control::EraseInLine(erase()?),
+            'm' => Control::SetGraphics(params),
+            's' => Control::SaveCursor,
+            'u' => Control::RestoreCursor,
+            _ => return None,
+        };
+        Some(control)
+    }
+}
+
+/// The colors of the 16 colors of SGR: black, red, green, yellow, blue, magenta, cyan, white
+/// and their bright versions.
+const PALETTE: [PixelColor; 16] = [
+    PixelColor::new(0, 0, 0),
+    PixelColor::new(205, 0, 0),
+    PixelColor::new(0, 205, 0),
+    PixelColor::new(205, 205, 0),
+    PixelColor::new(0, 0, 238),
+    PixelColor::new(205, 0, 205),
+    PixelColor::new(0, 205, 205),
+    PixelColor::new(229, 229, 229),
+    PixelColor::new(127, 127, 127),
+    PixelColor::new(255, 0, 0),
+    PixelColor::new(0, 255, 0),
+    PixelColor::new(255, 255, 0),
+    PixelColor::new(92, 92, 255),
+    PixelColor::new(255, 0, 255),
+    PixelColor::new(0, 255, 255),
+    PixelColor::new(255, 255, 255),
+];
+
+/// The colors and the attributes of a character.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) struct Style {
+    pub(super) fg: PixelColor,
+    pub(super) bg: PixelColor,
+    pub(super) bold: bool,
+    pub(super) reverse: bool,
+}
+
+impl Style {
+    pub(super) const DEFAULT: Style = Style {
+        fg: COLOR_WHITE,
+        bg: COLOR_BLACK,
+        bold: false,
+        reverse: false,
+    };
+
+    /// Returns the colors of the character and the background, which `reverse` swaps.
+    pub(super) fn colors(&self) -> (PixelColor, PixelColor) {
+        if self.reverse {
+            (self.bg, self.fg)
+        } else {
+            (self.fg, self.bg)
+        }
+    }
+
+    /// Returns the style of an erased cell, which keeps only the background color.
+    pub(super) fn erased(&self) -> Style {
+        Style {
+            bg: self.bg,
+            ..Style::DEFAULT
+        }
+    }
+
+    /// Applies the parameters of SGR. Unknown ones are ignored.
+    pub(super) fn apply(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            *self = Style::DEFAULT;
+            return;
+        }
+        let mut params = params.iter().copied();
+        while let Some(param) = params.next() {
+            match param {
+                0 => *self = Style::DEFAULT,
+                1 => self.bold = true,
+                22 => self.bold = false,
+                7 => self.reverse = true,
+                27 => self.reverse = false,
+                30..=37 => self.fg = PALETTE[usize::from(param - 30)],
+                90..=97 => self.fg = PALETTE[usize::from(param - 90 + 8)],
+                39 => self.fg = Style::DEFAULT.fg,
+                40..=47 => self.bg = PALETTE[usize::from(param - 40)],
+                100..=107 => self.bg = PALETTE[usize::from(param - 100 + 8)],
+                49 => self.bg = Style::DEFAULT.bg,
+                38 | 48 => {
+                    if let Some(color) = extended_color(&mut params) {
+                        if param == 38 {
+                            self.fg = color;
+                        } else {
+                            self.bg = color;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Reads the color after 38 or 48, which is `5;n` of the 256 colors or `2;r;g;b`.
+fn extended_color(params: &mut impl Iterator<Item = u16>) -> Option<PixelColor> {
+    let mut next = || params.next().map(|n| n.min(255) as u8);
+    match next()? {
+        5 => {
+            let n = next()?;
+            let color = match n {
+                0..=15 => PALETTE[usize::from(n)],
+                // the 6x6x6 color cube
+                16..=231 => {
+                    let level = |v: u8| if v == 0 { 0 } else { 55 + v * 40 };
+                    let n = n - 16;
+                    PixelColor::new(level(n / 36), level(n / 6 % 6), level(n % 6))
+                }
+                // the grayscale ramp
+                _ => {
+                    let v = 8 + (n - 232) * 10;
+                    PixelColor::new(v, v, v)
+                }
+            };
+            Some(color)
+        }
+        2 => Some(PixelColor::new(next()?, next()?, next()?)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn parse(s: &str) -> Vec<Control> {
+        let mut parser = Parser::new();
+        s.chars().filter_map(|c| parser.feed(c)).collect()
+    }
+
+    #[test]
+    fn parse_sequences() {
+        assert_eq!(
+            parse("a\x1b[1;31mb\x1b[m"),
+            vec![
+                Control::Print('a'),
+                Control::SetGraphics(vec![1, 31]),
+                Control::Print('b'),
+                Control::SetGraphics(vec![]),
+            ]
+        );
+        assert_eq!(
+            parse("\x1b[H\x1b[5;10f\x1b[3A\x1b[0C\x1b[12G"),
+            vec![
+                Control::CursorPosition(0, 0),
+                Control::CursorPosition(4, 9),
+                Control::CursorUp(3),
+                Control::CursorForward(1),
+                Control::CursorColumn(11),
+            ]
+        );
+        assert_eq!(
+            parse("\x1b[K\x1b[1K\x1b[2J\x1b7\x1b[u"),
+            vec![
+                Control::EraseInLine(Erase::ToEnd),
+                Control::EraseInLine(Erase::ToStart),
+                Control::EraseInDisplay(Erase::All),
+                Control::SaveCursor,
+                Control::RestoreCursor,
+            ]
+        );
+        // unsupported sequences are dropped
+        assert_eq!(parse("\x1b[?25l\x1b[5Z\x1b(Bx"), vec![Control::Print('x')]);
+    }
+
+    #[test]
+    fn sequence_may_be_split() {
+        let mut parser = Parser::new();
+        assert_eq!(parser.feed('\x1b'), None);
+        assert_eq!(parser.feed('['), None);
+        assert_eq!(parser.feed('2'), None);
+        assert_eq!(parser.feed('K'), Some(Control::EraseInLine(Erase::All)));
+        assert_eq!(parser.feed('K'), Some(Control::Print('K')));
+    }
+
+    #[test]
+    fn apply_graphics() {
+        let mut style = Style::DEFAULT;
+        style.apply(&[1, 32, 44]);
+        assert!(style.bold);
+        assert_eq!(style.colors(), (PALETTE[2], PALETTE[4]));
+        style.apply(&[7, 22, 39]);
+        assert!(!style.bold);
+        assert_eq!(style.colors(), (PALETTE[4], COLOR_WHITE));
+        assert_eq!(style.erased().colors(), (COLOR_WHITE, PALETTE[4]));
+        style.apply(&[0, 38, 5, 196, 48, 2, 1, 2, 3]);
+        assert_eq!(style.fg, PixelColor::new(255, 0, 0));
+        assert_eq!(style.bg, PixelColor::new(1, 2, 3));
+        style.apply(&[]);
+        assert_eq!(style, Style::DEFAULT);
+    }
+}
@@ -0,0 +1,132 @@
+//! Completes the word before the cursor by Tab from built-in commands, apps and paths.
+
+use crate::fat::resolve_path;
+use crate::io::S_IFDIR;
+use crate::vfs;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// Returns the last word of `line` and whether it is at the position of a command.
+///
+/// Quotes are not taken into account, so a word ends at any space or operator.
+pub(super) fn current_word(line: &str) -> (&str, bool) {
+    let is_delimiter = |c: char| c.is_whitespace() || ";|&<>".contains(c);
+    let start = line.rfind(is_delimiter).map_or(0, |i| i + 1);
+    let before = line[..start].trim_end_matches(char::is_whitespace);
+    let is_command = match before.chars().last() {
+        None => true,
+        Some(c) => ";|&".contains(c),
+    };
+    (&line[start..], is_command)
+}
+
+/// Returns the names starting with `prefix` ignoring ASCII case, sorted without duplicates.
+pub(super) fn filter(prefix: &str, names: impl IntoIterator<Item = String>) -> Vec<String> {
+    let mut matched = names
+        .into_iter()
+        .filter(|name| starts_with_ignore_case(name, prefix))
+        .collect::<Vec<_>>();
+    matched.sort();
+    matched.dedup();
+    matched
+}
+
+fn starts_with_ignore_case(s: &str, prefix: &str) -> bool {
+    s.len() >= prefix.len()
+        && s.is_char_boundary(prefix.len())
+        && s[..prefix.len()].eq_ignore_ascii_case(prefix)
+}
+
+/// Returns the longest prefix of the first candidate which all the candidates share ignoring ASCII case.
+pub(super) fn common_prefix(candidates: &[String]) -> &str {
+    let first = match candidates.first() {
+        Some(first) => first.as_str(),
+        None => return "",
+    };
+    let mut len = first.len();
+    for candidate in &candidates[1..] {
+        len = first
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| !a.eq_ignore_ascii_case(b))
+            .map_or(len.min(candidate.len()), |((i, _), _)| i.min(len));
+    }
+    &first[..len]
+}
+
+/// Escapes the characters which the shell would not take literally.
+pub(super) fn escape(name: &str) -> String {
+    let mut escaped = String::new();
+    for c in name.chars() {
+        if !(c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c)) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Returns the entries of the directory `dir` relative to `cwd` whose names start with `prefix`.
+/// The names of directories end with `/`.
+pub(super) fn entries(dir: &str, prefix: &str, cwd: &str) -> Vec<String> {
+    let path = if dir.is_empty() {
+        cwd.to_string()
+    } else {
+        resolve_path(cwd, dir)
+    };
+    let items = match vfs::read_dir(&path) {
+        Ok(items) => items,
+        Err(_) => return Vec::new(),
+    };
+    let names = items
+        .into_iter()
+        // hidden files are shown only if they are asked for
+        .filter(|item| !item.name.starts_with('.') || prefix.starts_with('.'))
+        .map(|item| {
+            if item.stat.mode == S_IFDIR {
+                item.name + "/"
+            } else {
+                item.name
+            }
+        });
+    filter(prefix, names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn strings(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn current_word_and_position() {
+        assert_eq!(current_word(""), ("", true));
+        assert_eq!(current_word("ec"), ("ec", true));
+        assert_eq!(current_word("cat /apps/gr"), ("/apps/gr", false));
+        assert_eq!(current_word("cat a | so"), ("so", true));
+        assert_eq!(current_word("a && b;c"), ("c", true));
+        assert_eq!(current_word("sort <da"), ("da", false));
+        assert_eq!(current_word("ls "), ("", false));
+    }
+
+    #[test]
+    fn filter_and_common_prefix() {
+        let matched = filter("re", strings(&["rpn", "README.TXT", "rename", "rename"]));
+        assert_eq!(matched, strings(&["README.TXT", "rename"]));
+        assert_eq!(common_prefix(&matched), "RE");
+        assert_eq!(common_prefix(&strings(&["grep", "grep2"])), "grep");
+        assert_eq!(common_prefix(&strings(&["grep2", "grep"])), "grep");
+        assert_eq!(common_prefix(&strings(&["ls"])), "ls");
+        assert_eq!(common_prefix(&[]), "");
+        assert_eq!(filter("x", vec![]), Vec::<String>::new());
+    }
+
+    #[test]
+    fn escape_special_characters() {
+        assert_eq!(escape("a b's.txt"), "a\\ b\\'s.txt");
+        assert_eq!(escape("dir/"), "dir/");
+    }
+}
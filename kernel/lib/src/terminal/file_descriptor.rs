@@ -1,17 +1,18 @@
 use crate::io::FileDescriptor;
-use crate::keyboard::{is_control_key_inputted, KEY_D};
 use crate::libc::{memcpy, memmove};
 use crate::message::{Message, MessageType, PipeMessage};
 use crate::str_trimming_nul;
 use crate::sync::{Mutex, MutexGuard};
 use crate::task::global::task_manager;
 use crate::task::TaskID;
+use crate::terminal::lib::resize_terminal;
+use crate::terminal::line_discipline::{key_bytes, TerminalMode};
 use crate::terminal::terminal_writer::{TerminalWriter, TERMINAL_WRITERS};
+use alloc::collections::BTreeMap;
 use alloc::string::String;
 use alloc::sync::Arc;
 use core::arch::asm;
 use core::ffi::c_void;
-use core::fmt::Write;
 use core::{cmp, mem};
 
 pub(super) struct TerminalDescriptor {
@@ -19,6 +20,8 @@ pub(super) struct TerminalDescriptor {
     pub(super) exit_after_command: bool,
     pub(super) show_window: bool,
     pub(super) files: [Arc<Mutex<FileDescriptor>>; 3],
+    pub(super) cwd: String,
+    pub(super) environment: BTreeMap<String, String>,
 }
 
 pub(crate) struct TerminalFileDescriptor {
@@ -30,8 +33,18 @@ impl TerminalFileDescriptor {
         Self { terminal_id }
     }
 
+    /// Reads the keys typed into the terminal as the mode of its line discipline says.
     pub fn read(&mut self, buf: &mut [u8]) -> usize {
         loop {
+            let nonblocking = {
+                let mut w = self.terminal_writer();
+                let discipline = w.line_discipline();
+                if let Some(len) = discipline.read(buf) {
+                    return len;
+                }
+                discipline.mode().is_nonblocking()
+            };
+
             unsafe { asm!("cli") };
             let task = task_manager().get_task_mut(self.terminal_id);
             if task.is_none() {
@@ -39,6 +52,10 @@ impl TerminalFileDescriptor {
             }
             let task = task.unwrap();
             let message = match task.receive_message() {
+                None if nonblocking => {
+                    unsafe { asm!("sti") };
+                    return 0;
+                }
                 None => {
                     task_manager().sleep(task.id()).unwrap();
                     continue;
@@ -46,41 +63,43 @@ impl TerminalFileDescriptor {
                 Some(m) => m,
             };
             unsafe { asm!("sti") };
-            let arg = if let MessageType::KeyPush(arg) = message.m_type {
-                arg
-            } else {
-                continue;
+            let arg = match message.m_type {
+                MessageType::KeyPush(arg) => arg,
+                MessageType::Interrupt => return 0,
+                MessageType::WindowResize(arg) => {
+                    resize_terminal(arg);
+                    continue;
+                }
+                _ => continue,
             };
             if !arg.press {
                 continue;
             }
 
-            if is_control_key_inputted(arg.modifier) && arg.ascii.is_ascii() {
-                write!(
-                    self.terminal_writer(),
-                    "^{}",
-                    arg.ascii.to_ascii_uppercase()
-                )
-                .unwrap();
-                if arg.keycode == KEY_D {
-                    return 0; // EOT
-                }
-                continue;
-            }
-
-            let mut bytes = [0_u8; 4];
-            let str = arg.ascii.encode_utf8(&mut bytes);
-            {
-                let mut w = self.terminal_writer();
-                w.print(str);
+            let key = key_bytes(arg.modifier, arg.keycode, arg.ascii);
+            let mut w = self.terminal_writer();
+            let echo = w.line_discipline().input(&key);
+            if !echo.is_empty() {
+                w.print(&echo);
                 w.redraw();
             }
-            let size = cmp::min(buf.len(), bytes.len());
-            buf[..size].copy_from_slice(&bytes[..size]);
-            return bytes.iter().filter(|&&x| x != 0).count();
         }
     }
 
+    pub fn mode(&self) -> TerminalMode {
+        self.terminal_writer().line_discipline().mode()
+    }
+
+    pub fn set_mode(&mut self, mode: TerminalMode) {
+        self.terminal_writer().line_discipline().set_mode(mode)
+    }
+
+    /// Returns the numbers of columns and rows of the terminal.
+    pub fn window_size(&self) -> (usize, usize) {
+        let w = self.terminal_writer();
+        (w.columns(), w.rows())
+    }
+
     pub fn write(&mut self, buf: &[u8]) -> usize {
         match str_trimming_nul(buf) {
             Ok(str) => {
@@ -174,6 +193,7 @@ impl PipeDescriptor {
 
             let pipe_message = match message.m_type {
                 MessageType::Pipe(p) => p,
+                MessageType::Interrupt => return 0,
                 _ => continue,
             };
             break pipe_message;
@@ -1,4 +1,8 @@
+//! The lines typed at the prompt, which are recalled by the arrow keys, `!n` and Ctrl-R.
+
+use crate::terminal::lib::LINE_MAX;
 use alloc::collections::VecDeque;
+use alloc::format;
 use alloc::string::String;
 
 pub(crate) enum Direction {
@@ -12,15 +16,28 @@ pub(crate) struct CommandHistory {
     // index   0 1 2 3
     history: VecDeque<String>,
     pointing_index: Option<usize>,
+    size: usize,
+    /// the number of the oldest line, which `history` shows and `!n` refers to
+    first_number: usize,
 }
 
 impl CommandHistory {
-    const MAX: usize = 8;
+    pub(crate) const DEFAULT_SIZE: usize = 100;
 
     pub(crate) fn new() -> CommandHistory {
         Self {
-            history: VecDeque::with_capacity(Self::MAX),
+            history: VecDeque::with_capacity(Self::DEFAULT_SIZE),
             pointing_index: None,
+            size: Self::DEFAULT_SIZE,
+            first_number: 1,
+        }
+    }
+
+    /// Changes the number of lines to keep, dropping the oldest ones which no longer fit.
+    pub(crate) fn set_size(&mut self, size: usize) {
+        self.size = size;
+        while self.history.len() > size {
+            self.pop_oldest();
         }
     }
 
@@ -57,21 +74,198 @@ impl CommandHistory {
     pub(crate) fn push(&mut self, command: String) {
         self.pointing_index = None;
 
-        if command.is_empty() {
+        if command.is_empty() || self.size == 0 {
             return;
         }
 
-        if self.history.len() == Self::MAX {
-            self.history.pop_back().unwrap();
+        if self.history.len() == self.size {
+            self.pop_oldest();
         }
         self.history.push_front(command);
     }
+
+    fn pop_oldest(&mut self) {
+        self.history.pop_back().unwrap();
+        self.first_number += 1;
+    }
+
+    /// Returns the lines from the oldest with their numbers.
+    pub(crate) fn entries(&self) -> impl Iterator<Item = (usize, &str)> {
+        (self.first_number..).zip(self.history.iter().rev().map(String::as_str))
+    }
+
+    /// Returns the line of the number `n` shown by `entries`.
+    pub(crate) fn get(&self, n: usize) -> Option<&str> {
+        let newest = self.first_number + self.history.len();
+        if n < self.first_number || n >= newest {
+            return None;
+        }
+        self.history.get(newest - 1 - n).map(String::as_str)
+    }
+
+    /// Returns the `i`th newest line, where 0 is the newest.
+    pub(crate) fn recent(&self, i: usize) -> Option<&str> {
+        self.history.get(i).map(String::as_str)
+    }
+
+    /// Returns the newest line containing `query` from the `start`th newest line and its index.
+    pub(crate) fn search(&self, query: &str, start: usize) -> Option<usize> {
+        self.history
+            .iter()
+            .enumerate()
+            .skip(start)
+            .find(|(_, line)| line.contains(query))
+            .map(|(i, _)| i)
+    }
+
+    /// Replaces `!n`, `!-n` and `!!` in `line` with the lines of the history,
+    /// except in single quotes or after a backslash.
+    ///
+    /// Fails if the expanded line is longer than a line which can be typed.
+    pub(crate) fn expand(&self, line: &str) -> Result<String, String> {
+        let mut expanded = String::new();
+        let mut chars = line.char_indices().peekable();
+        let mut in_single_quote = false;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' if !in_single_quote => {
+                    expanded.push(c);
+                    if let Some((_, next)) = chars.next() {
+                        expanded.push(next);
+                    }
+                    continue;
+                }
+                '\'' => in_single_quote = !in_single_quote,
+                '!' if !in_single_quote => {
+                    let rest = &line[i + 1..];
+                    let (event, len) = if rest.starts_with('!') {
+                        (self.recent(0), 1)
+                    } else {
+                        let digits_start = usize::from(rest.starts_with('-'));
+                        let len = rest[digits_start..]
+                            .find(|c: char| !c.is_ascii_digit())
+                            .unwrap_or(rest.len() - digits_start);
+                        if len == 0 {
+                            // a `!` not followed by a number is taken literally
+                            expanded.push(c);
+                            continue;
+                        }
+                        let n = rest[digits_start..digits_start + len]
+                            .parse::<usize>()
+                            .unwrap_or(usize::MAX);
+                        let event = if digits_start == 1 {
+                            n.checked_sub(1).and_then(|i| self.recent(i))
+                        } else {
+                            self.get(n)
+                        };
+                        (event, digits_start + len)
+                    };
+                    match event {
+                        Some(event) => expanded.push_str(event),
+                        None => return Err(format!("!{}: event not found", &rest[..len])),
+                    }
+                    for _ in 0..len {
+                        chars.next();
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            expanded.push(c);
+        }
+        if expanded.len() > LINE_MAX {
+            return Err(format!("{}: expanded line too long", line));
+        }
+        Ok(expanded)
+    }
+
+    /// Replaces the lines with those of `text`, the content of a history file.
+    /// Lines longer than a line which can be typed are skipped.
+    pub(crate) fn load(&mut self, text: &str) {
+        self.history.clear();
+        self.pointing_index = None;
+        self.first_number = 1;
+        for line in text.lines().filter(|line| line.len() <= LINE_MAX) {
+            self.push(line.into());
+        }
+    }
+
+    /// Returns the lines from the oldest as the content of a history file.
+    pub(crate) fn to_text(&self) -> String {
+        let mut text = String::new();
+        for line in self.history.iter().rev() {
+            text.push_str(line);
+            text.push('\n');
+        }
+        text
+    }
+}
+
+/// Ctrl-R, which finds the newest line containing the characters typed so far.
+#[derive(Debug, Default)]
+pub(crate) struct ReverseSearch {
+    query: String,
+    /// the index of the found line in the history, where 0 is the newest
+    found: Option<usize>,
+    failed: bool,
+}
+
+impl ReverseSearch {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn push(&mut self, c: char, history: &CommandHistory) {
+        self.query.push(c);
+        self.search(history, self.found.unwrap_or(0));
+    }
+
+    pub(crate) fn pop(&mut self, history: &CommandHistory) {
+        self.query.pop();
+        if self.query.is_empty() {
+            self.found = None;
+            self.failed = false;
+        } else {
+            self.search(history, 0);
+        }
+    }
+
+    /// Finds the next older line, as Ctrl-R is pressed again.
+    pub(crate) fn next(&mut self, history: &CommandHistory) {
+        self.search(history, self.found.map_or(0, |i| i + 1));
+    }
+
+    fn search(&mut self, history: &CommandHistory, start: usize) {
+        match history.search(&self.query, start) {
+            Some(i) => {
+                self.found = Some(i);
+                self.failed = false;
+            }
+            None => self.failed = true,
+        }
+    }
+
+    /// Returns the line found, which is kept even if the query no longer matches.
+    pub(crate) fn line<'a>(&self, history: &'a CommandHistory) -> Option<&'a str> {
+        self.found.and_then(|i| history.recent(i))
+    }
+
+    /// Returns what is shown in the input line and the position of the cursor in it,
+    /// which is at the start of the match.
+    pub(crate) fn render(&self, history: &CommandHistory) -> (String, usize) {
+        let failed = if self.failed { "failed " } else { "" };
+        let prompt = format!("({}reverse-i-search)`{}': ", failed, self.query);
+        let line = self.line(history).unwrap_or("");
+        let cursor = prompt.len() + line.find(self.query.as_str()).unwrap_or(0);
+        (prompt + line, cursor)
+    }
 }
 
 #[cfg(test)]
 mod command_history_tests {
     use super::*;
     use alloc::string::ToString;
+    use alloc::vec::Vec;
 
     #[test]
     fn up_should_return_empty_if_it_has_no_history() {
@@ -138,16 +332,102 @@ mod command_history_tests {
     #[test]
     fn push_should_remove_oldest_if_history_is_full() {
         let mut history = CommandHistory::new();
-        for i in 0..CommandHistory::MAX {
+        for i in 0..CommandHistory::DEFAULT_SIZE {
             history.push(i.to_string());
         }
 
-        history.push(CommandHistory::MAX.to_string());
+        history.push(CommandHistory::DEFAULT_SIZE.to_string());
 
         assert_eq!(
             history.history.front().unwrap(),
-            &CommandHistory::MAX.to_string()
+            &CommandHistory::DEFAULT_SIZE.to_string()
         );
         assert_eq!(history.history.back().unwrap(), &"1".to_string()); // not "0"
     }
+
+    fn history(lines: &[&str]) -> CommandHistory {
+        let mut history = CommandHistory::new();
+        for line in lines {
+            history.push(line.to_string());
+        }
+        history
+    }
+
+    #[test]
+    fn set_size_should_keep_numbers_of_remaining_lines() {
+        let mut history = history(&["a", "b", "c", "d"]);
+        history.set_size(2);
+        assert_eq!(history.entries().collect::<Vec<_>>(), [(3, "c"), (4, "d")]);
+        history.push("e".to_string());
+        assert_eq!(history.entries().collect::<Vec<_>>(), [(4, "d"), (5, "e")]);
+        assert_eq!(history.get(3), None);
+        assert_eq!(history.get(5), Some("e"));
+        assert_eq!(history.get(6), None);
+    }
+
+    #[test]
+    fn expand_should_replace_history_references() {
+        let history = history(&["ls", "echo hi"]);
+        assert_eq!(history.expand("!1 /apps").unwrap(), "ls /apps");
+        assert_eq!(history.expand("!! | !-2").unwrap(), "echo hi | ls");
+        assert_eq!(
+            history.expand("echo '!1' \\!1 ! !x").unwrap(),
+            "echo '!1' \\!1 ! !x"
+        );
+        assert_eq!(history.expand("!3").unwrap_err(), "!3: event not found");
+        assert_eq!(history.expand("!-3").unwrap_err(), "!-3: event not found");
+        assert!(CommandHistory::new().expand("!!").is_err());
+    }
+
+    #[test]
+    fn expand_should_reject_too_long_lines() {
+        let history = history(&[&"a".repeat(LINE_MAX / 2)]);
+        assert_eq!(history.expand("!!!!").unwrap().len(), LINE_MAX);
+        assert_eq!(
+            history.expand("!! !!").unwrap_err(),
+            "!! !!: expanded line too long"
+        );
+    }
+
+    #[test]
+    fn load_should_restore_saved_lines() {
+        let mut loaded = CommandHistory::new();
+        loaded.set_size(2);
+        loaded.load(&history(&["a", "b", "c"]).to_text());
+        assert_eq!(loaded.to_text(), "b\nc\n");
+        assert_eq!(loaded.up(), "c");
+    }
+
+    #[test]
+    fn load_should_skip_too_long_lines() {
+        let mut loaded = CommandHistory::new();
+        let long = "a".repeat(LINE_MAX + 1);
+        loaded.load(&format!("ls\n{}\n{}\n", long, &long[1..]));
+        assert_eq!(loaded.to_text(), format!("ls\n{}\n", &long[1..]));
+    }
+
+    #[test]
+    fn reverse_search_should_find_older_lines() {
+        let history = history(&["echo a", "ls", "echo b"]);
+        let mut search = ReverseSearch::new();
+        assert_eq!(
+            search.render(&history),
+            ("(reverse-i-search)`': ".to_string(), 22)
+        );
+        search.push('e', &history);
+        assert_eq!(search.line(&history), Some("echo b"));
+        search.push('c', &history);
+        search.next(&history);
+        assert_eq!(search.line(&history), Some("echo a"));
+        search.next(&history);
+        assert_eq!(
+            search.render(&history),
+            ("(failed reverse-i-search)`ec': echo a".to_string(), 31)
+        );
+        search.pop(&history);
+        assert_eq!(search.line(&history), Some("echo b"));
+        search.push('x', &history);
+        assert_eq!(search.line(&history), Some("echo b"));
+        assert!(search.failed);
+    }
 }
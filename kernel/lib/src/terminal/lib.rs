@@ -1,39 +1,52 @@
 use crate::asm::global::{call_app, get_cr3, set_cr3};
 use crate::elf::Elf64Ehdr;
 use crate::error::{Code, Error};
-use crate::fat::global::{boot_volume_image, create_file, find_file};
-use crate::fat::{Attribute, DirectoryEntry, FatFileDescriptor, END_OF_CLUSTER_CHAIN};
+use crate::fat::global::{boot_volume_image, find_file};
+use crate::fat::{resolve_path, DirectoryEntry};
 use crate::graphics::global::frame_buffer_config;
 use crate::graphics::{
     draw_text_box_with_colors, PixelColor, PixelWriter, Rectangle, Vector2D, COLOR_BLACK,
 };
-use crate::io::{FileDescriptor, STD_ERR, STD_IN, STD_OUT};
+use crate::io::{FileDescriptor, SeekFrom, STD_ERR, STD_IN, STD_OUT, S_IFCHR, S_IFDIR};
+use crate::keyboard::{
+    is_control_key_inputted, is_shift_key_inputted, KEY_DELETE, KEY_DOWN, KEY_END, KEY_HOME,
+    KEY_LEFT, KEY_PAGE_DOWN, KEY_PAGE_UP, KEY_RIGHT, KEY_UP,
+};
 use crate::layer::global::layer_manager;
 use crate::layer::LayerID;
-use crate::libc::{memcpy, strcpy, strlen};
+use crate::libc::memcpy;
 use crate::memory_manager::global::MEMORY_MANAGER;
 use crate::message::MessageType::Layer;
-use crate::message::{LayerMessage, LayerOperation, Message, MessageType, WindowActiveMode};
-use crate::paging::global::{copy_page_maps, free_page_map, reset_cr3};
+use crate::message::{
+    LayerMessage, LayerOperation, Message, MessageType, WindowActiveMode, WindowResizeMessage,
+};
+use crate::paging::global::{copy_page_maps, free_page_map, kernel_cr3, reset_cr3};
 use crate::paging::{LinearAddress4Level, PageMapEntry};
 use crate::pci::devices;
-use crate::rust_official::c_str::CString;
 use crate::rust_official::cchar::c_char;
 use crate::sync::Mutex;
 use crate::sync::MutexGuard;
+use crate::syscall::SIGINT;
 use crate::task::global::{main_task_id, task_manager};
 use crate::task::{Task, TaskID};
+use crate::terminal::completion;
 use crate::terminal::file_descriptor::{
     PipeDescriptor, TerminalDescriptor, TerminalFileDescriptor,
 };
-use crate::terminal::history::{CommandHistory, Direction};
-use crate::terminal::terminal_writer::{TerminalWriter, TERMINAL_WRITERS};
+use crate::terminal::history::{CommandHistory, Direction, ReverseSearch};
+use crate::terminal::line_buffer::LineBuffer;
+use crate::terminal::line_discipline::TerminalMode;
+use crate::terminal::parser::{
+    is_name, parse, quote, AndOr, Command, Connector, List, Redirect, RedirectOp, SimpleCommand,
+};
+use crate::terminal::terminal_writer::{TerminalWriter, DEFAULT_SCROLLBACK_SIZE, TERMINAL_WRITERS};
 use crate::timer::global::{current_tick, do_with_timer_manager};
 use crate::timer::{Timer, TIMER_FREQ};
+use crate::vfs;
 use crate::window::{TITLED_WINDOW_BOTTOM_RIGHT_MARGIN, TITLED_WINDOW_TOP_LEFT_MARGIN};
 use crate::{make_error, str_trimming_nul_unchecked, Window};
 use alloc::boxed::Box;
-use alloc::collections::{BTreeMap, VecDeque};
+use alloc::collections::BTreeMap;
 use alloc::string::{String, ToString};
 use alloc::sync::Arc;
 use alloc::vec::Vec;
@@ -41,8 +54,7 @@ use alloc::{format, vec};
 use core::arch::asm;
 use core::ffi::c_void;
 use core::fmt::Write;
-use core::mem;
-use core::ops::DerefMut;
+use core::{cmp, mem};
 use shared::PixelFormat;
 
 static APP_LOADS: Mutex<BTreeMap<usize, AppLoadInfo>> = Mutex::new(BTreeMap::new());
@@ -72,10 +84,9 @@ pub fn task_terminal(task_id: u64, data: usize) {
     let mut terminal = create_terminal(task_id, term_desc, show_window);
 
     if !show_window {
-        for c in command.chars() {
-            terminal.input_key(0, 0, c);
-        }
-        terminal.input_key(0, 0, '\n');
+        // not typed through input_key, so the command is not limited to a line of the terminal
+        terminal.line_buf.set(command);
+        terminal.execute_line();
     }
 
     if let Some(fd) = term_desc {
@@ -139,7 +150,29 @@ pub fn task_terminal(task_id: u64, data: usize) {
                     unsafe { asm!("sti") };
                 }
             }
+            MessageType::MouseWheel(arg) => {
+                let area = terminal.scroll_wheel(arg.delta);
+                if show_window {
+                    let msg = Message::new(Layer(LayerMessage {
+                        layer_id: terminal.layer_id,
+                        op: LayerOperation::DrawArea(area),
+                        src_task_id: task_id,
+                    }));
+                    unsafe { asm!("cli") };
+                    task_manager().send_message(main_task_id(), msg).unwrap();
+                    unsafe { asm!("sti") };
+                }
+            }
+            MessageType::Interrupt => {
+                // the flag is already cleared if the interrupt has stopped a command
+                if terminal.take_interrupted() {
+                    terminal.cancel_line();
+                }
+            }
             MessageType::WindowActive(mode) => active_mode = mode,
+            MessageType::WindowResize(message) => {
+                resize_terminal(message);
+            }
             MessageType::WindowClose(message) => {
                 let _ = layer_manager().lock().close_layer(message.layer_id);
                 unsafe { asm!("cli") };
@@ -150,6 +183,92 @@ pub fn task_terminal(task_id: u64, data: usize) {
     }
 }
 
+struct AppDescriptor {
+    path: String,
+    /// None for the shell, which is built into the terminal
+    file_entry: Option<&'static DirectoryEntry>,
+    argv: Vec<String>,
+    files: [Arc<Mutex<FileDescriptor>>; STD_ERR + 1],
+    cwd: String,
+    environment: BTreeMap<String, String>,
+}
+
+/// Starts the app at `path` in a new task without a window and returns the id of the task.
+///
+/// `files` become stdin, stdout and stderr of the app, `cwd` its current directory
+/// and `environment` its environment variables. `sh` starts the shell unless there is an app
+/// of the name.
+///
+/// An app whose stdin is the slave side of a pty leads its own group, which Ctrl-C typed on the
+/// master side interrupts.
+pub(crate) fn spawn_app(
+    path: &str,
+    argv: Vec<String>,
+    files: [Arc<Mutex<FileDescriptor>>; STD_ERR + 1],
+    cwd: String,
+    environment: BTreeMap<String, String>,
+) -> Result<TaskID, Error> {
+    let (path, file_entry) = match find_command(path, &cwd) {
+        Some((_, file_entry)) if file_entry.is_directory() => {
+            return Err(make_error!(Code::IsDirectory))
+        }
+        Some((path, file_entry)) => (path, Some(file_entry)),
+        None if path == "sh" => (path.to_string(), None),
+        None => return Err(make_error!(Code::NoSuchEntry)),
+    };
+
+    let stdin = Arc::clone(&files[STD_IN]);
+    let app_desc = Box::new(AppDescriptor {
+        path,
+        file_entry,
+        argv,
+        files,
+        cwd,
+        environment,
+    });
+    let group = task_manager().current_task().group();
+    let task = task_manager().new_task();
+    let on_pty = match &*stdin.lock() {
+        FileDescriptor::Pty(pty) => pty.set_foreground(task.id()),
+        _ => false,
+    };
+    if !on_pty {
+        // Ctrl-C interrupts the app together with the caller
+        task.set_group(group);
+    }
+    let task_id = task
+        .init_context(task_app, Box::into_raw(app_desc) as u64, kernel_cr3)
+        .id();
+    task_manager().wake_up(task_id)?;
+    Ok(task_id)
+}
+
+fn task_app(task_id: u64, data: usize) {
+    let app_desc = unsafe { Box::from_raw(data as *mut AppDescriptor) };
+    let term_desc = TerminalDescriptor {
+        command_line: String::new(),
+        exit_after_command: true,
+        show_window: false,
+        files: app_desc.files.clone(),
+        cwd: app_desc.cwd.clone(),
+        environment: app_desc.environment.clone(),
+    };
+    let mut terminal = create_terminal(TaskID::new(task_id), Some(&term_desc), false);
+
+    let argv = app_desc.argv.iter().map(String::as_str).collect::<Vec<_>>();
+    let exit_code = match app_desc.file_entry {
+        Some(file_entry) => terminal.execute_command_file(&app_desc.path, file_entry, &argv),
+        None => terminal.execute_argv(&argv),
+    };
+    drop(argv);
+    drop(terminal);
+    drop(term_desc);
+    drop(app_desc);
+
+    unsafe { asm!("cli") };
+    task_manager().finish(exit_code);
+}
+
 fn create_terminal(
     task_id: TaskID,
     term_desc: Option<&TerminalDescriptor>,
@@ -189,14 +308,46 @@ impl AppLoadInfo {
 pub(super) const ROWS: usize = 15;
 pub(super) const COLUMNS: usize = 60;
 pub(super) const LINE_MAX: usize = 128;
+/// the smallest size a terminal window can be resized to, which holds a whole input line
+/// after the prompt even if the prompt is at the end of a row
+pub(super) const MIN_COLUMNS: usize = 20;
+pub(super) const MIN_ROWS: usize = LINE_MAX / MIN_COLUMNS + 2;
+const MAX_SCRIPT_DEPTH: usize = 8;
+/// the rows a notch of the mouse wheel scrolls the view
+const WHEEL_ROWS: isize = 3;
+/// The file which keeps the command history of the terminals with a window.
+const HISTORY_FILE: &str = "/.history";
+
+/// The commands which `execute_argv` runs by itself.
+const BUILTINS: &[&str] = &[
+    "echo", "clear", "lspci", "cd", "pwd", "ls", "cat", "noterm", "memstat", "export", "unset",
+    "env", "sh", "jobs", "fg", "kill", "sync", "history",
+];
+
+/// Commands started by `&`, which run in a terminal task without a window.
+struct Job {
+    id: usize,
+    task_id: TaskID,
+    command: String,
+}
 
 pub(crate) struct Terminal {
     task_id: TaskID,
     layer_id: LayerID,
-    line_buf: String,
+    line_buf: LineBuffer,
     command_history: CommandHistory,
+    /// the number of lines in the history file, which is rewritten when it grows too long
+    history_file_lines: usize,
+    /// Ctrl-R being typed, which shows a line of the history in place of `line_buf`
+    search: Option<ReverseSearch>,
     files: [Arc<Mutex<FileDescriptor>>; STD_ERR + 1],
     last_exit_code: i32,
+    cwd: String,
+    environment: BTreeMap<String, String>,
+    /// the positional parameters `$0`, `$1`, ... of the running script
+    args: Vec<String>,
+    script_depth: usize,
+    jobs: Vec<Job>,
 }
 
 impl Terminal {
@@ -217,14 +368,26 @@ impl Terminal {
             ]
         };
 
-        Self {
+        let cwd = terminal_desc.map_or_else(|| "/".to_string(), |td| td.cwd.clone());
+        let environment = terminal_desc.map_or_else(BTreeMap::new, |td| td.environment.clone());
+
+        let mut terminal = Self {
             task_id,
             layer_id: LayerID::MAX,
-            line_buf: String::with_capacity(LINE_MAX),
+            line_buf: LineBuffer::new(LINE_MAX),
             command_history: CommandHistory::new(),
+            history_file_lines: 0,
+            search: None,
             files,
             last_exit_code: 0,
-        }
+            cwd: String::new(),
+            environment,
+            args: Vec::new(),
+            script_depth: 0,
+            jobs: Vec::new(),
+        };
+        terminal.set_cwd(cwd);
+        terminal
     }
 
     pub(crate) fn layer_id(&self) -> LayerID {
@@ -251,6 +414,7 @@ impl Terminal {
                 .lock()
                 .new_layer(Arc::clone(&window))
                 .set_draggable(true)
+                .set_resizable(true)
                 .id();
             Some(window)
         } else {
@@ -265,7 +429,8 @@ impl Terminal {
         }
 
         if show_window {
-            self.print(">")
+            self.load_history();
+            self.prompt();
         }
     }
 
@@ -277,84 +442,285 @@ impl Terminal {
         self.writer().draw_cursor(visible)
     }
 
-    fn input_key(&mut self, _modifier: u8, keycode: u8, ascii: char) -> Rectangle<i32> {
-        self.draw_cursor(false);
+    /// Scrolls the view back by `WHEEL_ROWS` for each notch the wheel is turned away from the
+    /// user, or forward if `delta` is negative.
+    fn scroll_wheel(&mut self, delta: i32) -> Rectangle<i32> {
+        self.writer().scroll_view(delta as isize * WHEEL_ROWS)
+    }
 
-        let mut draw_area = Rectangle::new(self.calc_cursor_pos(), Vector2D::new(8 * 2, 16));
+    fn input_key(&mut self, modifier: u8, keycode: u8, ascii: char) -> Rectangle<i32> {
+        self.draw_cursor(false);
 
-        match ascii {
-            '\n' => {
-                self.command_history.push(self.line_buf.to_string());
-
-                self.writer().new_line();
-                self.execute_line();
-                self.print(">");
-                draw_area.pos = TITLED_WINDOW_TOP_LEFT_MARGIN;
-                draw_area.size = self.writer().window_inner_size().unwrap_or(
-                    Vector2D::new(0, 0)
-                        - TITLED_WINDOW_TOP_LEFT_MARGIN
-                        - TITLED_WINDOW_BOTTOM_RIGHT_MARGIN,
-                )
-            }
-            '\x08' => {
-                if self.line_buf.pop().is_some() {
-                    self.writer().back_space();
-                    draw_area.pos = self.calc_cursor_pos();
+        let page_rows = self.writer().rows();
+        let draw_area = if let Some(rows) = scroll_rows(modifier, keycode, page_rows) {
+            self.writer().scroll_view(rows)
+        } else if self.search_history(modifier, ascii) {
+            self.draw_line()
+        } else {
+            match ascii {
+                '\n' => {
+                    self.writer().end_input();
+                    self.writer().new_line();
+                    if self.expand_history() {
+                        self.execute_line();
+                    }
+                    let size = self.scrollback_size();
+                    self.writer().set_scrollback_size(size);
+                    self.report_jobs(false);
+                    self.prompt();
+                    self.window_area()
                 }
-            }
-            '\x00' => {
-                if keycode == 0x51 {
-                    draw_area = self.history_up_down(Direction::Down);
-                } else if keycode == 0x52 {
-                    draw_area = self.history_up_down(Direction::Up);
+                '\t' => self.complete(),
+                _ => {
+                    self.edit_line(modifier, keycode, ascii);
+                    self.draw_line()
                 }
             }
+        };
+
+        self.draw_cursor(true);
+        draw_area
+    }
+
+    fn edit_line(&mut self, modifier: u8, keycode: u8, ascii: char) {
+        let ctrl = is_control_key_inputted(modifier);
+        match ascii {
+            '\x08' => self.line_buf.backspace(),
+            '\0' => match keycode {
+                KEY_UP => self.history_up_down(Direction::Up),
+                KEY_DOWN => self.history_up_down(Direction::Down),
+                KEY_LEFT if ctrl => self.line_buf.move_word_left(),
+                KEY_LEFT => self.line_buf.move_left(),
+                KEY_RIGHT if ctrl => self.line_buf.move_word_right(),
+                KEY_RIGHT => self.line_buf.move_right(),
+                KEY_HOME => self.line_buf.move_home(),
+                KEY_END => self.line_buf.move_end(),
+                KEY_DELETE => self.line_buf.delete(),
+                _ => {}
+            },
+            c if ctrl => match c.to_ascii_lowercase() {
+                'a' => self.line_buf.move_home(),
+                'e' => self.line_buf.move_end(),
+                'u' => self.line_buf.kill_to_start(),
+                'k' => self.line_buf.kill_to_end(),
+                'r' => self.search = Some(ReverseSearch::new()),
+                _ => {}
+            },
+            c => {
+                self.line_buf.insert(c);
+            }
+        }
+    }
+
+    /// Handles a key while Ctrl-R is being typed. Returns false if the key ends the search
+    /// with the line found put in `line_buf`, and is to be handled as usual.
+    fn search_history(&mut self, modifier: u8, ascii: char) -> bool {
+        let search = match &mut self.search {
+            Some(search) => search,
+            None => return false,
+        };
+        let history = &self.command_history;
+        let ctrl = is_control_key_inputted(modifier);
+        match ascii {
+            'r' | 'R' if ctrl => search.next(history),
+            // Ctrl-G gives up the search and restores the line
+            'g' | 'G' if ctrl => self.search = None,
+            '\x08' => search.pop(history),
+            c if !ctrl && (c == ' ' || c.is_ascii_graphic()) => search.push(c, history),
             _ => {
-                if self.writer().can_write_on_this_line() && self.line_buf.len() < LINE_MAX {
-                    self.line_buf.push(ascii);
-                    self.writer().input_ascii(ascii);
+                if let Some(line) = search.line(history) {
+                    self.line_buf.set(line);
                 }
+                self.search = None;
+                return false;
             }
         }
+        true
+    }
 
-        self.draw_cursor(true);
-        draw_area
+    /// Draws the line being typed after the prompt.
+    fn draw_line(&mut self) -> Rectangle<i32> {
+        let (line, cursor) = match &self.search {
+            Some(search) => search.render(&self.command_history),
+            None => (self.line_buf.as_str().to_string(), self.line_buf.cursor()),
+        };
+        self.writer().draw_input(&line, cursor)
+    }
+
+    fn prompt(&mut self) {
+        self.print(">");
+        self.writer().start_input();
     }
 
     fn calc_cursor_pos(&self) -> Vector2D<i32> {
         self.writer().calc_cursor_pos()
     }
 
-    fn execute_line(&mut self) {
-        let line_buf = mem::take(&mut self.line_buf);
-        let mut argv = if let Some(argv) = parse_command(line_buf.as_str()) {
-            argv
+    fn window_area(&self) -> Rectangle<i32> {
+        Rectangle::new(
+            TITLED_WINDOW_TOP_LEFT_MARGIN,
+            self.writer().window_inner_size().unwrap_or(
+                Vector2D::new(0, 0)
+                    - TITLED_WINDOW_TOP_LEFT_MARGIN
+                    - TITLED_WINDOW_BOTTOM_RIGHT_MARGIN,
+            ),
+        )
+    }
+
+    /// Completes the last word of the line from built-in commands, apps and paths,
+    /// or lists the candidates if they share nothing more than the word.
+    fn complete(&mut self) -> Rectangle<i32> {
+        let (word, is_command) = completion::current_word(self.line_buf.before_cursor());
+        let (dir, prefix) = word.rfind('/').map_or(("", word), |i| word.split_at(i + 1));
+        let candidates = if is_command && dir.is_empty() {
+            let names = BUILTINS
+                .iter()
+                .map(|name| name.to_string())
+                .chain(completion::entries("", prefix, &self.cwd))
+                .chain(completion::entries("/apps", prefix, &self.cwd));
+            completion::filter(prefix, names)
         } else {
-            return;
+            completion::entries(dir, prefix, &self.cwd)
         };
-        let original_stdout = Arc::clone(&self.files[STD_OUT]);
-
-        // handles redirect
-        if let Some(redirect_dest_index) = find_redirect_dest(&argv) {
-            match extract_redirect(&argv, redirect_dest_index) {
-                Ok(redirect_dest_file) => {
-                    self.files[STD_OUT] = Arc::new(Mutex::new(FileDescriptor::Fat(
-                        FatFileDescriptor::new(redirect_dest_file),
-                    )))
+
+        let completed = match candidates.as_slice() {
+            [] => return Rectangle::new(self.calc_cursor_pos(), Vector2D::new(8, 16)),
+            [name] if name.ends_with('/') => completion::escape(name),
+            [name] => completion::escape(name) + " ",
+            _ => {
+                let common = completion::common_prefix(&candidates);
+                if common.len() <= prefix.len() {
+                    return self.list_candidates(&candidates);
                 }
-                Err(e) => {
-                    writeln!(self.stderr(), "{}", e).unwrap_or_default();
-                    return;
+                completion::escape(common)
+            }
+        };
+
+        let prefix_len = prefix.len();
+        self.line_buf.replace_before_cursor(prefix_len, &completed);
+        self.draw_line()
+    }
+
+    fn list_candidates(&mut self, candidates: &[String]) -> Rectangle<i32> {
+        self.writer().end_input();
+        self.print("\n");
+        self.print(&candidates.join("  "));
+        self.print("\n");
+        self.prompt();
+        self.draw_line();
+        self.window_area()
+    }
+
+    fn execute_line(&mut self) {
+        let line_buf = self.line_buf.take();
+        self.export_size();
+        match parse(&line_buf) {
+            Ok(list) => self.execute_list(&list),
+            Err(e) => {
+                writeln!(self.stderr(), "{}", e).unwrap_or_default();
+                self.last_exit_code = 2;
+            }
+        }
+        if self.take_interrupted() {
+            self.print("^C\n");
+        }
+        // the apps may have left the terminal in the raw mode
+        self.writer().line_discipline().reset();
+
+        if let Err(e) = vfs::sync() {
+            writeln!(self.stderr(), "failed to write back the volume: {}", e).unwrap_or_default();
+        }
+    }
+
+    /// Discards the line being typed when Ctrl-C is pressed at the prompt.
+    fn cancel_line(&mut self) {
+        self.line_buf.clear();
+        self.search = None;
+        self.writer().end_input();
+        self.print("^C\n");
+        self.prompt();
+    }
+
+    fn execute_list(&mut self, list: &List) {
+        for and_or in list {
+            if and_or.background {
+                self.start_job(and_or);
+                continue;
+            }
+            self.execute_command(&and_or.first);
+            for (connector, command) in &and_or.rest {
+                let succeeded = self.last_exit_code == 0;
+                if succeeded == (*connector == Connector::And) {
+                    self.execute_command(command);
                 }
             }
-            argv = argv[..redirect_dest_index - 1].to_vec();
         }
+    }
 
-        let pipe_fd_for_write = if let Some(pipe_dest_index) = find_pipe_dest(&argv) {
-            let sub_command = join_to_string(' ', &argv[pipe_dest_index..]);
-            argv = argv[..pipe_dest_index - 1].to_vec();
+    fn execute_command(&mut self, command: &Command) {
+        // the rest of the line is skipped after Ctrl-C
+        if self.is_interrupted() {
+            return;
+        }
+        match command {
+            Command::Pipeline(commands) => self.execute_pipeline(commands),
+            Command::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    self.execute_list(condition);
+                    if self.last_exit_code == 0 {
+                        self.execute_list(body);
+                        return;
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.execute_list(body),
+                    None => self.last_exit_code = 0,
+                }
+            }
+            Command::For { name, words, body } => {
+                let values = words
+                    .iter()
+                    .filter_map(|word| word.expand(&|name| self.variable(name)))
+                    .collect::<Vec<_>>();
+                self.last_exit_code = 0;
+                for value in values {
+                    self.environment.insert(name.clone(), value);
+                    self.execute_list(body);
+                }
+            }
+        }
+    }
 
-            let sub_task = task_manager().new_task();
+    fn execute_pipeline(&mut self, commands: &[SimpleCommand]) {
+        let command = commands[0].expand(&|name| self.variable(name));
+        let original_files = self.files.clone();
+
+        let redirect_files = match command
+            .redirects
+            .iter()
+            .map(|redirect| open_redirect(redirect, &self.cwd))
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(files) => files,
+            Err(e) => {
+                writeln!(self.stderr(), "{}", e).unwrap_or_default();
+                self.last_exit_code = 1;
+                return;
+            }
+        };
+
+        let pipe_fd_for_write = if commands.len() > 1 {
+            let sub_command = commands[1..]
+                .iter()
+                .map(|c| c.expand(&|name| self.variable(name)).to_command_line())
+                .collect::<Vec<_>>()
+                .join(" | ");
+
+            let group = self.group();
+            let sub_task = task_manager().new_task().set_group(group);
             let pipe_fd = PipeDescriptor::new(sub_task.id());
             let pipe_fd_for_write = pipe_fd.copy_for_write();
 
@@ -367,6 +733,8 @@ impl Terminal {
                     self.files[STD_OUT].clone(),
                     self.files[STD_ERR].clone(),
                 ],
+                cwd: self.cwd.clone(),
+                environment: self.environment.clone(),
             };
             let b = Box::new(term_desc);
             sub_task.init_context(task_terminal, Box::into_raw(b) as u64, get_cr3);
@@ -383,21 +751,43 @@ impl Terminal {
             None
         };
 
-        let command = match argv.first() {
-            None => return, // if enters a line that starts with '>' such as '> foo'
-            Some(&c) => c,
+        // handles redirects, which take the place of the pipe
+        for (redirect, file) in command.redirects.iter().zip(redirect_files) {
+            self.files[redirect.fd] = match file {
+                Some(file) => Arc::new(Mutex::new(file)),
+                None => Arc::clone(&self.files[redirect.target.parse::<usize>().unwrap()]),
+            };
+        }
+
+        let argv = command.argv.iter().map(String::as_str).collect::<Vec<_>>();
+        // a command may be only redirects such as '> foo'
+        let exit_code = if argv.is_empty() {
+            0
+        } else {
+            self.execute_argv(&argv)
         };
-        let exit_code = match command {
+
+        if let Some(mut fd) = pipe_fd_for_write {
+            fd.finish_write();
+            unsafe { asm!("cli") };
+            let ec = task_manager().wait_finish(fd.task_id);
+            unsafe { asm!("sti") };
+            layer_manager()
+                .lock()
+                .register_layer_task_relation(self.layer_id, self.task_id);
+            self.last_exit_code = ec;
+        } else {
+            self.last_exit_code = exit_code;
+        }
+
+        self.files = original_files;
+    }
+
+    fn execute_argv(&mut self, argv: &[&str]) -> i32 {
+        let command = argv[0];
+        match command {
             "echo" => {
-                if let Some(&arg) = argv.get(1) {
-                    let _ = if arg == "$?" {
-                        let last = self.last_exit_code;
-                        write!(self.stdout(), "{}", last)
-                    } else {
-                        write!(self.stdout(), "{}", arg)
-                    };
-                }
-                let _ = writeln!(self.stdout());
+                let _ = writeln!(self.stdout(), "{}", argv[1..].join(" "));
                 0
             }
             "clear" => {
@@ -410,41 +800,180 @@ impl Terminal {
                 }
                 0
             }
-            "ls" => self.execute_ls(&argv),
-            "cat" => self.execute_cat(&argv),
-            "noterm" => self.exec_noterm(&argv),
+            "cd" => self.execute_cd(argv),
+            "pwd" => {
+                let cwd = self.cwd.clone();
+                writeln!(self.stdout(), "{}", cwd).unwrap();
+                0
+            }
+            "ls" => self.execute_ls(argv),
+            "cat" => self.execute_cat(argv),
+            "noterm" => self.exec_noterm(argv),
             "memstat" => self.execute_memstat(),
+            "export" => self.execute_export(argv),
+            "unset" => self.execute_unset(argv),
+            "env" => self.execute_env(),
+            "sh" => self.execute_sh(argv),
+            "jobs" => {
+                self.report_jobs(true);
+                0
+            }
+            "fg" => self.execute_fg(argv),
+            "history" => self.execute_history(argv),
+            "kill" => self.execute_kill(argv),
+            "sync" => match vfs::sync() {
+                Ok(_) => 0,
+                Err(e) => {
+                    writeln!(self.stderr(), "sync: {}", e).unwrap_or_default();
+                    1
+                }
+            },
             _ => {
-                let root_cluster = boot_volume_image().get_root_cluster();
-                if let Some(file_entry) = find_command(command, root_cluster as u64) {
-                    match self.execute_file(file_entry, argv.as_slice()) {
-                        Ok(ec) => ec,
-                        Err((ec, err)) => {
-                            let _ = writeln!(self.stderr(), "failed to exec file: {}", err);
-                            -ec
-                        }
-                    }
+                if let Some((path, file_entry)) = find_command(command, &self.cwd) {
+                    self.execute_command_file(&path, file_entry, argv)
                 } else {
                     writeln!(self.stderr(), "no such command: {}", command).unwrap();
                     1
                 }
             }
+        }
+    }
+
+    /// Runs the app, or the script if the file starts with `#!`.
+    fn execute_command_file(
+        &mut self,
+        path: &str,
+        file_entry: &DirectoryEntry,
+        argv: &[&str],
+    ) -> i32 {
+        let mut magic = [0; 2];
+        file_entry.load_file(&mut magic, boot_volume_image());
+        if &magic == b"#!" {
+            let mut script = vec![0; file_entry.file_size() as usize];
+            file_entry.load_file(&mut script, boot_volume_image());
+            return self.execute_script(path, &script, argv);
+        }
+
+        match self.execute_file(file_entry, argv) {
+            Ok(ec) => ec,
+            Err((ec, err)) => {
+                let _ = writeln!(self.stderr(), "failed to exec file: {}", err);
+                -ec
+            }
+        }
+    }
+
+    /// Runs `sh <file> [args...]`, which reads the script from any filesystem, or the commands
+    /// from stdin without a file.
+    fn execute_sh(&mut self, argv: &[&str]) -> i32 {
+        let arg = match argv.get(1) {
+            Some(&arg) => arg,
+            None => return self.execute_stdin(),
         };
+        let path = resolve_path(&self.cwd, arg);
+        let script = vfs::open(&path, false, false).map(|mut fd| {
+            let mut script = Vec::new();
+            let mut buf = [0; 512];
+            loop {
+                let n = fd.read(&mut buf);
+                if n == 0 {
+                    break script;
+                }
+                script.extend_from_slice(&buf[..n]);
+            }
+        });
+        match script {
+            Ok(script) => self.execute_script(&path, &script, &argv[1..]),
+            Err(e) => {
+                writeln!(self.stderr(), "sh: cannot open {}: {}", arg, e).unwrap_or_default();
+                2
+            }
+        }
+    }
 
-        if let Some(mut fd) = pipe_fd_for_write {
-            fd.finish_write();
-            unsafe { asm!("cli") };
-            let ec = task_manager().wait_finish(fd.task_id);
-            unsafe { asm!("sti") };
-            layer_manager()
-                .lock()
-                .register_layer_task_relation(self.layer_id, self.task_id);
-            self.last_exit_code = ec;
-        } else {
-            self.last_exit_code = exit_code;
+    /// Runs the commands read from stdin line by line until it ends, showing a prompt if stdin is
+    /// a terminal such as the slave side of a pty.
+    fn execute_stdin(&mut self) -> i32 {
+        let stdin = Arc::clone(&self.files[STD_IN]);
+        let interactive = stdin.lock().stat().mode == S_IFCHR;
+        self.last_exit_code = 0;
+        loop {
+            if interactive {
+                write!(self.stdout(), "> ").unwrap_or_default();
+            }
+            let line = match read_line(&stdin) {
+                Some(line) => line,
+                // Ctrl-C discards the line being typed
+                None if interactive && self.take_interrupted() => continue,
+                None => break,
+            };
+            match parse(&String::from_utf8_lossy(&line)) {
+                Ok(list) => self.execute_list(&list),
+                Err(e) => {
+                    writeln!(self.stderr(), "sh: {}", e).unwrap_or_default();
+                    self.last_exit_code = 2;
+                }
+            }
+            // Ctrl-C stops only the command if the commands are typed
+            if self.take_interrupted() && !interactive {
+                break;
+            }
+            // the apps may have left the terminal in the raw mode
+            let _ = stdin.lock().set_terminal_mode(TerminalMode::DEFAULT);
+        }
+        self.last_exit_code
+    }
+
+    /// Runs the script at `path` with the positional parameters `argv`.
+    ///
+    /// If the script starts with `#!` naming an interpreter other than `sh`, the interpreter
+    /// runs with the path of the script following its arguments.
+    fn execute_script(&mut self, path: &str, script: &[u8], argv: &[&str]) -> i32 {
+        if self.script_depth >= MAX_SCRIPT_DEPTH {
+            writeln!(self.stderr(), "{}: scripts are nested too deeply", argv[0])
+                .unwrap_or_default();
+            return 2;
         }
+        let script = match core::str::from_utf8(script) {
+            Ok(s) => s,
+            Err(_) => {
+                writeln!(self.stderr(), "{}: not a text file", argv[0]).unwrap_or_default();
+                return 2;
+            }
+        };
 
-        self.files[STD_OUT] = original_stdout;
+        let interpreter = script
+            .strip_prefix("#!")
+            .and_then(|s| s.lines().next())
+            .map(|line| line.split_whitespace().collect::<Vec<_>>())
+            .filter(|words| {
+                words
+                    .first()
+                    .map_or(false, |w| w.rsplit('/').next() != Some("sh"))
+            });
+
+        self.script_depth += 1;
+        let exit_code = if let Some(mut interpreter_argv) = interpreter {
+            interpreter_argv.push(path);
+            interpreter_argv.extend_from_slice(&argv[1..]);
+            self.execute_argv(&interpreter_argv)
+        } else {
+            match parse(script) {
+                Ok(list) => {
+                    let args = argv.iter().map(|arg| arg.to_string()).collect();
+                    let caller_args = mem::replace(&mut self.args, args);
+                    self.execute_list(&list);
+                    self.args = caller_args;
+                    self.last_exit_code
+                }
+                Err(e) => {
+                    writeln!(self.stderr(), "{}: {}", argv[0], e).unwrap_or_default();
+                    2
+                }
+            }
+        };
+        self.script_depth -= 1;
+        exit_code
     }
 
     fn execute_file(
@@ -458,21 +987,26 @@ impl Terminal {
 
         let app_load = self.load_app(file_entry, task).map_err(|e| (0, e))?;
 
-        let args_frame_addr = LinearAddress4Level::new(0xffff_ffff_ffff_f000);
-        PageMapEntry::setup_page_maps(args_frame_addr, 1, true, get_cr3()).map_err(|e| (0, e))?;
-        let argv = args_frame_addr.value() as *mut u64 as *mut *mut c_char;
-        let argv_len = 32; // argv = 8x32 = 256 bytes
-        let p_p_cchar_size = mem::size_of::<*const *const c_char>();
-        let argbuf =
-            (args_frame_addr.value() as usize + p_p_cchar_size * argv_len) as *const c_char;
-        let argbuf_len = 4096 - p_p_cchar_size * argv_len;
-
-        let c_chars_vec = new_c_chars_vec(args);
-        let argc =
-            make_argv(&c_chars_vec, argv, argv_len, argbuf, argbuf_len).map_err(|e| (0, e))?;
+        let envs = self
+            .environment
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>();
+        let envs = envs.iter().map(String::as_str).collect::<Vec<_>>();
+        let args_block = ArgsBlock::new(args, &envs);
+        let args_frame_addr = LinearAddress4Level::new(args_block.base);
+        PageMapEntry::setup_page_maps(args_frame_addr, args_block.num_pages(), true, get_cr3())
+            .map_err(|e| (0, e))?;
+        unsafe {
+            memcpy(
+                args_block.base as *mut c_void,
+                args_block.image.as_ptr() as *const c_void,
+                args_block.image.len(),
+            );
+        }
 
         let stack_size = Task::DEFAULT_STACK_BYTES;
-        let stack_frame_addr = LinearAddress4Level::new(0xffff_ffff_ffff_f000 - stack_size as u64);
+        let stack_frame_addr = LinearAddress4Level::new(args_block.base - stack_size as u64);
         PageMapEntry::setup_page_maps(stack_frame_addr, stack_size / 4096, true, get_cr3())
             .map_err(|e| (0, e))?;
 
@@ -487,8 +1021,9 @@ impl Terminal {
         task.file_map_end = stack_frame_addr.value();
 
         let ret = call_app(
-            argc as i32,
-            argv as *const *const c_char,
+            args.len() as i32,
+            args_block.argv(),
+            args_block.envp(),
             3 << 3 | 3,
             app_load.entry,
             stack_frame_addr.value() + stack_size as u64 - 8,
@@ -497,10 +1032,6 @@ impl Terminal {
 
         task.clear_files();
         task.clear_file_mappings();
-        // retake pointers to free memory
-        for c_arg in c_chars_vec {
-            let _ = unsafe { CString::from_raw(c_arg as *mut c_char) };
-        }
 
         PageMapEntry::clean_page_maps(LinearAddress4Level::new(0xffff_8000_0000_0000), get_cr3())
             .map_err(|e| (ret, e))?;
@@ -554,69 +1085,193 @@ impl Terminal {
         self.writer().redraw()
     }
 
-    fn history_up_down(&mut self, direction: Direction) -> Rectangle<i32> {
-        self.line_buf = match direction {
-            Direction::Up => self.command_history.up().to_string(),
-            Direction::Down => self.command_history.down().to_string(),
+    /// Replaces the history references such as `!n` in the line typed and adds it to the history.
+    /// Returns false if a reference is not found.
+    fn expand_history(&mut self) -> bool {
+        let line = match self.command_history.expand(self.line_buf.as_str()) {
+            Ok(line) => line,
+            Err(e) => {
+                self.line_buf.clear();
+                writeln!(self.stderr(), "{}", e).unwrap_or_default();
+                self.last_exit_code = 1;
+                return false;
+            }
         };
+        if line != self.line_buf.as_str() {
+            // shows the line to be executed
+            self.print(&line);
+            self.print("\n");
+            self.line_buf.set(&line);
+        }
 
-        self.writer().history_up_down(self.line_buf.as_str())
+        let size = self.history_size();
+        self.command_history.set_size(size);
+        let typed = !line.is_empty();
+        self.command_history.push(line);
+        if typed {
+            self.save_history();
+        }
+        true
     }
 
-    fn execute_ls(&mut self, argv: &[&str]) -> i32 {
-        let root_cluster = boot_volume_image().get_root_cluster();
+    /// Returns the number of lines kept in the history, which `HISTSIZE` changes.
+    fn history_size(&self) -> usize {
+        self.environment
+            .get("HISTSIZE")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(CommandHistory::DEFAULT_SIZE)
+    }
 
-        let first_arg = argv.get(1);
-        if first_arg.is_none() {
-            list_all_entries(self.stdout(), root_cluster);
-            return 0;
-        }
+    /// Returns the number of rows kept in the scrollback, which `SCROLLBACK` changes.
+    fn scrollback_size(&self) -> usize {
+        self.environment
+            .get("SCROLLBACK")
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_SCROLLBACK_SIZE)
+    }
 
-        let &first_arg = first_arg.unwrap();
-        let (dir, post_slash) = find_file(first_arg, root_cluster.into());
-        if dir.is_none() {
-            writeln!(self.stderr(), "No such file or directory: {}", first_arg).unwrap();
-            return 1;
-        }
+    /// Sets `COLUMNS` and `LINES` to the size of the window, which the apps started by the line
+    /// get. The apps running when the window is resized get `AppEventType::TerminalResize`.
+    fn export_size(&mut self) {
+        let (columns, rows) = {
+            let writer = self.writer();
+            if writer.window().is_none() {
+                return;
+            }
+            (writer.columns(), writer.rows())
+        };
+        self.environment
+            .insert("COLUMNS".to_string(), columns.to_string());
+        self.environment
+            .insert("LINES".to_string(), rows.to_string());
+    }
 
-        let dir = dir.unwrap();
-        if dir.is_directory() {
-            list_all_entries(self.stdout(), dir.first_cluster());
-            return 1;
+    fn load_history(&mut self) {
+        let mut file = match vfs::open(HISTORY_FILE, false, false) {
+            Ok(file) => file,
+            Err(_) => return,
+        };
+        let mut text = vec![0; file.size()];
+        let len = file.read(&mut text);
+        let text = String::from_utf8_lossy(&text[..len]);
+        self.history_file_lines = text.lines().count();
+        self.command_history.load(&text);
+        let size = self.history_size();
+        self.command_history.set_size(size);
+    }
+
+    /// Appends the newest line of the history to the history file, which is rewritten with
+    /// the whole history instead once it holds twice as many lines as the history keeps.
+    fn save_history(&mut self) {
+        let line = match self.command_history.recent(0) {
+            Some(line) => format!("{}\n", line),
+            None => return,
+        };
+        let rewrite = self.history_file_lines >= 2 * self.history_size();
+        let saved = if rewrite {
+            let text = self.command_history.to_text();
+            vfs::open(HISTORY_FILE, true, true).map(|mut file| {
+                file.write(text.as_bytes());
+                text.lines().count()
+            })
+        } else {
+            vfs::open(HISTORY_FILE, true, false).and_then(|mut file| {
+                file.seek(SeekFrom::End(0))?;
+                file.write(line.as_bytes());
+                Ok(self.history_file_lines + 1)
+            })
+        };
+        match saved {
+            Ok(lines) => self.history_file_lines = lines,
+            Err(e) => {
+                writeln!(self.stderr(), "failed to save the history: {}", e).unwrap_or_default()
+            }
         }
+    }
 
-        let name_bytes = dir.formatted_name();
-        let name = str_trimming_nul_unchecked(&name_bytes);
+    fn history_up_down(&mut self, direction: Direction) {
+        self.line_buf.set(match direction {
+            Direction::Up => self.command_history.up(),
+            Direction::Down => self.command_history.down(),
+        });
+    }
 
-        if post_slash {
-            writeln!(self.stderr(), "{} is not a directory", name).unwrap();
-            1
-        } else {
-            writeln!(self.stdout(), "{}", name).unwrap();
-            0
+    fn execute_ls(&mut self, argv: &[&str]) -> i32 {
+        let arg = argv.get(1).copied().unwrap_or(".");
+        let path = resolve_path(&self.cwd, arg);
+        let listed = vfs::lookup(&path).and_then(|stat| {
+            if stat.mode == S_IFDIR {
+                vfs::read_dir(&path).map(|items| items.into_iter().map(|item| item.name).collect())
+            } else {
+                Ok(vec![arg
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap()
+                    .to_string()])
+            }
+        });
+
+        match listed {
+            Ok(names) => {
+                for name in names {
+                    writeln!(self.stdout(), "{}", name).unwrap();
+                }
+                0
+            }
+            Err(e) => {
+                let _ = match e.code {
+                    Code::NotDirectory => writeln!(self.stderr(), "{} is not a directory", arg),
+                    _ => writeln!(self.stderr(), "No such file or directory: {}", arg),
+                };
+                1
+            }
         }
     }
 
-    fn execute_cat(&mut self, argv: &[&str]) -> i32 {
-        let bpb = boot_volume_image();
-        let fd = if let Some(first_arg) = argv.get(1) {
-            let (file_entry, post_slash) = find_file(first_arg, bpb.get_root_cluster() as u64);
-            if file_entry.is_none() {
-                writeln!(self.stderr(), "no such file: {}", first_arg).unwrap();
+    fn execute_cd(&mut self, argv: &[&str]) -> i32 {
+        let arg = argv.get(1).copied().unwrap_or("/");
+        let mut path = resolve_path(&self.cwd, arg);
+        match vfs::lookup(&path) {
+            Ok(stat) if stat.mode == S_IFDIR => {}
+            Ok(_) => {
+                writeln!(self.stderr(), "not a directory: {}", arg).unwrap_or_default();
                 return 1;
             }
-
-            let file_entry = file_entry.unwrap();
-            if !file_entry.is_directory() && post_slash {
-                let name_bytes = file_entry.formatted_name();
-                let name = str_trimming_nul_unchecked(&name_bytes);
-                writeln!(self.stderr(), "{} is not a directory", name).unwrap();
+            Err(e) => {
+                let _ = match e.code {
+                    Code::NotDirectory => writeln!(self.stderr(), "not a directory: {}", arg),
+                    _ => writeln!(self.stderr(), "no such directory: {}", arg),
+                };
                 return 1;
             }
+        }
+
+        if path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+        self.set_cwd(path);
+        0
+    }
 
-            Arc::new(Mutex::new(FileDescriptor::Fat(FatFileDescriptor::new(
-                file_entry,
-            ))))
+    fn execute_cat(&mut self, argv: &[&str]) -> i32 {
+        let fd = if let Some(first_arg) = argv.get(1) {
+            let path = resolve_path(&self.cwd, first_arg);
+            match vfs::open(&path, false, false) {
+                Ok(fd) => Arc::new(Mutex::new(fd)),
+                Err(e) => {
+                    let _ = match e.code {
+                        Code::NotDirectory => {
+                            writeln!(self.stderr(), "{} is not a directory", first_arg)
+                        }
+                        Code::IsDirectory => {
+                            writeln!(self.stderr(), "{} is a directory", first_arg)
+                        }
+                        _ => writeln!(self.stderr(), "no such file: {}", first_arg),
+                    };
+                    return 1;
+                }
+            }
         } else {
             Arc::clone(&self.files[STD_IN])
         };
@@ -650,7 +1305,11 @@ impl Terminal {
 
     fn exec_noterm(&mut self, argv: &[&str]) -> i32 {
         assert_eq!(argv[0], "noterm");
-        let argv_without_noterm = join_to_string(' ', &argv[1..]);
+        let argv_without_noterm = argv[1..]
+            .iter()
+            .map(|arg| quote(arg))
+            .collect::<Vec<_>>()
+            .join(" ");
         if argv_without_noterm.is_empty() {
             return 0;
         }
@@ -660,6 +1319,8 @@ impl Terminal {
             exit_after_command: true,
             show_window: false,
             files: self.files.clone(),
+            cwd: self.cwd.clone(),
+            environment: self.environment.clone(),
         };
         let b = Box::new(term_dec);
         let task_id = task_manager()
@@ -670,36 +1331,242 @@ impl Terminal {
         0
     }
 
-    fn stdout(&mut self) -> MutexGuard<FileDescriptor> {
-        self.files[STD_OUT].lock()
+    /// Runs the commands in a new terminal task, which leads its own group
+    /// so that Ctrl-C does not interrupt it, and reads nothing from stdin.
+    fn start_job(&mut self, and_or: &AndOr) {
+        let command = and_or.to_string();
+        let term_desc = TerminalDescriptor {
+            command_line: command.clone(),
+            exit_after_command: true,
+            show_window: false,
+            files: [
+                Arc::new(Mutex::new(FileDescriptor::Null)),
+                self.files[STD_OUT].clone(),
+                self.files[STD_ERR].clone(),
+            ],
+            cwd: self.cwd.clone(),
+            environment: self.environment.clone(),
+        };
+        let b = Box::new(term_desc);
+        let task_id = task_manager()
+            .new_task()
+            .init_context(task_terminal, Box::into_raw(b) as u64, get_cr3)
+            .id();
+        task_manager().wake_up(task_id).unwrap();
+
+        let id = self.jobs.last().map_or(1, |job| job.id + 1);
+        writeln!(self.stdout(), "[{}] {}", id, task_id.value()).unwrap_or_default();
+        self.jobs.push(Job {
+            id,
+            task_id,
+            command,
+        });
+        self.last_exit_code = 0;
     }
 
-    fn stderr(&mut self) -> MutexGuard<FileDescriptor> {
-        self.files[STD_ERR].lock()
+    /// Prints the finished jobs and forgets them, and also prints the running jobs if `all` is true.
+    fn report_jobs(&mut self, all: bool) {
+        for job in mem::take(&mut self.jobs) {
+            unsafe { asm!("cli") };
+            let exit_code = task_manager().try_wait(job.task_id);
+            unsafe { asm!("sti") };
+            let state = match exit_code {
+                None => "Running".to_string(),
+                Some(0) => "Done".to_string(),
+                Some(ec) => format!("Exit {}", ec),
+            };
+            if exit_code.is_some() || all {
+                writeln!(self.stdout(), "[{}] {:<8} {}", job.id, state, job.command)
+                    .unwrap_or_default();
+            }
+            if exit_code.is_none() {
+                self.jobs.push(job);
+            }
+        }
     }
 
-    fn writer(&self) -> MutexGuard<TerminalWriter> {
-        unsafe { TERMINAL_WRITERS.get(self.task_id).lock() }
+    /// Returns the index of the job given as `%n` or `n`, or of the latest job if `spec` is `None`.
+    fn find_job(&self, spec: Option<&str>) -> Result<usize, String> {
+        let index = match spec {
+            None => self.jobs.len().checked_sub(1),
+            Some(spec) => {
+                let id = spec.strip_prefix('%').unwrap_or(spec).parse::<usize>().ok();
+                self.jobs.iter().position(|job| Some(job.id) == id)
+            }
+        };
+        index.ok_or_else(|| format!("no such job: {}", spec.unwrap_or("current")))
     }
-}
 
-fn list_all_entries<T: DerefMut<Target = FileDescriptor>>(mut fd: T, dir_cluster: u32) {
-    let mut dir_cluster = dir_cluster as u64;
+    /// Brings the job to the foreground, where it receives the keys of the window, and waits for it.
+    fn execute_fg(&mut self, argv: &[&str]) -> i32 {
+        let index = match self.find_job(argv.get(1).copied()) {
+            Ok(index) => index,
+            Err(e) => {
+                writeln!(self.stderr(), "fg: {}", e).unwrap_or_default();
+                return 1;
+            }
+        };
+        let job = self.jobs.remove(index);
+        writeln!(self.stdout(), "{}", job.command).unwrap_or_default();
+
+        layer_manager()
+            .lock()
+            .register_layer_task_relation(self.layer_id, job.task_id);
+        unsafe { asm!("cli") };
+        let exit_code = task_manager().wait_finish(job.task_id);
+        unsafe { asm!("sti") };
+        layer_manager()
+            .lock()
+            .register_layer_task_relation(self.layer_id, self.task_id);
+        if exit_code == 128 + SIGINT {
+            self.print("^C\n");
+        }
+        exit_code
+    }
 
-    while dir_cluster != END_OF_CLUSTER_CHAIN {
-        let dirs = boot_volume_image().get_sector_by_cluster::<DirectoryEntry>(dir_cluster);
-        for dir in dirs {
-            if dir.is_free_and_no_more_allocated_after_this() {
-                break;
+    /// Interrupts jobs given as `%n` or tasks given by their IDs as Ctrl-C does.
+    fn execute_kill(&mut self, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            writeln!(self.stderr(), "Usage: kill %job|task_id...").unwrap_or_default();
+            return 1;
+        }
+        let mut exit_code = 0;
+        for arg in &argv[1..] {
+            let task_id = if arg.starts_with('%') {
+                self.find_job(Some(arg)).map(|i| self.jobs[i].task_id)
+            } else {
+                arg.parse()
+                    .map(TaskID::new)
+                    .map_err(|_| format!("invalid task id: {}", arg))
+            };
+            let result = task_id.and_then(|task_id| {
+                unsafe { asm!("cli") };
+                let result = task_manager().interrupt(task_id);
+                unsafe { asm!("sti") };
+                result.map_err(|_| format!("no such task: {}", task_id.value()))
+            });
+            if let Err(e) = result {
+                writeln!(self.stderr(), "kill: {}", e).unwrap_or_default();
+                exit_code = 1;
             }
-            if dir.is_free() || dir.attr() == Some(Attribute::LongName) {
-                continue;
+        }
+        exit_code
+    }
+
+    /// Lists the lines of the history with their numbers, or only the last `argv[1]` lines.
+    fn execute_history(&mut self, argv: &[&str]) -> i32 {
+        let count = match argv.get(1).map(|count| count.parse::<usize>()) {
+            None => usize::MAX,
+            Some(Ok(count)) => count,
+            Some(Err(_)) => {
+                writeln!(self.stderr(), "history: invalid number: {}", argv[1]).unwrap_or_default();
+                return 1;
+            }
+        };
+        let entries = self
+            .command_history
+            .entries()
+            .map(|(n, line)| format!("{:>5}  {}", n, line))
+            .collect::<Vec<_>>();
+        for entry in &entries[entries.len().saturating_sub(count)..] {
+            writeln!(self.stdout(), "{}", entry).unwrap_or_default();
+        }
+        0
+    }
+
+    fn execute_export(&mut self, argv: &[&str]) -> i32 {
+        if argv.len() < 2 {
+            return self.execute_env();
+        }
+        let mut exit_code = 0;
+        for arg in &argv[1..] {
+            // every variable is exported, so a name without a value changes nothing
+            let (name, value) = arg.split_once('=').unwrap_or((arg, ""));
+            if !is_name(name) {
+                writeln!(self.stderr(), "export: invalid name: {}", name).unwrap_or_default();
+                exit_code = 1;
+            } else if arg.contains('=') {
+                self.environment.insert(name.to_string(), value.to_string());
             }
+        }
+        exit_code
+    }
 
-            let name = dir.formatted_name();
-            writeln!(fd, "{}", str_trimming_nul_unchecked(&name)).unwrap();
+    fn execute_unset(&mut self, argv: &[&str]) -> i32 {
+        for name in &argv[1..] {
+            self.environment.remove(*name);
         }
-        dir_cluster = boot_volume_image().next_cluster(dir_cluster);
+        0
+    }
+
+    fn execute_env(&mut self) -> i32 {
+        for (name, value) in self.environment.clone() {
+            writeln!(self.stdout(), "{}={}", name, value).unwrap_or_default();
+        }
+        0
+    }
+
+    /// Changes the current directory of both the terminal and its task.
+    fn set_cwd(&mut self, cwd: String) {
+        unsafe { asm!("cli") };
+        if let Some(task) = task_manager().get_task_mut(self.task_id) {
+            task.set_cwd(cwd.clone());
+        }
+        unsafe { asm!("sti") };
+        self.cwd = cwd;
+    }
+
+    /// The group of tasks which Ctrl-C interrupts together with the terminal.
+    fn group(&self) -> TaskID {
+        unsafe { asm!("cli") };
+        let group = task_manager()
+            .get_task(self.task_id)
+            .map_or(self.task_id, |task| task.group());
+        unsafe { asm!("sti") };
+        group
+    }
+
+    fn is_interrupted(&self) -> bool {
+        unsafe { asm!("cli") };
+        let interrupted = task_manager()
+            .get_task(self.task_id)
+            .map_or(false, |task| task.is_interrupted());
+        unsafe { asm!("sti") };
+        interrupted
+    }
+
+    fn take_interrupted(&mut self) -> bool {
+        unsafe { asm!("cli") };
+        let interrupted = task_manager()
+            .get_task_mut(self.task_id)
+            .map_or(false, |task| task.take_interrupted());
+        unsafe { asm!("sti") };
+        interrupted
+    }
+
+    /// Returns the value of the shell variable `name`, which is `?`, a positional parameter
+    /// of the running script or an environment variable.
+    fn variable(&self, name: &str) -> Option<String> {
+        match name {
+            "?" => Some(self.last_exit_code.to_string()),
+            "#" => Some(self.args.len().saturating_sub(1).to_string()),
+            _ => match name.parse::<usize>() {
+                Ok(i) => self.args.get(i).cloned(),
+                Err(_) => self.environment.get(name).cloned(),
+            },
+        }
+    }
+
+    fn stdout(&mut self) -> MutexGuard<FileDescriptor> {
+        self.files[STD_OUT].lock()
+    }
+
+    fn stderr(&mut self) -> MutexGuard<FileDescriptor> {
+        self.files[STD_ERR].lock()
+    }
+
+    fn writer(&self) -> MutexGuard<TerminalWriter> {
+        unsafe { TERMINAL_WRITERS.get(self.task_id).lock() }
     }
 }
 
@@ -709,6 +1576,71 @@ impl Drop for Terminal {
     }
 }
 
+/// Returns how many rows the key scrolls the view back, or forward if it is negative.
+fn scroll_rows(modifier: u8, keycode: u8, rows: usize) -> Option<isize> {
+    let page = rows as isize - 1;
+    let shift = is_shift_key_inputted(modifier);
+    match keycode {
+        KEY_PAGE_UP => Some(page),
+        KEY_PAGE_DOWN => Some(-page),
+        KEY_UP if shift => Some(1),
+        KEY_DOWN if shift => Some(-1),
+        _ => None,
+    }
+}
+
+/// Resizes the terminal window whose border has been dragged to fit whole characters,
+/// and returns the new numbers of columns and rows.
+///
+/// Returns None if the window is not of a terminal.
+pub(crate) fn resize_terminal(message: WindowResizeMessage) -> Option<(usize, usize)> {
+    let writer = unsafe { TERMINAL_WRITERS.find_by_layer(message.layer_id) }?;
+    let window = writer.lock().window()?;
+    let old_size = window.lock().size().to_i32_vec2d();
+    let frame_size = Vector2D::new(8, 8) + Window::TITLED_WINDOW_MARGIN;
+    let diff = Vector2D::new(
+        message.diff.x * message.edges.x,
+        message.diff.y * message.edges.y,
+    );
+    let text_size = old_size - frame_size + diff;
+    let columns = cmp::max(text_size.x / 8, MIN_COLUMNS as i32) as usize;
+    let rows = cmp::max(text_size.y / 16, MIN_ROWS as i32) as usize;
+    let new_size = Vector2D::new(8 * columns as i32, 16 * rows as i32) + frame_size;
+    if new_size == old_size {
+        return None;
+    }
+
+    let is_active = layer_manager().lock().get_active_layer_id() == Some(message.layer_id);
+    {
+        let mut window = window.lock();
+        window.resize(new_size.x as usize, new_size.y as usize, is_active);
+        let inner_size = window.inner_size();
+        draw_terminal(&mut *window, Vector2D::new(0, 0), inner_size);
+    }
+    writer.lock().resize(columns, rows);
+
+    // the opposite edges stay where they are
+    let shift = Vector2D::new(
+        if message.edges.x < 0 {
+            old_size.x - new_size.x
+        } else {
+            0
+        },
+        if message.edges.y < 0 {
+            old_size.y - new_size.y
+        } else {
+            0
+        },
+    );
+    let mut manager = layer_manager().lock();
+    let layer = manager.get_layer_mut(message.layer_id)?;
+    let old_area = Rectangle::new(layer.position(), old_size);
+    layer.move_relative(shift);
+    manager.draw_on(old_area);
+    manager.draw_layer_of(message.layer_id);
+    Some((columns, rows))
+}
+
 fn draw_terminal<W: PixelWriter>(w: &mut W, pos: Vector2D<i32>, size: Vector2D<i32>) {
     draw_text_box_with_colors(
         w,
@@ -720,92 +1652,95 @@ fn draw_terminal<W: PixelWriter>(w: &mut W, pos: Vector2D<i32>, size: Vector2D<i
     );
 }
 
-fn parse_command(s: &str) -> Option<Vec<&str>> {
-    let parsed = s.split_whitespace().collect::<VecDeque<_>>();
-    if parsed.is_empty() {
-        return None;
-    }
-
-    Some(Vec::from(parsed))
+/// The arguments and the environment of an app, mapped at the end of the address space.
+///
+/// `argv` is followed by `envp` like the initial stack of a System V process, and both are
+/// NULL-terminated arrays of pointers to the strings following them.
+struct ArgsBlock {
+    base: u64,
+    image: Vec<u8>,
+    argc: usize,
 }
 
-fn make_argv(
-    c_chars_slice: &[*const c_char],
-    argv: *mut *mut c_char,
-    argv_len: usize,
-    argbuf: *const c_char,
-    argbuf_len: usize,
-) -> Result<usize, Error> {
-    let mut argc = 0;
-    let mut argbuf_index = 0;
-
-    let mut push_to_argv = |s: *const c_char| {
-        if argc >= argv_len || argbuf_index >= argbuf_len {
-            Err(make_error!(Code::Full))
-        } else {
-            let dst = unsafe { argbuf.add(argbuf_index) } as *mut c_char;
-            unsafe { *argv.add(argc) = dst }
-            argc += 1;
-            unsafe { strcpy(dst, s) };
-            argbuf_index += unsafe { strlen(s) } + 1;
-            Ok(())
+impl ArgsBlock {
+    fn new(args: &[&str], envs: &[&str]) -> Self {
+        let pointers_len = (args.len() + envs.len() + 2) * mem::size_of::<u64>();
+        let strings_len = args.iter().chain(envs).map(|s| s.len() + 1).sum::<usize>();
+        let num_pages = (pointers_len + strings_len + 4095) / 4096;
+        let base = 0_u64.wrapping_sub(num_pages as u64 * 4096);
+
+        let mut pointers = Vec::with_capacity(pointers_len);
+        let mut strings = Vec::with_capacity(strings_len);
+        for list in [args, envs] {
+            for s in list {
+                let addr = base + (pointers_len + strings.len()) as u64;
+                pointers.extend_from_slice(&addr.to_ne_bytes());
+                strings.extend_from_slice(s.as_bytes());
+                strings.push(0);
+            }
+            pointers.extend_from_slice(&0_u64.to_ne_bytes());
         }
-    };
+        pointers.append(&mut strings);
 
-    for &c_chars in c_chars_slice {
-        push_to_argv(c_chars)?;
+        Self {
+            base,
+            image: pointers,
+            argc: args.len(),
+        }
     }
 
-    Ok(argc)
-}
+    fn num_pages(&self) -> usize {
+        (self.image.len() + 4095) / 4096
+    }
 
-fn find_redirect_dest(argv: &[&str]) -> Option<usize> {
-    match argv.iter().position(|&x| x == ">") {
-        None => None,
-        Some(i) => argv.get(i + 1).map(|_| i + 1),
+    fn argv(&self) -> *const *const c_char {
+        self.base as *const *const c_char
     }
-}
 
-fn extract_redirect<'a>(
-    argv: &'a [&str],
-    redirect_dest_index: usize,
-) -> Result<&'a DirectoryEntry, String> {
-    let redirect_dest = argv[redirect_dest_index];
-    let (file, post_slash) =
-        find_file(redirect_dest, boot_volume_image().get_root_cluster() as u64);
-    if let Some(file) = file {
-        if file.is_directory() || post_slash {
-            Err(format!("cannot redirect to a directory: {}", redirect_dest))
-        } else {
-            Ok(file)
-        }
-    } else {
-        create_file(redirect_dest).map_err(|e| format!("failed to create a redirect file: {}", e))
+    fn envp(&self) -> *const *const c_char {
+        (self.base as usize + (self.argc + 1) * mem::size_of::<u64>()) as *const *const c_char
     }
 }
 
-fn find_pipe_dest(argv: &[&str]) -> Option<usize> {
-    match argv.iter().position(|&s| s == "|") {
-        None => None,
-        Some(i) => argv.get(i + 1).map(|_| i + 1),
+/// Opens the file of the redirect, or returns `None` for `>&`, whose target is a descriptor.
+fn open_redirect(redirect: &Redirect<String>, cwd: &str) -> Result<Option<FileDescriptor>, String> {
+    let target = &redirect.target;
+    let is_std_fd = |fd: usize| fd <= STD_ERR;
+    if !is_std_fd(redirect.fd) {
+        return Err(format!("bad file descriptor: {}", redirect.fd));
     }
-}
 
-fn join_to_string(separator: char, strs: &[&str]) -> String {
-    strs.iter().fold("".to_string(), |mut acc, &s| {
-        acc.push_str(s);
-        acc.push(separator);
-        acc
+    let path = resolve_path(cwd, target);
+    let file = match redirect.op {
+        RedirectOp::Duplicate => {
+            return match target.parse() {
+                Ok(fd) if is_std_fd(fd) => Ok(None),
+                _ => Err(format!("bad file descriptor: {}", target)),
+            }
+        }
+        RedirectOp::Read => vfs::open(&path, false, false).and_then(|file| {
+            if file.stat().mode == S_IFDIR {
+                return Err(make_error!(Code::IsDirectory));
+            }
+            Ok(file)
+        }),
+        RedirectOp::Write => vfs::open(&path, true, true),
+        RedirectOp::Append => vfs::open(&path, true, false).map(|mut file| {
+            // devices such as /dev/null cannot seek
+            let _ = file.seek(SeekFrom::End(0));
+            file
+        }),
+    };
+    file.map(Some).map_err(|e| match e.code {
+        Code::IsDirectory => format!("cannot redirect to a directory: {}", target),
+        Code::Busy => format!("file is in use: {}", target),
+        Code::NoSuchEntry if redirect.op == RedirectOp::Read => {
+            format!("no such file: {}", target)
+        }
+        _ => format!("failed to open a redirect file: {}", e),
     })
 }
 
-fn new_c_chars_vec(strs: &[&str]) -> Vec<*const c_char> {
-    strs.iter()
-        .map(|&s| CString::_new(s.as_bytes().to_vec()).unwrap())
-        .map(|c| c.into_raw() as *const c_char)
-        .collect::<Vec<_>>()
-}
-
 pub fn setup_pml4(current_task: &mut Task) -> Result<*mut PageMapEntry, Error> {
     let pml4 = PageMapEntry::new_page_map()?;
 
@@ -831,57 +1766,70 @@ pub fn free_pml4(current_task: &mut Task) -> Result<(), Error> {
     free_page_map(cr3 as *mut u64 as *mut PageMapEntry)
 }
 
-fn find_command(command: &str, dir_cluster: u64) -> Option<&DirectoryEntry> {
-    if let (Some(file_entry), post_slash) = find_file(command, dir_cluster) {
-        if file_entry.is_directory() && post_slash {
-            return None;
+/// Reads a line from `fd` including the newline. Returns None at the end of the input.
+fn read_line(fd: &Mutex<FileDescriptor>) -> Option<Vec<u8>> {
+    let mut line = Vec::new();
+    let mut b = [0];
+    while fd.lock().read(&mut b) > 0 {
+        line.push(b[0]);
+        if b[0] == b'\n' {
+            break;
         }
-        return Some(file_entry);
     }
+    (!line.is_empty()).then_some(line)
+}
 
+/// Looks up `command` relative to `cwd`, and then in /apps if it has no slash.
+/// Returns the absolute path and the entry of the file.
+fn find_command(command: &str, cwd: &str) -> Option<(String, &'static DirectoryEntry)> {
     let root_cluster = boot_volume_image().get_root_cluster() as u64;
-    if dir_cluster != root_cluster || command.contains('/') {
-        return None;
-    }
-
-    let apps_entry = match find_file("apps", root_cluster).0 {
-        None => return None,
-        Some(apps_entry) => {
-            if !apps_entry.is_directory() {
-                return None;
+    let find_in = |dir: &str| {
+        let path = resolve_path(dir, command);
+        match find_file(&path, root_cluster) {
+            (Some(file_entry), post_slash) if !(file_entry.is_directory() && post_slash) => {
+                Some((path, file_entry))
             }
-            apps_entry
+            _ => None,
         }
     };
 
-    find_command(command, apps_entry.first_cluster() as u64)
+    find_in(cwd).or_else(|| {
+        if command.contains('/') {
+            None
+        } else {
+            find_in("/apps")
+        }
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use alloc::vec;
 
     #[test]
-    fn parse_command_empty() {
-        assert_eq!(parse_command(""), None);
-    }
-
-    #[test]
-    fn parse_command_no_args() {
-        assert_eq!(parse_command("echo"), Some(vec!["echo"]));
-    }
-
-    #[test]
-    fn parse_command_one_arg() {
-        assert_eq!(parse_command("echo a\\aa"), Some(vec!["echo", "a\\aa"]));
-    }
-
-    #[test]
-    fn parse_command_args() {
-        assert_eq!(
-            parse_command("ls -l | sort"),
-            Some(vec!["ls", "-l", "|", "sort"])
-        );
+    fn args_block_layout() {
+        let block = ArgsBlock::new(&["ls", "-l"], &["A=1"]);
+        assert_eq!(block.base, 0xffff_ffff_ffff_f000);
+        assert_eq!(block.num_pages(), 1);
+        assert_eq!(block.envp() as u64, block.base + 24);
+
+        let pointer = |i: usize| {
+            let addr = u64::from_ne_bytes(block.image[i * 8..i * 8 + 8].try_into().unwrap());
+            (addr != 0).then(|| (addr - block.base) as usize)
+        };
+        let string = |offset: usize| {
+            let len = block.image[offset..].iter().position(|&b| b == 0).unwrap();
+            &block.image[offset..offset + len]
+        };
+        assert_eq!(string(pointer(0).unwrap()), b"ls");
+        assert_eq!(string(pointer(1).unwrap()), b"-l");
+        assert_eq!(pointer(2), None);
+        assert_eq!(string(pointer(3).unwrap()), b"A=1");
+        assert_eq!(pointer(4), None);
+
+        let long = "x".repeat(5000);
+        let block = ArgsBlock::new(&[&long], &[]);
+        assert_eq!(block.num_pages(), 2);
+        assert_eq!(block.base, 0xffff_ffff_ffff_e000);
     }
 }
@@ -0,0 +1,203 @@
+//! The line typed at the prompt, which is edited at a cursor like Emacs.
+
+use alloc::string::{String, ToString};
+use core::mem;
+
+/// A line of ASCII characters with a cursor between them.
+#[derive(Debug)]
+pub(super) struct LineBuffer {
+    line: String,
+    /// the index of the character just after the cursor
+    cursor: usize,
+    max_len: usize,
+}
+
+impl LineBuffer {
+    pub(super) fn new(max_len: usize) -> Self {
+        Self {
+            line: String::with_capacity(max_len),
+            cursor: 0,
+            max_len,
+        }
+    }
+
+    pub(super) fn as_str(&self) -> &str {
+        &self.line
+    }
+
+    pub(super) fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub(super) fn before_cursor(&self) -> &str {
+        &self.line[..self.cursor]
+    }
+
+    /// Replaces the line and puts the cursor at its end. The line is not limited to `max_len`,
+    /// which only limits typing.
+    pub(super) fn set(&mut self, line: &str) {
+        self.line = line.to_string();
+        self.cursor = self.line.len();
+    }
+
+    pub(super) fn take(&mut self) -> String {
+        self.cursor = 0;
+        mem::take(&mut self.line)
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.take();
+    }
+
+    /// Inserts `c` at the cursor unless it is not ASCII or the line is full.
+    pub(super) fn insert(&mut self, c: char) -> bool {
+        if !c.is_ascii() || self.line.len() >= self.max_len {
+            return false;
+        }
+        self.line.insert(self.cursor, c);
+        self.cursor += 1;
+        true
+    }
+
+    /// Replaces `len` characters before the cursor with `s` unless the line gets too long.
+    pub(super) fn replace_before_cursor(&mut self, len: usize, s: &str) -> bool {
+        let start = self.cursor - len;
+        if !s.is_ascii() || self.line.len() - len + s.len() > self.max_len {
+            return false;
+        }
+        self.line.replace_range(start..self.cursor, s);
+        self.cursor = start + s.len();
+        true
+    }
+
+    pub(super) fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.line.remove(self.cursor);
+        }
+    }
+
+    pub(super) fn delete(&mut self) {
+        if self.cursor < self.line.len() {
+            self.line.remove(self.cursor);
+        }
+    }
+
+    pub(super) fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    pub(super) fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.line.len());
+    }
+
+    pub(super) fn move_home(&mut self) {
+        self.cursor = 0;
+    }
+
+    pub(super) fn move_end(&mut self) {
+        self.cursor = self.line.len();
+    }
+
+    /// Moves the cursor to the start of the word before it.
+    pub(super) fn move_word_left(&mut self) {
+        let bytes = self.line.as_bytes();
+        while self.cursor > 0 && !is_word(bytes[self.cursor - 1]) {
+            self.cursor -= 1;
+        }
+        while self.cursor > 0 && is_word(bytes[self.cursor - 1]) {
+            self.cursor -= 1;
+        }
+    }
+
+    /// Moves the cursor to the end of the word after it.
+    pub(super) fn move_word_right(&mut self) {
+        let bytes = self.line.as_bytes();
+        while self.cursor < bytes.len() && !is_word(bytes[self.cursor]) {
+            self.cursor += 1;
+        }
+        while self.cursor < bytes.len() && is_word(bytes[self.cursor]) {
+            self.cursor += 1;
+        }
+    }
+
+    /// Deletes the characters before the cursor.
+    pub(super) fn kill_to_start(&mut self) {
+        self.line.replace_range(..self.cursor, "");
+        self.cursor = 0;
+    }
+
+    /// Deletes the characters after the cursor.
+    pub(super) fn kill_to_end(&mut self) {
+        self.line.truncate(self.cursor);
+    }
+}
+
+fn is_word(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(s: &str, cursor: usize) -> LineBuffer {
+        let mut buf = LineBuffer::new(16);
+        buf.set(s);
+        buf.cursor = cursor;
+        buf
+    }
+
+    #[test]
+    fn insert_and_delete_in_middle() {
+        let mut buf = line("ac", 1);
+        assert!(buf.insert('b'));
+        assert_eq!((buf.as_str(), buf.cursor()), ("abc", 2));
+        buf.backspace();
+        assert_eq!((buf.as_str(), buf.cursor()), ("ac", 1));
+        buf.delete();
+        assert_eq!((buf.as_str(), buf.cursor()), ("a", 1));
+        buf.delete();
+        buf.move_home();
+        buf.backspace();
+        assert_eq!((buf.as_str(), buf.cursor()), ("a", 0));
+        assert!(!buf.insert('あ'));
+
+        let mut buf = line("0123456789abcdef", 3);
+        assert!(!buf.insert('x'));
+        assert_eq!(buf.as_str(), "0123456789abcdef");
+    }
+
+    #[test]
+    fn move_cursor() {
+        let mut buf = line("ls -l /apps", 0);
+        buf.move_left();
+        assert_eq!(buf.cursor(), 0);
+        buf.move_end();
+        buf.move_right();
+        assert_eq!(buf.cursor(), 11);
+        buf.move_word_left();
+        assert_eq!(buf.before_cursor(), "ls -l /");
+        buf.move_word_left();
+        assert_eq!(buf.before_cursor(), "ls -");
+        buf.move_word_right();
+        assert_eq!(buf.before_cursor(), "ls -l");
+        buf.move_word_right();
+        assert_eq!(buf.before_cursor(), "ls -l /apps");
+    }
+
+    #[test]
+    fn kill_and_replace() {
+        let mut buf = line("echo hello", 5);
+        buf.kill_to_end();
+        assert_eq!((buf.as_str(), buf.cursor()), ("echo ", 5));
+        assert!(buf.replace_before_cursor(1, "! "));
+        assert_eq!((buf.as_str(), buf.cursor()), ("echo! ", 6));
+        assert!(!buf.replace_before_cursor(0, "0123456789ab"));
+        buf.move_left();
+        buf.kill_to_start();
+        assert_eq!((buf.as_str(), buf.cursor()), (" ", 0));
+        assert_eq!(buf.take(), " ");
+        assert_eq!((buf.as_str(), buf.cursor()), ("", 0));
+    }
+}
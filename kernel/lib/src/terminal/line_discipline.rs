@@ -0,0 +1,262 @@
+//! Turns the keys typed into a terminal into the bytes which apps read from it, like the line
+//! discipline of a POSIX tty.
+
+use crate::keyboard::{
+    is_control_key_inputted, KEY_DELETE, KEY_DOWN, KEY_END, KEY_HOME, KEY_LEFT, KEY_PAGE_DOWN,
+    KEY_PAGE_UP, KEY_RIGHT, KEY_UP,
+};
+use crate::terminal::lib::LINE_MAX;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// Reads return whole lines, which can be edited with Backspace and Ctrl-U before Enter.
+pub(crate) const MODE_CANONICAL: u32 = 1;
+/// Typed characters are shown.
+pub(crate) const MODE_ECHO: u32 = 2;
+/// Reads return 0 instead of waiting if no input is ready.
+pub(crate) const MODE_NONBLOCK: u32 = 4;
+
+/// Passed to and from apps by the `IoControl` syscall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(C)]
+pub(crate) struct TerminalMode {
+    pub(crate) flags: u32,
+}
+
+impl TerminalMode {
+    /// the mode every command starts with
+    pub(crate) const DEFAULT: TerminalMode = TerminalMode {
+        flags: MODE_CANONICAL | MODE_ECHO,
+    };
+
+    fn has(&self, flag: u32) -> bool {
+        self.flags & flag != 0
+    }
+
+    pub(crate) fn is_nonblocking(&self) -> bool {
+        self.has(MODE_NONBLOCK)
+    }
+}
+
+const ERASE: u8 = 0x08;
+const DELETE: u8 = 0x7f;
+/// Ctrl-U
+const KILL: u8 = 0x15;
+/// Ctrl-D
+const EOF: u8 = 0x04;
+
+/// Returns the bytes a key sends, which are control codes for Ctrl and escape sequences for
+/// the cursor keys.
+pub(crate) fn key_bytes(modifier: u8, keycode: u8, ascii: char) -> Vec<u8> {
+    let sequence: &[u8] = match keycode {
+        KEY_UP => b"\x1b[A",
+        KEY_DOWN => b"\x1b[B",
+        KEY_RIGHT => b"\x1b[C",
+        KEY_LEFT => b"\x1b[D",
+        KEY_HOME => b"\x1b[H",
+        KEY_END => b"\x1b[F",
+        KEY_DELETE => b"\x1b[3~",
+        KEY_PAGE_UP => b"\x1b[5~",
+        KEY_PAGE_DOWN => b"\x1b[6~",
+        _ => b"",
+    };
+    if !sequence.is_empty() {
+        return sequence.to_vec();
+    }
+    if !ascii.is_ascii() || ascii == '\0' {
+        return Vec::new();
+    }
+    let byte = ascii as u8;
+    if is_control_key_inputted(modifier) && (b'@'..=b'~').contains(&byte) {
+        return vec![byte & 0x1f];
+    }
+    vec![byte]
+}
+
+#[derive(Debug)]
+pub(crate) struct LineDiscipline {
+    mode: TerminalMode,
+    /// the line being edited in the canonical mode
+    line: Vec<u8>,
+    /// the bytes which can be read
+    ready: VecDeque<u8>,
+    /// whether Ctrl-D has been typed at the start of a line, which makes the next read return 0
+    eof: bool,
+}
+
+impl LineDiscipline {
+    pub(crate) fn new() -> Self {
+        Self {
+            mode: TerminalMode::DEFAULT,
+            line: Vec::new(),
+            ready: VecDeque::new(),
+            eof: false,
+        }
+    }
+
+    pub(crate) fn mode(&self) -> TerminalMode {
+        self.mode
+    }
+
+    /// Changes the mode. The line being edited can be read at once if the canonical mode ends.
+    pub(crate) fn set_mode(&mut self, mode: TerminalMode) {
+        if !mode.has(MODE_CANONICAL) {
+            self.ready.extend(self.line.drain(..));
+        }
+        self.mode = mode;
+    }
+
+    /// Goes back to the default mode and discards the input nobody has read.
+    pub(crate) fn reset(&mut self) {
+        *self = LineDiscipline::new();
+    }
+
+    /// Discards the line being edited, which Ctrl-C cancels.
+    pub(crate) fn discard_line(&mut self) {
+        self.line.clear();
+    }
+
+    /// Takes the bytes of a key and returns what to show for it.
+    pub(crate) fn input(&mut self, key: &[u8]) -> String {
+        let mut echo = String::new();
+        if !self.mode.has(MODE_CANONICAL) {
+            self.ready.extend(key);
+            if key
+                .iter()
+                .all(|&b| b == b'\n' || b == b'\t' || !b.is_ascii_control())
+            {
+                echo.extend(key.iter().map(|&b| b as char));
+            }
+        } else if key.first() != Some(&0x1b) {
+            // the escape sequences of the cursor keys are ignored while editing a line
+            for &b in key {
+                self.edit(b, &mut echo);
+            }
+        }
+        if !self.mode.has(MODE_ECHO) {
+            echo.clear();
+        }
+        echo
+    }
+
+    fn edit(&mut self, b: u8, echo: &mut String) {
+        match b {
+            ERASE | DELETE => {
+                if self.line.pop().is_some() {
+                    echo.push_str("\x08 \x08");
+                }
+            }
+            KILL => {
+                for _ in self.line.drain(..) {
+                    echo.push_str("\x08 \x08");
+                }
+            }
+            EOF => {
+                self.eof = self.line.is_empty();
+                self.ready.extend(self.line.drain(..));
+            }
+            b'\n' => {
+                self.line.push(b'\n');
+                self.ready.extend(self.line.drain(..));
+                echo.push('\n');
+            }
+            b'\t' | b' '..=b'~' => {
+                if self.line.len() < LINE_MAX {
+                    self.line.push(b);
+                    echo.push(b as char);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Moves the bytes which are ready into `buf`, at most a line in the canonical mode.
+    ///
+    /// Returns None if there are none and the reader has to wait for keys.
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> Option<usize> {
+        if self.ready.is_empty() {
+            if self.eof {
+                self.eof = false;
+                return Some(0);
+            }
+            return None;
+        }
+        let mut len = 0;
+        while len < buf.len() {
+            let b = match self.ready.pop_front() {
+                Some(b) => b,
+                None => break,
+            };
+            buf[len] = b;
+            len += 1;
+            if b == b'\n' && self.mode.has(MODE_CANONICAL) {
+                break;
+            }
+        }
+        Some(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keyboard::L_CONTROL_BIT_MASK;
+
+    fn type_keys(discipline: &mut LineDiscipline, keys: &[u8]) -> String {
+        keys.iter().map(|&b| discipline.input(&[b])).collect()
+    }
+
+    #[test]
+    fn canonical_mode_edits_a_line() {
+        let mut discipline = LineDiscipline::new();
+        let mut buf = [0; 16];
+        assert_eq!(type_keys(&mut discipline, b"ab\x08c"), "ab\x08 \x08c");
+        assert_eq!(discipline.read(&mut buf), None);
+        assert_eq!(
+            type_keys(&mut discipline, b"\nxy\x15z\n"),
+            "\nxy\x08 \x08\x08 \x08z\n"
+        );
+        assert_eq!(discipline.read(&mut buf), Some(3));
+        assert_eq!(&buf[..3], b"ac\n");
+        assert_eq!(discipline.read(&mut buf), Some(2));
+        assert_eq!(&buf[..2], b"z\n");
+        assert_eq!(discipline.read(&mut buf), None);
+
+        // Ctrl-D ends the input at the start of a line, and passes the line otherwise
+        type_keys(&mut discipline, b"q\x04\x04");
+        assert_eq!(discipline.read(&mut buf), Some(1));
+        assert_eq!(discipline.read(&mut buf), Some(0));
+        assert_eq!(discipline.read(&mut buf), None);
+    }
+
+    #[test]
+    fn raw_mode_passes_keys() {
+        let mut discipline = LineDiscipline::new();
+        let mut buf = [0; 16];
+        type_keys(&mut discipline, b"ab");
+        discipline.set_mode(TerminalMode { flags: 0 });
+        assert_eq!(discipline.input(b"\x1b[A"), "");
+        assert_eq!(discipline.input(&[0x08]), "");
+        assert_eq!(discipline.read(&mut buf), Some(6));
+        assert_eq!(&buf[..6], b"ab\x1b[A\x08");
+
+        discipline.set_mode(TerminalMode {
+            flags: MODE_ECHO | MODE_NONBLOCK,
+        });
+        assert!(discipline.mode().is_nonblocking());
+        assert_eq!(discipline.input(b"x"), "x");
+        discipline.reset();
+        assert_eq!(discipline.mode(), TerminalMode::DEFAULT);
+        assert_eq!(discipline.read(&mut buf), None);
+    }
+
+    #[test]
+    fn bytes_of_keys() {
+        assert_eq!(key_bytes(0, 4, 'a'), b"a");
+        assert_eq!(key_bytes(L_CONTROL_BIT_MASK, 7, 'd'), [EOF]);
+        assert_eq!(key_bytes(0, KEY_LEFT, '\0'), b"\x1b[D");
+        assert_eq!(key_bytes(0, 57, '\0'), b"");
+    }
+}
@@ -0,0 +1,886 @@
+//! Parses command lines and scripts like a POSIX shell.
+//!
+//! Words can be quoted by `'...'`, in which every character is literal, or by `"..."`,
+//! in which `\` escapes only `"`, `\` and `$`, and variables are expanded.
+//! Outside of quotes, `\` escapes any character, and `#` at the start of a word begins
+//! a comment. `$NAME`, `${NAME}`, `$0`-`$9`, `$#` and `$?` are expanded when the command
+//! is executed unless they are in single quotes, and the result is never split into words.
+//!
+//! A simple command can redirect its standard descriptors by `< file`, `> file`, `>> file`
+//! and `>&N`, each of which can be preceded by the number of the descriptor such as `2>`.
+//! Commands are separated by `;` or newlines and joined by `&&`, `||` and `|`, and `&`
+//! after them runs them in the background instead of `;`. The compound commands `if ... then ... elif ... else ... fi` and `for NAME in ...; do ... done`
+//! can contain them.
+
+use crate::io::{STD_IN, STD_OUT};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::{IntoIter, Vec};
+use core::fmt;
+use core::iter::Peekable;
+use core::str::Chars;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Part {
+    Text(String),
+    Variable(String),
+}
+
+/// A word before expansion.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub(super) struct Word {
+    parts: Vec<Part>,
+    /// whether the word has quotes or escapes, which make it a word even if it is empty
+    /// and prevent it from being a keyword
+    quoted: bool,
+}
+
+impl Word {
+    fn push(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(Part::Text(text)) => text.push(c),
+            _ => self.parts.push(Part::Text(c.into())),
+        }
+    }
+
+    /// Returns the expanded word, or `None` if the word is unquoted and expanded to nothing.
+    pub(super) fn expand(&self, lookup: &impl Fn(&str) -> Option<String>) -> Option<String> {
+        let mut s = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => s.push_str(text),
+                Part::Variable(name) => s.push_str(&lookup(name).unwrap_or_default()),
+            }
+        }
+        (self.quoted || !s.is_empty()).then(|| s)
+    }
+
+    /// Returns the word if it is a plain word without quotes or variables.
+    fn plain(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [Part::Text(text)] if !self.quoted => Some(text),
+            _ => None,
+        }
+    }
+
+    fn is_keyword(&self, keywords: &[&str]) -> bool {
+        self.plain().map_or(false, |w| keywords.contains(&w))
+    }
+}
+
+/// Shows the word as source which `parse` gives back as the same word.
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.parts.is_empty() {
+            return write!(f, "''");
+        }
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => write!(f, "{}", quote(text))?,
+                Part::Variable(name) if self.quoted => write!(f, "\"${{{}}}\"", name)?,
+                Part::Variable(name) => write!(f, "${{{}}}", name)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+    Word(Word),
+    /// `|`
+    Pipe,
+    /// `<`, `>`, `>>` or `>&` with the number of the descriptor
+    Redirect(usize, RedirectOp),
+    /// `;` or a newline
+    Separator,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `&`
+    Background,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(word) => {
+                for part in &word.parts {
+                    match part {
+                        Part::Text(text) => write!(f, "{}", text)?,
+                        Part::Variable(name) => write!(f, "${{{}}}", name)?,
+                    }
+                }
+                Ok(())
+            }
+            Token::Pipe => write!(f, "|"),
+            Token::Redirect(fd, op) => write!(f, "{}", op.with_fd(*fd)),
+            Token::Separator => write!(f, ";"),
+            Token::And => write!(f, "&&"),
+            Token::Or => write!(f, "||"),
+            Token::Background => write!(f, "&"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ParseError {
+    UnterminatedQuote(char),
+    BadSubstitution,
+    UnexpectedToken(String),
+    UnexpectedEnd,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedQuote(q) => write!(f, "syntax error: unterminated {}", q),
+            ParseError::BadSubstitution => write!(f, "syntax error: bad substitution"),
+            ParseError::UnexpectedToken(t) => write!(f, "syntax error near {}", t),
+            ParseError::UnexpectedEnd => write!(f, "syntax error: unexpected end of input"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum RedirectOp {
+    /// `<`
+    Read,
+    /// `>`, which truncates the file
+    Write,
+    /// `>>`
+    Append,
+    /// `>&`, which makes the descriptor a copy of the one given as the target
+    Duplicate,
+}
+
+impl RedirectOp {
+    /// Returns the operator preceded by `fd` unless it is the default one.
+    fn with_fd(self, fd: usize) -> String {
+        let (default_fd, op) = match self {
+            RedirectOp::Read => (STD_IN, "<"),
+            RedirectOp::Write => (STD_OUT, ">"),
+            RedirectOp::Append => (STD_OUT, ">>"),
+            RedirectOp::Duplicate => (STD_OUT, ">&"),
+        };
+        if fd == default_fd {
+            op.into()
+        } else {
+            format!("{}{}", fd, op)
+        }
+    }
+}
+
+/// A redirection such as `2>> log`, whose target is a `Word` before expansion.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct Redirect<T> {
+    pub(super) fd: usize,
+    pub(super) op: RedirectOp,
+    pub(super) target: T,
+}
+
+/// A command with its arguments, such as `sort < data > out`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct SimpleCommand {
+    pub(super) words: Vec<Word>,
+    /// applied in order after the pipe
+    pub(super) redirects: Vec<Redirect<Word>>,
+}
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = self.words.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        for redirect in &self.redirects {
+            words.push(format!(
+                "{} {}",
+                redirect.op.with_fd(redirect.fd),
+                redirect.target
+            ));
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl SimpleCommand {
+    pub(super) fn expand(&self, lookup: &impl Fn(&str) -> Option<String>) -> ExpandedCommand {
+        ExpandedCommand {
+            argv: self.words.iter().filter_map(|w| w.expand(lookup)).collect(),
+            redirects: self
+                .redirects
+                .iter()
+                .map(|r| Redirect {
+                    fd: r.fd,
+                    op: r.op,
+                    target: r.target.expand(lookup).unwrap_or_default(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// A `SimpleCommand` whose words are expanded.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct ExpandedCommand {
+    pub(super) argv: Vec<String>,
+    pub(super) redirects: Vec<Redirect<String>>,
+}
+
+impl ExpandedCommand {
+    /// Returns the command as a line which `parse` gives back without expanding anything.
+    pub(super) fn to_command_line(&self) -> String {
+        let mut words = self.argv.iter().map(|arg| quote(arg)).collect::<Vec<_>>();
+        for redirect in &self.redirects {
+            words.push(redirect.op.with_fd(redirect.fd));
+            words.push(quote(&redirect.target));
+        }
+        words.join(" ")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) enum Command {
+    /// Simple commands connected by `|`
+    Pipeline(Vec<SimpleCommand>),
+    If {
+        /// pairs of a condition and the commands run if it succeeds
+        branches: Vec<(List, List)>,
+        otherwise: Option<List>,
+    },
+    For {
+        name: String,
+        words: Vec<Word>,
+        body: List,
+    },
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Pipeline(commands) => {
+                let commands = commands.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", commands.join(" | "))
+            }
+            Command::If {
+                branches,
+                otherwise,
+            } => {
+                for (i, (condition, body)) in branches.iter().enumerate() {
+                    write!(f, "{} ", if i == 0 { "if" } else { "elif" })?;
+                    write_list(f, condition)?;
+                    write!(f, "; then ")?;
+                    write_list(f, body)?;
+                    write!(f, "; ")?;
+                }
+                if let Some(otherwise) = otherwise {
+                    write!(f, "else ")?;
+                    write_list(f, otherwise)?;
+                    write!(f, "; ")?;
+                }
+                write!(f, "fi")
+            }
+            Command::For { name, words, body } => {
+                write!(f, "for {} in", name)?;
+                for word in words {
+                    write!(f, " {}", word)?;
+                }
+                write!(f, "; do ")?;
+                write_list(f, body)?;
+                write!(f, "; done")
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub(super) enum Connector {
+    And,
+    Or,
+}
+
+/// Commands connected by `&&` and `||`, which are evaluated from left to right.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct AndOr {
+    pub(super) first: Command,
+    pub(super) rest: Vec<(Connector, Command)>,
+    /// whether the commands are followed by `&`
+    pub(super) background: bool,
+}
+
+/// Shows the commands as source without the trailing `&`.
+impl fmt::Display for AndOr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, command) in &self.rest {
+            match connector {
+                Connector::And => write!(f, " && {}", command)?,
+                Connector::Or => write!(f, " || {}", command)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Commands run in order.
+pub(super) type List = Vec<AndOr>;
+
+fn write_list(f: &mut fmt::Formatter<'_>, list: &List) -> fmt::Result {
+    for (i, and_or) in list.iter().enumerate() {
+        if i > 0 {
+            write!(f, "{}", if list[i - 1].background { " " } else { "; " })?;
+        }
+        write!(f, "{}", and_or)?;
+        if and_or.background {
+            write!(f, " &")?;
+        }
+    }
+    Ok(())
+}
+
+/// Parses a command line or a whole script.
+pub(super) fn parse(s: &str) -> Result<List, ParseError> {
+    let mut parser = Parser {
+        tokens: tokenize(s)?.into_iter().peekable(),
+    };
+    let list = parser.list(&[])?;
+    match parser.tokens.next() {
+        None => Ok(list),
+        Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+    }
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    /// Parses commands until the end or one of `terminators` at the position of a command.
+    fn list(&mut self, terminators: &[&str]) -> Result<List, ParseError> {
+        let mut list = Vec::new();
+        loop {
+            while self.tokens.next_if_eq(&Token::Separator).is_some() {}
+            match self.tokens.peek() {
+                None => break,
+                Some(Token::Word(w)) if w.is_keyword(terminators) => break,
+                _ => {}
+            }
+            let mut and_or = self.and_or()?;
+            and_or.background = self.tokens.next_if_eq(&Token::Background).is_some();
+            let background = and_or.background;
+            list.push(and_or);
+            if background {
+                continue;
+            }
+            match self.tokens.peek() {
+                None | Some(Token::Separator) => {}
+                Some(token) => return Err(ParseError::UnexpectedToken(token.to_string())),
+            }
+        }
+        Ok(list)
+    }
+
+    fn and_or(&mut self) -> Result<AndOr, ParseError> {
+        let first = self.command()?;
+        let mut rest = Vec::new();
+        loop {
+            let connector = match self.tokens.peek() {
+                Some(Token::And) => Connector::And,
+                Some(Token::Or) => Connector::Or,
+                _ => break,
+            };
+            self.tokens.next();
+            // a command can follow on the next line
+            while self.tokens.next_if_eq(&Token::Separator).is_some() {}
+            rest.push((connector, self.command()?));
+        }
+        Ok(AndOr {
+            first,
+            rest,
+            background: false,
+        })
+    }
+
+    fn command(&mut self) -> Result<Command, ParseError> {
+        match self.tokens.peek() {
+            Some(Token::Word(w)) if w.is_keyword(&["if"]) => self.if_command(),
+            Some(Token::Word(w)) if w.is_keyword(&["for"]) => self.for_command(),
+            Some(Token::Word(w)) if w.is_keyword(KEYWORDS) => {
+                Err(ParseError::UnexpectedToken(w.plain().unwrap().into()))
+            }
+            _ => self.pipeline(),
+        }
+    }
+
+    fn pipeline(&mut self) -> Result<Command, ParseError> {
+        let mut commands = vec![self.simple_command()?];
+        while self.tokens.next_if_eq(&Token::Pipe).is_some() {
+            commands.push(self.simple_command()?);
+        }
+        Ok(Command::Pipeline(commands))
+    }
+
+    fn simple_command(&mut self) -> Result<SimpleCommand, ParseError> {
+        let mut words = Vec::new();
+        let mut redirects = Vec::new();
+        loop {
+            match self.tokens.peek() {
+                Some(Token::Word(_)) => words.push(self.word()?),
+                Some(&Token::Redirect(fd, op)) => {
+                    self.tokens.next();
+                    let target = self.word()?;
+                    redirects.push(Redirect { fd, op, target });
+                }
+                _ => break,
+            }
+        }
+        if words.is_empty() && redirects.is_empty() {
+            return Err(self.unexpected());
+        }
+        Ok(SimpleCommand { words, redirects })
+    }
+
+    fn if_command(&mut self) -> Result<Command, ParseError> {
+        let mut branches = Vec::new();
+        let mut otherwise = None;
+        self.tokens.next();
+        loop {
+            let condition = self.list(&["then"])?;
+            self.keyword("then")?;
+            let body = self.list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            match self.word()?.plain() {
+                Some("elif") => continue,
+                Some("else") => {
+                    otherwise = Some(self.list(&["fi"])?);
+                    self.keyword("fi")?;
+                }
+                _ => {}
+            }
+            break;
+        }
+        Ok(Command::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn for_command(&mut self) -> Result<Command, ParseError> {
+        self.tokens.next();
+        let word = self.word()?;
+        let name = match word.plain() {
+            Some(name) if is_name(name) => name.into(),
+            _ => return Err(ParseError::UnexpectedToken(Token::Word(word).to_string())),
+        };
+        self.keyword("in")?;
+        let mut words = Vec::new();
+        while let Some(Token::Word(_)) = self.tokens.peek() {
+            words.push(self.word()?);
+        }
+        while self.tokens.next_if_eq(&Token::Separator).is_some() {}
+        self.keyword("do")?;
+        let body = self.list(&["done"])?;
+        self.keyword("done")?;
+        Ok(Command::For { name, words, body })
+    }
+
+    fn word(&mut self) -> Result<Word, ParseError> {
+        match self.tokens.next() {
+            Some(Token::Word(w)) => Ok(w),
+            Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn keyword(&mut self, keyword: &str) -> Result<(), ParseError> {
+        match self.tokens.next() {
+            Some(Token::Word(w)) if w.is_keyword(&[keyword]) => Ok(()),
+            Some(token) => Err(ParseError::UnexpectedToken(token.to_string())),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+
+    fn unexpected(&mut self) -> ParseError {
+        match self.tokens.peek() {
+            Some(token) => ParseError::UnexpectedToken(token.to_string()),
+            None => ParseError::UnexpectedEnd,
+        }
+    }
+}
+
+const KEYWORDS: &[&str] = &["if", "then", "elif", "else", "fi", "for", "do", "done"];
+
+/// Splits `s` into words and operators.
+fn tokenize(s: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = s.chars().peekable();
+    let mut word: Option<Word> = None;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '<' | '>' => {
+                // a single digit just before the operator is the number of the descriptor
+                let fd = match word.as_ref().and_then(Word::plain) {
+                    Some(w) if w.len() == 1 => w.chars().next().unwrap().to_digit(10),
+                    _ => None,
+                };
+                if fd.is_some() {
+                    word = None;
+                } else if let Some(w) = word.take() {
+                    tokens.push(Token::Word(w));
+                }
+                let op = match c {
+                    '<' => RedirectOp::Read,
+                    _ if chars.next_if_eq(&'>').is_some() => RedirectOp::Append,
+                    _ if chars.next_if_eq(&'&').is_some() => RedirectOp::Duplicate,
+                    _ => RedirectOp::Write,
+                };
+                let default_fd = if c == '<' { STD_IN } else { STD_OUT };
+                tokens.push(Token::Redirect(fd.map_or(default_fd, |fd| fd as usize), op));
+            }
+            '\n' | ';' | '|' | '&' => {
+                if let Some(w) = word.take() {
+                    tokens.push(Token::Word(w));
+                }
+                tokens.push(match c {
+                    '|' if chars.next_if_eq(&'|').is_some() => Token::Or,
+                    '|' => Token::Pipe,
+                    '&' if chars.next_if_eq(&'&').is_some() => Token::And,
+                    '&' => Token::Background,
+                    _ => Token::Separator,
+                });
+            }
+            c if c.is_whitespace() => {
+                if let Some(w) = word.take() {
+                    tokens.push(Token::Word(w));
+                }
+            }
+            '#' if word.is_none() => while chars.next_if(|&c| c != '\n').is_some() {},
+            '\'' => {
+                let w = word.get_or_insert_with(Word::default);
+                w.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => w.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('\'')),
+                    }
+                }
+            }
+            '"' => {
+                let w = word.get_or_insert_with(Word::default);
+                w.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next_if(|&c| matches!(c, '"' | '\\' | '$')) {
+                            Some(c) => w.push(c),
+                            None => w.push('\\'),
+                        },
+                        Some('$') => variable(&mut chars, w)?,
+                        Some(c) => w.push(c),
+                        None => return Err(ParseError::UnterminatedQuote('"')),
+                    }
+                }
+            }
+            '\\' => {
+                let w = word.get_or_insert_with(Word::default);
+                w.quoted = true;
+                // a backslash at the end of the line is kept as is
+                w.push(chars.next().unwrap_or('\\'));
+            }
+            '$' => variable(&mut chars, word.get_or_insert_with(Word::default))?,
+            c => word.get_or_insert_with(Word::default).push(c),
+        }
+    }
+    if let Some(w) = word {
+        tokens.push(Token::Word(w));
+    }
+
+    Ok(tokens)
+}
+
+/// Reads a variable whose `$` is already consumed into `word`.
+/// A `$` not followed by a name is left as is.
+fn variable(chars: &mut Peekable<Chars>, word: &mut Word) -> Result<(), ParseError> {
+    let name = match chars.peek() {
+        Some(&c @ ('?' | '#' | '0'..='9')) => {
+            chars.next();
+            c.into()
+        }
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(ParseError::UnterminatedQuote('{')),
+                }
+            }
+            let is_special = name.len() == 1 && "?#0123456789".contains(&name);
+            if !is_special && !is_name(&name) {
+                return Err(ParseError::BadSubstitution);
+            }
+            name
+        }
+        Some(&c) if c == '_' || c.is_ascii_alphabetic() => {
+            let mut name = String::new();
+            while let Some(c) = chars.next_if(|&c| c == '_' || c.is_ascii_alphanumeric()) {
+                name.push(c);
+            }
+            name
+        }
+        _ => {
+            word.push('$');
+            return Ok(());
+        }
+    };
+    word.parts.push(Part::Variable(name));
+    Ok(())
+}
+
+/// Whether `s` can be the name of a variable.
+pub(super) fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Quotes `s` so that `parse` gives it back as a single word.
+pub(super) fn quote(s: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+    if !s.is_empty() && s.chars().all(is_plain) && !KEYWORDS.contains(&s) {
+        return s.into();
+    }
+    let mut quoted = String::from("'");
+    for c in s.chars() {
+        if c == '\'' {
+            quoted.push_str("'\\''");
+        } else {
+            quoted.push(c);
+        }
+    }
+    quoted.push('\'');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "?" => Some("1".to_string()),
+            "1" => Some("first".to_string()),
+            "HOME" => Some("/home".to_string()),
+            "SP" => Some("a b".to_string()),
+            _ => None,
+        }
+    }
+
+    /// Expands the words of the tokens, and shows the operators as they are.
+    fn words(s: &str) -> Vec<String> {
+        tokenize(s)
+            .unwrap()
+            .into_iter()
+            .filter_map(|token| match token {
+                Token::Word(w) => w.expand(&lookup),
+                token => Some(token.to_string()),
+            })
+            .collect()
+    }
+
+    fn simple(s: &str) -> SimpleCommand {
+        match &parse(s).unwrap()[0].first {
+            Command::Pipeline(commands) => commands[0].clone(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn tokenize_words_and_operators() {
+        assert!(words("").is_empty());
+        assert!(words("  # comment").is_empty());
+        assert_eq!(
+            words("ls -l | sort>out;a&&b||c\nd # e"),
+            ["ls", "-l", "|", "sort", ">", "out", ";", "a", "&&", "b", "||", "c", ";", "d"]
+        );
+        assert_eq!(words("a#b a&b"), ["a#b", "a", "&", "b"]);
+    }
+
+    #[test]
+    fn tokenize_quotes_and_escapes() {
+        assert_eq!(
+            words(r#"echo "hello world" 'a|b' \> a\\aa"#),
+            ["echo", "hello world", "a|b", ">", "a\\aa"]
+        );
+        assert_eq!(
+            words(r#"a"b"'c' "" '"' "\"\a" '#'"#),
+            ["abc", "", "\"", "\"\\a", "#"]
+        );
+        assert_eq!(
+            tokenize("echo 'a"),
+            Err(ParseError::UnterminatedQuote('\''))
+        );
+        assert_eq!(
+            tokenize("echo \"a"),
+            Err(ParseError::UnterminatedQuote('"'))
+        );
+    }
+
+    #[test]
+    fn expand_variables() {
+        assert_eq!(
+            words("echo $? ${HOME}/x $HOME.txt $SP $1 ${1}0"),
+            [
+                "echo",
+                "1",
+                "/home/x",
+                "/home.txt",
+                "a b",
+                "first",
+                "first0"
+            ]
+        );
+        assert_eq!(
+            words("echo \"$HOME $NONE\" '$HOME' \\$HOME $NONE \"$NONE\" $ a$"),
+            ["echo", "/home ", "$HOME", "$HOME", "", "$", "a$"]
+        );
+        assert_eq!(tokenize("echo ${1a}"), Err(ParseError::BadSubstitution));
+        assert_eq!(
+            tokenize("echo ${HOME"),
+            Err(ParseError::UnterminatedQuote('{'))
+        );
+    }
+
+    #[test]
+    fn parse_pipelines_and_connectors() {
+        let list = parse("a | b > out && c; d || e\n\nf").unwrap();
+        assert_eq!(list.len(), 3);
+        match &list[0].first {
+            Command::Pipeline(commands) => {
+                assert_eq!(commands.len(), 2);
+                assert_eq!(commands[1].expand(&lookup).to_command_line(), "b > out");
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(list[0].rest.len(), 1);
+        assert_eq!(list[0].rest[0].0, Connector::And);
+        assert_eq!(list[1].rest[0].0, Connector::Or);
+        assert!(list[2].rest.is_empty());
+
+        assert!(parse("").unwrap().is_empty());
+        assert_eq!(parse("| a"), Err(ParseError::UnexpectedToken("|".into())));
+        assert_eq!(parse("a &&"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(parse("a >"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(parse("fi"), Err(ParseError::UnexpectedToken("fi".into())));
+    }
+
+    #[test]
+    fn parse_if_and_for() {
+        let list = parse(
+            "if a; then b; elif c\nthen d; else e; fi\nfor x in 1 \"$HOME\"; do echo $x; done",
+        )
+        .unwrap();
+        assert_eq!(list.len(), 2);
+        match &list[0].first {
+            Command::If {
+                branches,
+                otherwise,
+            } => {
+                assert_eq!(branches.len(), 2);
+                assert_eq!(otherwise.as_ref().unwrap().len(), 1);
+            }
+            _ => unreachable!(),
+        }
+        match &list[1].first {
+            Command::For { name, words, body } => {
+                assert_eq!(name, "x");
+                assert_eq!(words.len(), 2);
+                assert_eq!(words[1].expand(&lookup), Some("/home".into()));
+                assert_eq!(body.len(), 1);
+            }
+            _ => unreachable!(),
+        }
+
+        // keywords are only recognized at the position of a command
+        assert_eq!(simple("echo fi").words.len(), 2);
+        assert_eq!(parse("if a; then b"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse("for 1 in a; do b; done"),
+            Err(ParseError::UnexpectedToken("1".into()))
+        );
+        assert!(parse("if a; then b; fi | c").is_err());
+    }
+
+    #[test]
+    fn parse_redirects() {
+        let command = simple("sort<data 2>>log >out 2>&1 a2>b \\2>c '1'>d");
+        assert_eq!(
+            command.expand(&lookup).to_command_line(),
+            "sort a2 2 1 < data 2>> log > out 2>& 1 > b > c > d"
+        );
+        let redirects = command
+            .redirects
+            .iter()
+            .map(|r| (r.fd, r.op))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            redirects[..4],
+            [
+                (STD_IN, RedirectOp::Read),
+                (2, RedirectOp::Append),
+                (STD_OUT, RedirectOp::Write),
+                (2, RedirectOp::Duplicate)
+            ]
+        );
+        assert_eq!(simple("< in").words.len(), 0);
+        assert_eq!(parse("a 2>"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            parse("a > | b"),
+            Err(ParseError::UnexpectedToken("|".into()))
+        );
+    }
+
+    #[test]
+    fn parse_background() {
+        let list = parse("a && b & c; d &").unwrap();
+        assert_eq!(list.len(), 3);
+        assert!(list[0].background);
+        assert_eq!(list[0].rest.len(), 1);
+        assert!(!list[1].background);
+        assert!(list[2].background);
+        assert_eq!(list[0].to_string(), "a && b");
+        assert_eq!(parse("& a"), Err(ParseError::UnexpectedToken("&".into())));
+    }
+
+    #[test]
+    fn display_round_trip() {
+        let source = "if a 'b c' \"$X\" $Y; then d & e; else f > 'o u t'; fi\n\
+                      for x in '' ${1}z; do g | h || i; done";
+        let list = parse(source).unwrap();
+        let shown = list
+            .iter()
+            .map(|and_or| and_or.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            shown,
+            [
+                "if a 'b c' \"${X}\" ${Y}; then d & e; else f > 'o u t'; fi",
+                "for x in '' ${1}z; do g | h || i; done"
+            ]
+        );
+        for (and_or, shown) in list.iter().zip(shown) {
+            assert_eq!(&parse(&shown).unwrap()[0], and_or);
+        }
+    }
+
+    #[test]
+    fn quote_round_trip() {
+        for s in ["plain", "", "a b", "it's", "$HOME", "|", "\\", "#", "fi"] {
+            assert_eq!(simple(&quote(s)).expand(&lookup).argv, [s]);
+        }
+        assert_eq!(quote("a/b.txt"), "a/b.txt");
+    }
+}
@@ -0,0 +1,333 @@
+//! Pseudo-terminals. The slave side works like a terminal for the apps using it, and the
+//! master side lets another app type into it and read what it shows.
+
+use crate::message::{Message, MessageType};
+use crate::sync::Mutex;
+use crate::task::global::task_manager;
+use crate::task::TaskID;
+use crate::terminal::lib::{COLUMNS, ROWS};
+use crate::terminal::line_discipline::{LineDiscipline, TerminalMode};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::mem;
+
+/// Ctrl-C
+const INTERRUPT: u8 = 0x03;
+
+struct Pty {
+    /// the keys written to the master side, which the slave side reads
+    line_discipline: LineDiscipline,
+    /// what the slave side shows, which the master side reads
+    output: VecDeque<u8>,
+    columns: usize,
+    rows: usize,
+    master_closed: bool,
+    slave_closed: bool,
+    /// the task whose group Ctrl-C typed on the master side interrupts
+    foreground: Option<TaskID>,
+    /// the tasks waiting to read either side
+    waiting: Vec<TaskID>,
+}
+
+impl Pty {
+    fn new() -> Self {
+        Self {
+            line_discipline: LineDiscipline::new(),
+            output: VecDeque::new(),
+            columns: COLUMNS,
+            rows: ROWS,
+            master_closed: false,
+            slave_closed: false,
+            foreground: None,
+            waiting: Vec::new(),
+        }
+    }
+
+    /// Moves the output into `buf`. Returns None if the master side has to wait for it.
+    fn read_output(&mut self, buf: &mut [u8], nonblocking: bool) -> Option<usize> {
+        if self.output.is_empty() {
+            return (self.slave_closed || nonblocking).then_some(0);
+        }
+        let len = buf.len().min(self.output.len());
+        for (b, output) in buf.iter_mut().zip(self.output.drain(..len)) {
+            *b = output;
+        }
+        Some(len)
+    }
+
+    /// Reads the keys as the line discipline says. Returns None if the slave side has to wait
+    /// for them.
+    fn read_input(&mut self, buf: &mut [u8]) -> Option<usize> {
+        self.line_discipline.read(buf).or_else(|| {
+            let nonblocking = self.line_discipline.mode().is_nonblocking();
+            (self.master_closed || nonblocking).then_some(0)
+        })
+    }
+
+    /// Types the keys in `bytes` and shows what they echo. Ctrl-C interrupts the foreground
+    /// group if there is one, and is typed as a key otherwise.
+    fn type_keys(&mut self, bytes: &[u8]) {
+        for key in split_keys(bytes) {
+            if key == [INTERRUPT] {
+                if let Some(task_id) = self.foreground {
+                    self.line_discipline.discard_line();
+                    self.output.extend(b"^C\n");
+                    unsafe { asm!("cli") };
+                    let _ = task_manager().interrupt(task_id);
+                    unsafe { asm!("sti") };
+                    continue;
+                }
+            }
+            let echo = self.line_discipline.input(key);
+            self.output.extend(echo.bytes());
+        }
+    }
+
+    /// Wakes up the waiting tasks by `MessageType::PtyReady`.
+    fn notify(&mut self) {
+        for task_id in mem::take(&mut self.waiting) {
+            unsafe { asm!("cli") };
+            let _ = task_manager().send_message(task_id, Message::new(MessageType::PtyReady));
+            unsafe { asm!("sti") };
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Side {
+    Master,
+    Slave,
+}
+
+pub(crate) struct PtyDescriptor {
+    pty: Arc<Mutex<Pty>>,
+    side: Side,
+    /// whether reads of the master side return 0 instead of waiting for the output
+    nonblocking: bool,
+}
+
+/// Returns the master and the slave sides of a new pseudo-terminal.
+pub(crate) fn open_pty(nonblocking: bool) -> (PtyDescriptor, PtyDescriptor) {
+    let pty = Arc::new(Mutex::new(Pty::new()));
+    let master = PtyDescriptor {
+        pty: Arc::clone(&pty),
+        side: Side::Master,
+        nonblocking,
+    };
+    let slave = PtyDescriptor {
+        pty,
+        side: Side::Slave,
+        nonblocking: false,
+    };
+    (master, slave)
+}
+
+impl PtyDescriptor {
+    /// Reads the output of the slave side on the master side, and the keys typed on the master
+    /// side as the line discipline says on the slave side.
+    ///
+    /// Returns 0 when the other side is closed.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        let side = self.side;
+        let nonblocking = self.nonblocking;
+        self.wait(|pty| match side {
+            Side::Master => pty.read_output(buf, nonblocking),
+            Side::Slave => pty.read_input(buf),
+        })
+        .unwrap_or(0)
+    }
+
+    /// Types `buf` on the master side, and shows `buf` on the slave side.
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        let mut pty = self.pty.lock();
+        match self.side {
+            Side::Master => {
+                if pty.slave_closed {
+                    return 0;
+                }
+                pty.type_keys(buf);
+            }
+            // nobody reads the output any longer
+            Side::Slave if pty.master_closed => {}
+            Side::Slave => pty.output.extend(buf),
+        }
+        pty.notify();
+        buf.len()
+    }
+
+    pub fn load(&mut self, _buf: &mut [u8], _offset: usize) -> usize {
+        0
+    }
+
+    pub fn size(&self) -> usize {
+        0
+    }
+
+    /// Returns the mode of the slave side, which either side can change.
+    pub fn mode(&self) -> TerminalMode {
+        self.pty.lock().line_discipline.mode()
+    }
+
+    pub fn set_mode(&mut self, mode: TerminalMode) {
+        let mut pty = self.pty.lock();
+        pty.line_discipline.set_mode(mode);
+        // the line being edited may have become readable
+        pty.notify();
+    }
+
+    pub fn window_size(&self) -> (usize, usize) {
+        let pty = self.pty.lock();
+        (pty.columns, pty.rows)
+    }
+
+    /// Sets the size the apps on the slave side get, which the master side shows them in.
+    pub fn set_window_size(&mut self, columns: usize, rows: usize) {
+        let mut pty = self.pty.lock();
+        pty.columns = columns;
+        pty.rows = rows;
+    }
+
+    /// Makes the group of `task_id` the one which Ctrl-C typed on the master side interrupts
+    /// if this is the slave side. Returns whether it is.
+    pub fn set_foreground(&self, task_id: TaskID) -> bool {
+        if self.side != Side::Slave {
+            return false;
+        }
+        self.pty.lock().foreground = Some(task_id);
+        true
+    }
+
+    /// Calls `f` until it returns Some, sleeping until the other side changes the pty.
+    ///
+    /// Returns None if the task is interrupted.
+    fn wait<T>(&self, mut f: impl FnMut(&mut Pty) -> Option<T>) -> Option<T> {
+        loop {
+            unsafe { asm!("cli") };
+            let task_id = task_manager().current_task().id();
+            unsafe { asm!("sti") };
+            {
+                let mut pty = self.pty.lock();
+                if let Some(result) = f(&mut pty) {
+                    return Some(result);
+                }
+                if !pty.waiting.contains(&task_id) {
+                    pty.waiting.push(task_id);
+                }
+            }
+
+            let message = loop {
+                unsafe { asm!("cli") };
+                match task_manager().current_task_mut().receive_message() {
+                    None => {
+                        task_manager().sleep(task_id).unwrap();
+                        continue;
+                    }
+                    Some(m) => break m,
+                };
+            };
+            unsafe { asm!("sti") };
+            if message.m_type == MessageType::Interrupt {
+                return None;
+            }
+        }
+    }
+}
+
+impl Drop for PtyDescriptor {
+    fn drop(&mut self) {
+        let mut pty = self.pty.lock();
+        match self.side {
+            Side::Master => pty.master_closed = true,
+            Side::Slave => pty.slave_closed = true,
+        }
+        pty.notify();
+    }
+}
+
+/// Splits what the master side types into keys, keeping the escape sequence of a key together.
+fn split_keys(bytes: &[u8]) -> Vec<&[u8]> {
+    let mut keys = Vec::new();
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let len = if rest.starts_with(b"\x1b[") {
+            rest[2..]
+                .iter()
+                .position(|b| (b'@'..=b'~').contains(b))
+                .map_or(rest.len(), |i| i + 3)
+        } else {
+            1
+        };
+        let (key, tail) = rest.split_at(len);
+        keys.push(key);
+        rest = tail;
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::line_discipline::MODE_ECHO;
+
+    fn output(pty: &Mutex<Pty>) -> Vec<u8> {
+        let mut buf = [0; 64];
+        let len = pty.lock().read_output(&mut buf, true).unwrap();
+        buf[..len].to_vec()
+    }
+
+    #[test]
+    fn split_typed_keys() {
+        let keys: &[&[u8]] = &[b"a", b"\x1b[A", b"\n", b"\x1b[3~", b"\x1b", b"b"];
+        assert_eq!(split_keys(b"a\x1b[A\n\x1b[3~\x1bb"), keys);
+        let keys: &[&[u8]] = &[b"\x1b[12"];
+        assert_eq!(split_keys(b"\x1b[12"), keys);
+    }
+
+    #[test]
+    fn master_types_lines_and_reads_output() {
+        let (mut master, mut slave) = open_pty(false);
+        let mut buf = [0; 64];
+        assert_eq!(master.write(b"lx\x7fs\nab"), 7);
+        // the echo of the keys
+        assert_eq!(output(&master.pty), b"lx\x08 \x08s\nab");
+        assert_eq!(master.pty.lock().read_input(&mut buf), Some(3));
+        assert_eq!(&buf[..3], b"ls\n");
+        // the line being edited is not read in the canonical mode
+        assert_eq!(master.pty.lock().read_input(&mut buf), None);
+
+        slave.set_mode(TerminalMode { flags: MODE_ECHO });
+        master.write(b"\x1b[A\x03");
+        assert_eq!(master.pty.lock().read_input(&mut buf), Some(6));
+        assert_eq!(&buf[..6], b"ab\x1b[A\x03");
+        assert_eq!(output(&master.pty), b"");
+
+        assert_eq!(slave.write(b"hello"), 5);
+        assert_eq!(master.pty.lock().read_output(&mut buf[..3], false), Some(3));
+        assert_eq!(&buf[..3], b"hel");
+        assert_eq!(output(&master.pty), b"lo");
+        assert_eq!(master.pty.lock().read_output(&mut buf, false), None);
+    }
+
+    #[test]
+    fn closed_side_ends_reads() {
+        let (mut master, slave) = open_pty(false);
+        let pty = Arc::clone(&master.pty);
+        let mut buf = [0; 8];
+        slave.set_foreground(TaskID::new(1));
+        assert!(!master.set_foreground(TaskID::new(1)));
+        drop(slave);
+        assert_eq!(pty.lock().read_output(&mut buf, false), Some(0));
+        assert_eq!(master.write(b"ls\n"), 0);
+
+        let (master, mut slave) = open_pty(false);
+        slave.write(b"bye");
+        let pty = Arc::clone(&slave.pty);
+        drop(master);
+        assert_eq!(pty.lock().read_input(&mut buf), Some(0));
+        // the output nobody reads is dropped
+        slave.write(b"more");
+        assert_eq!(pty.lock().output.len(), 3);
+    }
+}
@@ -1,17 +1,24 @@
-use crate::font::{write_ascii, write_string, write_unicode};
-use crate::graphics::{fill_rectangle, Rectangle, Vector2D, COLOR_BLACK, COLOR_WHITE};
+use crate::font::write_unicode;
+use crate::graphics::{fill_rectangle, Rectangle, Vector2D, COLOR_BLACK};
 use crate::layer::LayerID;
 use crate::message::{LayerMessage, LayerOperation, Message, MessageType};
 use crate::sync::Mutex;
 use crate::task::global::{main_task_id, task_manager};
 use crate::task::TaskID;
+use crate::terminal::ansi::{Control, Erase, Parser, Style};
 use crate::terminal::lib::{COLUMNS, ROWS};
+use crate::terminal::line_discipline::LineDiscipline;
 use crate::window::{TITLED_WINDOW_BOTTOM_RIGHT_MARGIN, TITLED_WINDOW_TOP_LEFT_MARGIN};
 use crate::Window;
-use alloc::collections::BTreeMap;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
 use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
 use core::arch::asm;
+use core::cmp;
 use core::fmt::Write;
+use core::mem;
 
 // pub(super) static TERMINAL_WRITERS: RwLock<TerminalWriters> = RwLock::new(TerminalWriters::new());
 pub(super) static mut TERMINAL_WRITERS: TerminalWriters = TerminalWriters::new();
@@ -44,14 +51,83 @@ impl TerminalWriters {
         let _ = self.0.remove(&task_id);
         unsafe { asm!("sti") };
     }
+
+    /// Returns the writer drawing to the window of the layer.
+    pub fn find_by_layer(&self, layer_id: LayerID) -> Option<&Mutex<TerminalWriter>> {
+        unsafe { asm!("cli") };
+        let m = self
+            .0
+            .values()
+            .find(|writer| writer.lock().layer_id == layer_id && writer.lock().window.is_some());
+        unsafe { asm!("sti") };
+        m
+    }
+}
+
+/// A character on the screen. The right half of a wide character is `'\0'`.
+#[derive(Debug, Clone, Copy)]
+struct Cell {
+    c: char,
+    style: Style,
+}
+
+impl Cell {
+    const BLANK: Cell = Cell {
+        c: ' ',
+        style: Style::DEFAULT,
+    };
+
+    fn is_blank(&self) -> bool {
+        self.c == ' ' && self.style == Style::DEFAULT
+    }
 }
 
+#[derive(Debug, Clone)]
+struct Row {
+    cells: Vec<Cell>,
+    /// whether the line goes on to the next row because it is longer than the row
+    wrapped: bool,
+}
+
+impl Row {
+    fn blank(columns: usize) -> Row {
+        Row {
+            cells: vec![Cell::BLANK; columns],
+            wrapped: false,
+        }
+    }
+}
+
+/// The number of rows kept in the scrollback unless `SCROLLBACK` says otherwise.
+pub(super) const DEFAULT_SCROLLBACK_SIZE: usize = 500;
+
 pub(super) struct TerminalWriter {
     layer_id: LayerID,
     task_id: TaskID,
     window: Option<Arc<Mutex<Window>>>,
+    columns: usize,
+    rows: usize,
     cursor: Vector2D<i32>,
     is_cursor_visible: bool,
+    /// the position just after the prompt
+    input_origin: Vector2D<i32>,
+    /// the line drawn from `input_origin`
+    input: String,
+    /// the characters on the screen, which are drawn again when the cursor leaves them
+    screen: Vec<Row>,
+    /// the style of the characters printed next, which SGR changes
+    style: Style,
+    parser: Parser,
+    saved_cursor: Vector2D<i32>,
+    /// the first and the last rows drawn by `print`
+    dirty_rows: (i32, i32),
+    /// the rows which have scrolled out of the top of the screen, the oldest first
+    scrollback: VecDeque<Row>,
+    scrollback_size: usize,
+    /// how many rows the view is scrolled back from the bottom of `scrollback`
+    view_offset: usize,
+    /// the keys typed while an app is reading the terminal
+    line_discipline: LineDiscipline,
 }
 
 impl TerminalWriter {
@@ -60,29 +136,53 @@ impl TerminalWriter {
             layer_id,
             task_id,
             window,
+            columns: COLUMNS,
+            rows: ROWS,
             cursor: Vector2D::new(0, 0),
             is_cursor_visible: false,
+            input_origin: Vector2D::new(0, 0),
+            input: String::new(),
+            screen: vec![Row::blank(COLUMNS); ROWS],
+            style: Style::DEFAULT,
+            parser: Parser::new(),
+            saved_cursor: Vector2D::new(0, 0),
+            dirty_rows: (0, 0),
+            scrollback: VecDeque::new(),
+            scrollback_size: DEFAULT_SCROLLBACK_SIZE,
+            view_offset: 0,
+            line_discipline: LineDiscipline::new(),
         }
     }
 
+    /// Prints `s`, which may contain escape sequences, and sends the area to be drawn.
     pub fn print(&mut self, s: &str) {
-        let prev_cursor = self.calc_cursor_pos();
+        self.dirty_rows = if self.snap_back() {
+            (0, self.rows as i32 - 1)
+        } else {
+            (self.cursor.y, self.cursor.y)
+        };
         self.draw_cursor(false);
 
-        for char in s.chars() {
-            self.print_char(char);
+        for c in s.chars() {
+            if let Some(control) = self.parser.feed(c) {
+                self.control(control);
+            }
         }
 
         self.draw_cursor(true);
-        let current_cursor = self.calc_cursor_pos();
+        self.mark_dirty(self.cursor.y);
 
-        let draw_pos = Vector2D::new(TITLED_WINDOW_TOP_LEFT_MARGIN.x, prev_cursor.y);
+        let (first, last) = self.dirty_rows;
+        let draw_pos = Vector2D::new(
+            TITLED_WINDOW_TOP_LEFT_MARGIN.x,
+            Self::cell_pos(Vector2D::new(0, first)).y,
+        );
         let draw_size = Vector2D::new(
             self.window
                 .as_ref()
                 .map(|w| w.lock().inner_size().x)
                 .unwrap_or(-TITLED_WINDOW_TOP_LEFT_MARGIN.x - TITLED_WINDOW_BOTTOM_RIGHT_MARGIN.x),
-            current_cursor.y - prev_cursor.y + 16,
+            16 * (last - first + 1),
         );
         let msg = Message::new(MessageType::Layer(LayerMessage {
             layer_id: self.layer_id,
@@ -95,47 +195,177 @@ impl TerminalWriter {
         unsafe { asm!("sti") };
     }
 
+    fn control(&mut self, control: Control) {
+        let rows = self.rows as i32;
+        let columns = self.columns as i32;
+        // the cursor is beyond the last column after printing there
+        let x = cmp::min(self.cursor.x, columns - 1);
+        let y = self.cursor.y;
+        match control {
+            Control::Print(c) => self.print_char(c),
+            Control::SetGraphics(params) => self.style.apply(&params),
+            Control::CursorUp(n) => self.cursor.y = cmp::max(y - i32::from(n), 0),
+            Control::CursorDown(n) => self.cursor.y = cmp::min(y + i32::from(n), rows - 1),
+            Control::CursorForward(n) => self.cursor.x = cmp::min(x + i32::from(n), columns - 1),
+            Control::CursorBack(n) => self.cursor.x = cmp::max(x - i32::from(n), 0),
+            Control::CursorPosition(row, column) => {
+                self.cursor = Vector2D::new(
+                    cmp::min(i32::from(column), columns - 1),
+                    cmp::min(i32::from(row), rows - 1),
+                );
+            }
+            Control::CursorColumn(column) => {
+                self.cursor.x = cmp::min(i32::from(column), columns - 1)
+            }
+            Control::EraseInLine(erase) => match erase {
+                Erase::ToEnd => self.erase_cells(y, x, columns),
+                Erase::ToStart => self.erase_cells(y, 0, x + 1),
+                Erase::All => self.erase_cells(y, 0, columns),
+            },
+            Control::EraseInDisplay(erase) => {
+                let (first_row, last_row) = match erase {
+                    Erase::ToEnd => {
+                        self.erase_cells(y, x, columns);
+                        (y + 1, rows)
+                    }
+                    Erase::ToStart => {
+                        self.erase_cells(y, 0, x + 1);
+                        (0, y)
+                    }
+                    Erase::All => (0, rows),
+                };
+                for row in first_row..last_row {
+                    self.erase_cells(row, 0, columns);
+                }
+            }
+            Control::SaveCursor => self.saved_cursor = self.cursor,
+            Control::RestoreCursor => self.cursor = self.saved_cursor,
+        }
+    }
+
     pub fn print_char(&mut self, c: char) {
-        let window = match &self.window {
-            None => return,
-            Some(w) => Arc::clone(w),
-        };
+        let columns = self.columns as i32;
+        match c {
+            '\n' => {
+                self.new_line();
+                return;
+            }
+            '\r' => {
+                self.cursor.x = 0;
+                return;
+            }
+            '\x08' => {
+                self.cursor.x = cmp::max(cmp::min(self.cursor.x, columns) - 1, 0);
+                return;
+            }
+            '\t' => {
+                self.cursor.x = cmp::min((self.cursor.x / 8 + 1) * 8, columns - 1);
+                return;
+            }
+            c if c.is_ascii_control() => return,
+            _ => {}
+        }
 
-        if c == '\n' {
+        let width = if c.is_ascii() { 1 } else { 2 };
+        if self.cursor.x + width > columns {
+            self.screen[self.cursor.y as usize].wrapped = true;
             self.new_line();
-            return;
         }
+        let row = &mut self.screen[self.cursor.y as usize].cells;
+        let x = self.cursor.x as usize;
+        row[x] = Cell {
+            c,
+            style: self.style,
+        };
+        if width == 2 {
+            row[x + 1] = Cell {
+                c: '\0',
+                style: self.style,
+            };
+        }
+        self.draw_cell(self.cursor);
+        self.mark_dirty(self.cursor.y);
+        self.cursor.x += width;
+    }
 
-        let columns = COLUMNS as i32;
-        if c.is_ascii() {
-            if self.cursor.x == columns {
-                self.new_line();
-            }
-            let pos = self.calc_cursor_pos();
-            write_unicode(
-                &mut window.lock().normal_window_writer(),
-                pos.x,
-                pos.y,
-                c,
-                &COLOR_WHITE,
-            )
-            .unwrap_or_default();
-            self.cursor.x += 1;
+    /// Returns the cell on the screen, which is blank beyond the last column.
+    fn cell_at(&self, cell: Vector2D<i32>) -> Cell {
+        self.screen
+            .get(cell.y as usize)
+            .and_then(|row| row.cells.get(cell.x as usize))
+            .copied()
+            .unwrap_or(Cell::BLANK)
+    }
+
+    /// Draws the character of the cell, or the wide character whose right half is the cell.
+    fn draw_cell(&self, mut cell: Vector2D<i32>) {
+        if cell.x > 0 && self.cell_at(cell).c == '\0' {
+            cell.x -= 1;
+        }
+        let width = if self.cell_at(cell).c.is_ascii() {
+            8
         } else {
-            if self.cursor.x == columns - 1 {
-                self.new_line();
+            16
+        };
+        self.draw_glyph(cell, self.cell_at(cell), Vector2D::new(width, 16), false);
+    }
+
+    /// Fills `size` from the position of `at` with the background color of `cell`
+    /// and draws its character on it, swapping the colors if `inverted`.
+    fn draw_glyph(&self, at: Vector2D<i32>, cell: Cell, size: Vector2D<i32>, inverted: bool) {
+        let window = match &self.window {
+            None => return,
+            Some(w) => w,
+        };
+        let Cell { c, style } = cell;
+        let (fg, bg) = if inverted {
+            let (fg, bg) = style.colors();
+            (bg, fg)
+        } else {
+            style.colors()
+        };
+        let pos = Self::cell_pos(at);
+        let mut window = window.lock();
+        let mut writer = window.normal_window_writer();
+        fill_rectangle(&mut writer, &pos, &size, &bg);
+        if c != '\0' {
+            write_unicode(&mut writer, pos.x, pos.y, c, &fg).unwrap_or_default();
+            if style.bold {
+                write_unicode(&mut writer, pos.x + 1, pos.y, c, &fg).unwrap_or_default();
             }
-            let pos = self.calc_cursor_pos();
-            write_unicode(
+        }
+    }
+
+    /// Erases the cells of the row from the column `start` to just before `end`
+    /// with the current background color.
+    fn erase_cells(&mut self, row: i32, start: i32, end: i32) {
+        let end = cmp::min(end, self.columns as i32);
+        if start >= end {
+            return;
+        }
+        let blank = Cell {
+            c: ' ',
+            style: self.style.erased(),
+        };
+        let screen_row = &mut self.screen[row as usize];
+        screen_row.cells[start as usize..end as usize].fill(blank);
+        if start == 0 && end == self.columns as i32 {
+            screen_row.wrapped = false;
+        }
+        if let Some(window) = &self.window {
+            fill_rectangle(
                 &mut window.lock().normal_window_writer(),
-                pos.x,
-                pos.y,
-                c,
-                &COLOR_WHITE,
-            )
-            .unwrap_or_default();
-            self.cursor.x += 2;
+                &Self::cell_pos(Vector2D::new(start, row)),
+                &Vector2D::new(8 * (end - start), 16),
+                &blank.style.bg,
+            );
         }
+        self.mark_dirty(row);
+    }
+
+    fn mark_dirty(&mut self, row: i32) {
+        let (first, last) = self.dirty_rows;
+        self.dirty_rows = (cmp::min(first, row), cmp::max(last, row));
     }
 
     pub fn blink_cursor(&mut self) -> Rectangle<i32> {
@@ -145,14 +375,29 @@ impl TerminalWriter {
     }
 
     pub fn calc_cursor_pos(&self) -> Vector2D<i32> {
-        TITLED_WINDOW_TOP_LEFT_MARGIN + Vector2D::new(4 + 8 * self.cursor.x, 4 + 16 * self.cursor.y)
+        Self::cell_pos(self.cursor)
+    }
+
+    /// Returns the position of the cell at the column and the row in the window.
+    fn cell_pos(cell: Vector2D<i32>) -> Vector2D<i32> {
+        TITLED_WINDOW_TOP_LEFT_MARGIN + Vector2D::new(4 + 8 * cell.x, 4 + 16 * cell.y)
     }
 
     pub fn scroll1(&mut self) {
+        let top = self.screen.remove(0);
+        self.screen.push(Row::blank(self.columns));
+        // terminals without a window have nothing to scroll back to
+        if self.window.is_some() && self.scrollback_size > 0 {
+            if self.scrollback.len() == self.scrollback_size {
+                self.scrollback.pop_front();
+            }
+            self.scrollback.push_back(top);
+        }
+        self.dirty_rows = (0, self.rows as i32 - 1);
         if let Some(window) = &self.window {
             let move_src = Rectangle::new(
                 TITLED_WINDOW_TOP_LEFT_MARGIN + Vector2D::new(4, 4 + 16),
-                Vector2D::new(8 * COLUMNS as i32, 16 * (ROWS as i32 - 1)),
+                Vector2D::new(8 * self.columns as i32, 16 * (self.rows as i32 - 1)),
             );
             window.lock().move_(
                 TITLED_WINDOW_TOP_LEFT_MARGIN + Vector2D::new(4, 4),
@@ -161,27 +406,25 @@ impl TerminalWriter {
             fill_rectangle(
                 window.lock().writer(),
                 &Vector2D::new(4, 4 + 16 * self.cursor.y),
-                &Vector2D::new(8 * COLUMNS as i32, 16),
+                &Vector2D::new(8 * self.columns as i32, 16),
                 &COLOR_BLACK,
             );
         }
     }
 
+    /// Draws the cursor as the inverted character under it, or the character as it is.
+    /// The cursor is hidden while the view is scrolled back.
     pub fn draw_cursor(&mut self, visible: bool) {
-        if let Some(window) = &self.window {
-            let color = if visible { &COLOR_WHITE } else { &COLOR_BLACK };
-            fill_rectangle(
-                &mut window.lock().normal_window_writer(),
-                &self.calc_cursor_pos(),
-                &Vector2D::new(7, 15),
-                color,
-            );
+        if self.view_offset > 0 {
+            return;
         }
+        let cell = self.cell_at(self.cursor);
+        self.draw_glyph(self.cursor, cell, Vector2D::new(7, 15), visible);
     }
 
     pub fn new_line(&mut self) {
         self.cursor.x = 0;
-        if self.cursor.y < ROWS as i32 - 1 {
+        if self.cursor.y < self.rows as i32 - 1 {
             self.cursor.y += 1;
         } else {
             self.scroll1()
@@ -206,75 +449,229 @@ impl TerminalWriter {
         unsafe { asm!("sti") };
     }
 
-    pub fn can_write_on_this_line(&self) -> bool {
-        self.cursor.x < COLUMNS as i32 - 1
-    }
-
-    pub fn back_space(&mut self) {
-        self.cursor.x -= 1;
+    pub fn clear(&mut self) {
+        self.snap_back();
+        self.screen.fill(Row::blank(self.columns));
         if let Some(window) = &self.window {
             fill_rectangle(
-                &mut window.lock().normal_window_writer(),
-                &self.calc_cursor_pos(),
-                &Vector2D::new(8, 16),
+                window.lock().writer(),
+                &Vector2D::new(4, 4),
+                &Vector2D::new(8 * self.columns as i32, 16 * self.rows as i32),
                 &COLOR_BLACK,
             );
         }
+        self.cursor = Vector2D::new(0, 0);
     }
 
-    pub fn input_ascii(&mut self, ascii: char) {
-        assert!(self.can_write_on_this_line());
+    /// Changes the number of rows kept in the scrollback, dropping the oldest ones
+    /// which no longer fit.
+    pub fn set_scrollback_size(&mut self, size: usize) {
+        self.scrollback_size = size;
+        while self.scrollback.len() > size {
+            self.scrollback.pop_front();
+        }
+        self.view_offset = cmp::min(self.view_offset, self.scrollback.len());
+    }
 
-        let pos = self.calc_cursor_pos();
-        if let Some(window) = &self.window {
-            write_ascii(
-                &mut window.lock().normal_window_writer(),
-                pos.x,
-                pos.y,
-                ascii,
-                &COLOR_WHITE,
-            );
+    /// Scrolls the view back by `rows`, or forward if it is negative, and returns the area
+    /// to be drawn.
+    pub fn scroll_view(&mut self, rows: isize) -> Rectangle<i32> {
+        let offset = if rows < 0 {
+            self.view_offset.saturating_sub(rows.unsigned_abs())
+        } else {
+            cmp::min(self.view_offset + rows as usize, self.scrollback.len())
+        };
+        if offset != self.view_offset {
+            self.view_offset = offset;
+            self.draw_view();
         }
-        self.cursor.x += 1;
+        self.window_area()
     }
 
-    pub fn clear(&mut self) {
-        if let Some(window) = &self.window {
-            fill_rectangle(
-                window.lock().writer(),
-                &Vector2D::new(4, 4),
-                &Vector2D::new(8 * COLUMNS as i32, 16 * ROWS as i32),
-                &COLOR_BLACK,
-            );
+    /// Returns the view to the bottom if it is scrolled back, and returns whether it is.
+    fn snap_back(&mut self) -> bool {
+        if self.view_offset == 0 {
+            return false;
         }
-        self.cursor = Vector2D::new(0, 0);
+        self.view_offset = 0;
+        self.draw_view();
+        true
     }
 
-    pub fn history_up_down(&mut self, line: &str) -> Rectangle<i32> {
-        self.cursor.x = 1;
-        let first_pos = self.calc_cursor_pos();
-        let draw_area = Rectangle::new(first_pos, Vector2D::new(8 * (COLUMNS as i32 - 1), 16));
-        if let Some(window) = &self.window {
-            fill_rectangle(
-                &mut window.lock().normal_window_writer(),
-                &draw_area.pos,
-                &draw_area.size,
-                &COLOR_BLACK,
-            );
+    /// Draws the rows of the scrollback and the screen which the view shows.
+    fn draw_view(&self) {
+        let first = self.scrollback.len() - self.view_offset;
+        let rows = self
+            .scrollback
+            .range(first..)
+            .chain(self.screen.iter())
+            .take(self.rows);
+        for (y, row) in rows.enumerate() {
+            for (x, &cell) in row.cells.iter().enumerate() {
+                if cell.c == '\0' {
+                    continue;
+                }
+                let width = if cell.c.is_ascii() { 8 } else { 16 };
+                let at = Vector2D::new(x as i32, y as i32);
+                self.draw_glyph(at, cell, Vector2D::new(width, 16), false);
+            }
         }
+    }
 
-        if let Some(window) = &self.window {
-            write_string(
-                &mut window.lock().normal_window_writer(),
-                first_pos.x,
-                first_pos.y,
-                line,
-                &COLOR_WHITE,
-            );
+    fn window_area(&self) -> Rectangle<i32> {
+        let size = self.window_inner_size().unwrap_or(Vector2D::new(0, 0));
+        Rectangle::new(TITLED_WINDOW_TOP_LEFT_MARGIN, size)
+    }
+
+    /// Starts the input line at the cursor, which is just after the prompt.
+    pub fn start_input(&mut self) {
+        self.input_origin = self.cursor;
+        self.input.clear();
+    }
+
+    /// Moves the cursor to the end of the input line, after which output follows.
+    pub fn end_input(&mut self) {
+        self.cursor = self.clip(self.input_cell(self.input.len()));
+        self.input.clear();
+    }
+
+    /// Returns the column and the row of the `index`-th character of the input line.
+    fn input_cell(&self, index: usize) -> Vector2D<i32> {
+        let columns = self.columns as i32;
+        let i = self.input_origin.x + index as i32;
+        Vector2D::new(i % columns, self.input_origin.y + i / columns)
+    }
+
+    /// Returns `cell` of the input line, or the last cell of the screen if the line goes
+    /// beyond the bottom.
+    fn clip(&self, cell: Vector2D<i32>) -> Vector2D<i32> {
+        if cell.y < self.rows as i32 {
+            cell
+        } else {
+            Vector2D::new(self.columns as i32 - 1, self.rows as i32 - 1)
         }
-        self.cursor.x = line.len() as i32 + 1;
+    }
+
+    /// Draws the input line over the previous one and puts the cursor at `cursor` in it.
+    ///
+    /// The line wraps at the right edge, and the window scrolls if it goes beyond the bottom.
+    /// The rest of a line longer than the screen is not shown.
+    pub fn draw_input(&mut self, line: &str, cursor: usize) -> Rectangle<i32> {
+        let mut scrolled = self.snap_back();
+        // the cursor may be just after the last character
+        let mut last_row = cmp::max(
+            self.input_cell(line.len()).y,
+            self.input_cell(self.input.len()).y,
+        );
+        while last_row >= self.rows as i32 && self.input_origin.y > 0 {
+            self.cursor.y = self.rows as i32 - 1;
+            self.scroll1();
+            self.input_origin.y -= 1;
+            last_row -= 1;
+            scrolled = true;
+        }
+
+        let last_row = cmp::min(last_row, self.rows as i32 - 1);
+
+        let first = self.input_cell(0);
+        let columns = self.columns as i32;
+        self.erase_cells(first.y, first.x, columns);
+        for row in first.y + 1..=last_row {
+            self.erase_cells(row, 0, columns);
+        }
+        for (i, c) in line.chars().enumerate() {
+            let cell = self.input_cell(i);
+            if cell.y > last_row {
+                break;
+            }
+            let row = &mut self.screen[cell.y as usize];
+            row.cells[cell.x as usize] = Cell {
+                c,
+                style: self.style,
+            };
+            // the line goes on if the next character is on the next row
+            row.wrapped = i + 1 < line.len() && cell.x == columns - 1;
+            self.draw_cell(cell);
+        }
+        self.input = line.to_string();
+        self.cursor = self.clip(self.input_cell(cursor));
+
+        if scrolled {
+            return self.window_area();
+        }
+        Rectangle::new(
+            Self::cell_pos(Vector2D::new(0, first.y)),
+            Vector2D::new(8 * columns, 16 * (last_row - first.y + 1)),
+        )
+    }
+
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
 
-        draw_area
+    pub fn line_discipline(&mut self) -> &mut LineDiscipline {
+        &mut self.line_discipline
+    }
+
+    pub fn window(&self) -> Option<Arc<Mutex<Window>>> {
+        self.window.clone()
+    }
+
+    /// Changes the numbers of columns and rows of the screen, whose window has been resized,
+    /// and draws the lines wrapped again at the new width.
+    pub fn resize(&mut self, columns: usize, rows: usize) {
+        let all_rows = self
+            .scrollback
+            .drain(..)
+            .chain(self.screen.drain(..))
+            .collect::<Vec<_>>();
+        let offset = Vector2D::new(0, (all_rows.len() - self.rows) as i32);
+        let mut marks = [
+            self.cursor + offset,
+            self.input_origin + offset,
+            self.saved_cursor + offset,
+        ];
+        let mut all_rows = reflow(all_rows, columns, &mut marks);
+
+        // the empty rows below the cursor are not kept so that the cursor stays on the screen
+        let cursor_row = marks[0].y as usize;
+        while all_rows.len() > cursor_row + 1
+            && all_rows
+                .last()
+                .map_or(false, |row| row.cells.iter().all(Cell::is_blank))
+        {
+            all_rows.pop();
+        }
+        while all_rows.len() < rows {
+            all_rows.push(Row::blank(columns));
+        }
+        let top = cmp::min(all_rows.len() - rows, cursor_row);
+        all_rows.truncate(top + rows);
+        self.screen = all_rows.split_off(top);
+        let dropped = all_rows.len().saturating_sub(self.scrollback_size);
+        self.scrollback = all_rows.into_iter().skip(dropped).collect();
+
+        let [cursor, input_origin, saved_cursor] =
+            marks.map(|mark| mark - Vector2D::new(0, top as i32));
+        self.cursor = cursor;
+        // the prompt may have gone out of the screen
+        self.input_origin = if input_origin.y < 0 {
+            Vector2D::new(0, 0)
+        } else {
+            input_origin
+        };
+        self.saved_cursor = Vector2D::new(
+            cmp::min(saved_cursor.x, columns as i32 - 1),
+            cmp::max(saved_cursor.y, 0),
+        );
+        self.columns = columns;
+        self.rows = rows;
+        self.view_offset = 0;
+        self.draw_view();
     }
 
     pub fn window_inner_size(&self) -> Option<Vector2D<i32>> {
@@ -282,9 +679,197 @@ impl TerminalWriter {
     }
 }
 
+/// Joins the wrapped rows into lines and wraps them again at `columns`.
+///
+/// `marks` are the columns and the rows in `rows` such as the cursor, which are moved to where
+/// their characters go.
+fn reflow(rows: Vec<Row>, columns: usize, marks: &mut [Vector2D<i32>]) -> Vec<Row> {
+    let mut reflowed = Vec::new();
+    let mut line = Vec::new();
+    // the index in `marks` and the index of the character in the line
+    let mut line_marks = Vec::new();
+    for (y, row) in rows.into_iter().enumerate() {
+        for (i, mark) in marks.iter().enumerate() {
+            if mark.y == y as i32 {
+                line_marks.push((i, line.len() + mark.x as usize));
+            }
+        }
+        line.extend(row.cells);
+        if row.wrapped {
+            continue;
+        }
+
+        // the blanks at the end are padding unless a mark is on them
+        let marked_len = line_marks
+            .iter()
+            .map(|&(_, index)| index)
+            .max()
+            .unwrap_or(0);
+        while line.len() > marked_len && line.last().map_or(false, Cell::is_blank) {
+            line.pop();
+        }
+        wrap_line(&mut line, columns, &mut reflowed, &line_marks, marks);
+        line_marks.clear();
+    }
+    if !line.is_empty() {
+        wrap_line(&mut line, columns, &mut reflowed, &line_marks, marks);
+    }
+    reflowed
+}
+
+/// Appends the rows of the line wrapped at `columns` to `rows`, and moves the marks in the line.
+fn wrap_line(
+    line: &mut Vec<Cell>,
+    columns: usize,
+    rows: &mut Vec<Row>,
+    line_marks: &[(usize, usize)],
+    marks: &mut [Vector2D<i32>],
+) {
+    let mut row = Row::blank(columns);
+    let mut x = 0;
+    // the position of each character in the line, and the one just after the last character
+    let mut positions = Vec::with_capacity(line.len() + 1);
+    let mut cells = line.drain(..).peekable();
+    while let Some(cell) = cells.next() {
+        // a wide character is not split into two rows
+        let is_wide = cells.peek().map_or(false, |next| next.c == '\0');
+        let width = if is_wide { 2 } else { 1 };
+        if x + width > columns {
+            row.wrapped = true;
+            rows.push(mem::replace(&mut row, Row::blank(columns)));
+            x = 0;
+        }
+        positions.push(Vector2D::new(x as i32, rows.len() as i32));
+        row.cells[x] = cell;
+        if is_wide {
+            positions.push(Vector2D::new(x as i32 + 1, rows.len() as i32));
+            row.cells[x + 1] = cells.next().unwrap();
+        }
+        x += width;
+    }
+    // the cursor may be just beyond the last column as after printing there
+    positions.push(Vector2D::new(x as i32, rows.len() as i32));
+    rows.push(row);
+
+    for &(i, index) in line_marks {
+        let last = positions[positions.len() - 1];
+        marks[i] = positions.get(index).copied().unwrap_or(last);
+    }
+}
+
 impl Write for TerminalWriter {
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         self.print(s);
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::terminal::lib::{LINE_MAX, MIN_COLUMNS, MIN_ROWS};
+
+    fn rows(lines: &[(&str, bool)]) -> Vec<Row> {
+        lines
+            .iter()
+            .map(|&(line, wrapped)| {
+                let mut row = Row::blank(4);
+                for (x, c) in line.chars().enumerate() {
+                    row.cells[x].c = c;
+                }
+                row.wrapped = wrapped;
+                row
+            })
+            .collect()
+    }
+
+    fn text(rows: &[Row]) -> Vec<(String, bool)> {
+        rows.iter()
+            .map(|row| {
+                let line = row.cells.iter().map(|cell| cell.c).collect::<String>();
+                (line.trim_end().to_string(), row.wrapped)
+            })
+            .collect()
+    }
+
+    fn writer() -> TerminalWriter {
+        TerminalWriter::new(LayerID::new(0), TaskID::new(0), None)
+    }
+
+    #[test]
+    fn input_longer_than_screen_is_clipped() {
+        let mut writer = writer();
+        writer.cursor = Vector2D::new(2, 5);
+        writer.start_input();
+        let line = "a".repeat(1000);
+        writer.draw_input(&line, 10);
+        assert_eq!(writer.input_origin, Vector2D::new(2, 0));
+        assert_eq!(writer.cursor, Vector2D::new(12, 0));
+        writer.draw_input(&line, line.len());
+        assert_eq!(
+            writer.cursor,
+            Vector2D::new(COLUMNS as i32 - 1, ROWS as i32 - 1)
+        );
+        writer.draw_input("ab", 2);
+        writer.end_input();
+        assert_eq!(writer.cursor, Vector2D::new(4, 0));
+    }
+
+    #[test]
+    fn smallest_terminal_holds_input_line() {
+        let mut writer = writer();
+        writer.resize(MIN_COLUMNS, MIN_ROWS);
+        // the prompt is at the end of the last row
+        writer.cursor = Vector2D::new(MIN_COLUMNS as i32 - 1, MIN_ROWS as i32 - 1);
+        writer.start_input();
+        writer.draw_input(&"a".repeat(LINE_MAX), LINE_MAX);
+        assert_eq!(writer.input_origin.y, 0);
+        assert_eq!(writer.cursor, writer.input_cell(LINE_MAX));
+    }
+
+    #[test]
+    fn reflow_joins_and_wraps_lines() {
+        let mut marks = [Vector2D::new(1, 3), Vector2D::new(2, 1)];
+        let reflowed = reflow(
+            rows(&[("abcd", true), ("ef", false), ("", false), ("gh", false)]),
+            3,
+            &mut marks,
+        );
+        assert_eq!(
+            text(&reflowed),
+            [
+                ("abc".to_string(), true),
+                ("def".to_string(), false),
+                ("".to_string(), false),
+                ("gh".to_string(), false),
+            ]
+        );
+        // the cursor after the last column stays there
+        assert_eq!(marks, [Vector2D::new(1, 3), Vector2D::new(3, 1)]);
+
+        // the blanks before a mark are kept
+        let mut marks = [Vector2D::new(3, 0)];
+        let reflowed = reflow(rows(&[("a", false)]), 2, &mut marks);
+        assert_eq!(
+            text(&reflowed),
+            [("a".to_string(), true), ("".to_string(), false)]
+        );
+        assert_eq!(marks, [Vector2D::new(1, 1)]);
+    }
+
+    #[test]
+    fn reflow_keeps_wide_characters_in_a_row() {
+        let mut line = rows(&[("aあ\0b", false)]);
+        let mut marks = [Vector2D::new(4, 0)];
+        let reflowed = reflow(line.split_off(0), 2, &mut marks);
+        assert_eq!(
+            text(&reflowed),
+            [
+                ("a".to_string(), true),
+                ("あ\0".to_string(), true),
+                ("b".to_string(), false),
+            ]
+        );
+        assert_eq!(marks, [Vector2D::new(1, 2)]);
+    }
+}
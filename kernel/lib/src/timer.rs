@@ -1,4 +1,5 @@
 use crate::interrupt::global::notify_end_of_interrupt;
+use crate::interrupt::{count_interrupt, InterruptVectorNumber};
 use crate::message::{Message, MessageType};
 use crate::task::global::{main_task_id, task_manager};
 use crate::task::{TaskContext, TaskID, TaskManager};
@@ -74,6 +75,7 @@ pub mod global {
 #[no_mangle]
 #[allow(clippy::not_unsafe_ptr_arg_deref)]
 pub extern "C" fn LAPICTimerOnInterrupt(context_stack: *const TaskContext) {
+    count_interrupt(InterruptVectorNumber::LAPICTimer as u8);
     let context_ref = unsafe { context_stack.as_ref() }.unwrap();
     let task_timer_timeout = timer_manager().tick(task_manager());
     notify_end_of_interrupt();
@@ -0,0 +1,107 @@
+use crate::asm::global::{copy_user_memory, get_cr3};
+use crate::error::{Code, Error};
+use crate::make_error;
+use crate::paging::global::copy_on_page;
+use crate::paging::{LinearAddress4Level, PageMapEntry};
+use crate::task::global::task_manager;
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::{cmp, mem, slice};
+
+/// Applications live in the upper half of the canonical address space.
+pub(crate) const USER_SPACE_BEGIN: u64 = 0xffff_8000_0000_0000;
+
+const PAGE_SIZE: u64 = 4096;
+
+/// Checks that `[addr, addr + len)` lies in the user half and every page of it is mapped,
+/// or will be mapped by the page fault handler.
+///
+/// Pages shared by copy-on-write are copied in advance if `write` is true,
+/// because the kernel runs with CR0.WP cleared and would write to the shared page otherwise.
+pub(crate) fn verify_user_range(addr: u64, len: usize, write: bool) -> Result<(), Error> {
+    if len == 0 {
+        return Ok(());
+    }
+    if addr < USER_SPACE_BEGIN {
+        return Err(make_error!(Code::BadAddress));
+    }
+    let last = addr
+        .checked_add(len as u64 - 1)
+        .ok_or_else(|| make_error!(Code::BadAddress))?;
+
+    unsafe { asm!("cli") };
+    let task = task_manager().current_task();
+    unsafe { asm!("sti") };
+
+    let cr3 = get_cr3();
+    let mut page = addr & !(PAGE_SIZE - 1);
+    loop {
+        match PageMapEntry::find_user_page(LinearAddress4Level::new(page), cr3) {
+            Some(entry) => {
+                if write && !entry.writable() {
+                    copy_on_page(page)?;
+                }
+            }
+            None => {
+                let demand_paged = task.dpaging_begin <= page && page < task.dpaging_end;
+                if !demand_paged && task.find_file_mapping(page).is_none() {
+                    return Err(make_error!(Code::BadAddress));
+                }
+            }
+        }
+
+        if page >= last & !(PAGE_SIZE - 1) {
+            return Ok(());
+        }
+        page += PAGE_SIZE;
+    }
+}
+
+pub(crate) fn copy_from_user(dst: &mut [u8], src: u64) -> Result<(), Error> {
+    verify_user_range(src, dst.len(), false)?;
+    if copy_user_memory(dst.as_mut_ptr(), src as *const u8, dst.len()) {
+        Ok(())
+    } else {
+        Err(make_error!(Code::BadAddress))
+    }
+}
+
+pub(crate) fn copy_to_user(dst: u64, src: &[u8]) -> Result<(), Error> {
+    verify_user_range(dst, src.len(), true)?;
+    if copy_user_memory(dst as *mut u8, src.as_ptr(), src.len()) {
+        Ok(())
+    } else {
+        Err(make_error!(Code::BadAddress))
+    }
+}
+
+pub(crate) fn copy_value_to_user<T>(dst: u64, value: &T) -> Result<(), Error> {
+    let bytes =
+        unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) };
+    copy_to_user(dst, bytes)
+}
+
+/// Copies a NUL-terminated string excluding the NUL.
+///
+/// Returns `Code::BufferTooSmall` if no NUL is found within `max_len` bytes.
+pub(crate) fn strncpy_from_user(src: u64, max_len: usize) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+    let mut addr = src;
+    let mut chunk = [0_u8; 256];
+    while bytes.len() < max_len {
+        // never read beyond the page that may contain the terminating NUL
+        let page_remain = (PAGE_SIZE - (addr & (PAGE_SIZE - 1))) as usize;
+        let len = cmp::min(cmp::min(page_remain, max_len - bytes.len()), chunk.len());
+        copy_from_user(&mut chunk[..len], addr)?;
+
+        if let Some(nul) = chunk[..len].iter().position(|&b| b == 0) {
+            bytes.extend_from_slice(&chunk[..nul]);
+            return Ok(bytes);
+        }
+        bytes.extend_from_slice(&chunk[..len]);
+        addr = addr
+            .checked_add(len as u64)
+            .ok_or_else(|| make_error!(Code::BadAddress))?;
+    }
+    Err(make_error!(Code::BufferTooSmall))
+}
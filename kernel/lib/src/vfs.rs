@@ -0,0 +1,277 @@
+//! Virtual filesystem, which passes each path operation to the filesystem mounted at the longest
+//! mount point the path starts with.
+//!
+//! Paths given to the functions of this module are absolute and normalized by `resolve_path`.
+//! A filesystem receives them relative to its mount point without leading and trailing slashes,
+//! so its root is "".
+
+use crate::error::{Code, Error};
+use crate::io::{FileDescriptor, FileStat, S_IFDIR};
+use crate::make_error;
+use crate::sync::Mutex;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+pub(crate) mod devfs;
+pub(crate) mod fatfs;
+pub(crate) mod procfs;
+pub(crate) mod ramfs;
+
+pub mod global {
+    use super::devfs::DevFileSystem;
+    use super::fatfs::FatFileSystem;
+    use super::mount;
+    use super::procfs::ProcFileSystem;
+    use super::ramfs::RamFileSystem;
+    use alloc::sync::Arc;
+
+    /// Mounts the boot volume at "/", an empty ramfs at "/tmp", procfs at "/proc"
+    /// and devfs at "/dev".
+    pub fn initialize() {
+        mount("/", Arc::new(FatFileSystem)).unwrap();
+        mount("/tmp", Arc::new(RamFileSystem::default())).unwrap();
+        mount("/proc", Arc::new(ProcFileSystem::default())).unwrap();
+        mount("/dev", Arc::new(DevFileSystem)).unwrap();
+    }
+}
+
+pub(crate) trait FileSystem: Send + Sync {
+    /// Returns the type and the size of the file at `path`.
+    fn lookup(&self, path: &str) -> Result<FileStat, Error>;
+
+    /// Opens the file at `path` for reading and writing, creating it if `create` is true and
+    /// emptying it if `truncate` is true.
+    fn open(&self, path: &str, create: bool, truncate: bool) -> Result<FileDescriptor, Error>;
+
+    /// Returns the entries of the directory at `path`.
+    fn read_dir(&self, path: &str) -> Result<Vec<DirectoryItem>, Error>;
+
+    fn make_directory(&self, _path: &str) -> Result<(), Error> {
+        Err(make_error!(Code::ReadOnly))
+    }
+
+    /// Removes the file at `path`. Directories are not removed.
+    fn remove_file(&self, _path: &str) -> Result<(), Error> {
+        Err(make_error!(Code::ReadOnly))
+    }
+
+    /// Removes the empty directory at `path`.
+    fn remove_directory(&self, _path: &str) -> Result<(), Error> {
+        Err(make_error!(Code::ReadOnly))
+    }
+
+    /// Moves the entry at `old_path` to `new_path`, replacing the file at `new_path` if any.
+    fn rename(&self, _old_path: &str, _new_path: &str) -> Result<(), Error> {
+        Err(make_error!(Code::ReadOnly))
+    }
+
+    /// Writes modified data back to the underlying device.
+    fn sync(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+pub(crate) struct DirectoryItem {
+    pub(crate) name: String,
+    pub(crate) stat: FileStat,
+}
+
+impl DirectoryItem {
+    pub(crate) fn new(name: String, stat: FileStat) -> Self {
+        Self { name, stat }
+    }
+}
+
+/// An open directory, whose entries are read at `open_directory`.
+pub(crate) struct DirectoryDescriptor {
+    items: Vec<DirectoryItem>,
+    next: usize,
+}
+
+impl DirectoryDescriptor {
+    pub(crate) fn new(items: Vec<DirectoryItem>) -> Self {
+        Self { items, next: 0 }
+    }
+
+    pub(crate) fn next_item(&mut self) -> Option<&DirectoryItem> {
+        let item = self.items.get(self.next)?;
+        self.next += 1;
+        Some(item)
+    }
+}
+
+struct Mount {
+    path: String,
+    fs: Arc<dyn FileSystem>,
+}
+
+static MOUNTS: Mutex<Vec<Mount>> = Mutex::new(Vec::new());
+
+/// Mounts `fs` at `path`, which does not need to exist in the parent filesystem.
+pub(crate) fn mount(path: &str, fs: Arc<dyn FileSystem>) -> Result<(), Error> {
+    let path = normalize_mount_point(path);
+    let mut mounts = MOUNTS.lock();
+    if mounts.iter().any(|m| m.path == path) {
+        return Err(make_error!(Code::Busy));
+    }
+    mounts.push(Mount { path, fs });
+    Ok(())
+}
+
+pub(crate) fn lookup(path: &str) -> Result<FileStat, Error> {
+    let (_, fs, rel) = resolve(path)?;
+    let stat = fs.lookup(&rel)?;
+    if path.len() > 1 && path.ends_with('/') && stat.mode != S_IFDIR {
+        return Err(make_error!(Code::NotDirectory));
+    }
+    Ok(stat)
+}
+
+pub(crate) fn open(path: &str, create: bool, truncate: bool) -> Result<FileDescriptor, Error> {
+    if path.ends_with('/') {
+        return match lookup(path) {
+            Ok(_) => Err(make_error!(Code::IsDirectory)),
+            Err(e) => Err(e),
+        };
+    }
+    let (_, fs, rel) = resolve(path)?;
+    fs.open(&rel, create, truncate)
+}
+
+/// Returns the entries of the directory at `path`, including the mount points just under it.
+pub(crate) fn read_dir(path: &str) -> Result<Vec<DirectoryItem>, Error> {
+    let (_, fs, rel) = resolve(path)?;
+    let mut items = fs.read_dir(&rel)?;
+
+    let dir = normalize_mount_point(path);
+    for mount in MOUNTS.lock().iter() {
+        let name = match parent_and_name(&mount.path) {
+            Some((parent, name)) if parent == dir => name,
+            _ => continue,
+        };
+        if !items.iter().any(|item| item.name == name) {
+            let stat = FileStat {
+                size: 0,
+                mode: S_IFDIR,
+            };
+            items.push(DirectoryItem::new(name.to_string(), stat));
+        }
+    }
+    Ok(items)
+}
+
+pub(crate) fn make_directory(path: &str) -> Result<(), Error> {
+    let (_, fs, rel) = resolve(path)?;
+    if rel.is_empty() {
+        return Err(make_error!(Code::FileExists));
+    }
+    fs.make_directory(&rel)
+}
+
+pub(crate) fn remove_file(path: &str) -> Result<(), Error> {
+    let (_, fs, rel) = resolve(path)?;
+    if rel.is_empty() {
+        return Err(make_error!(Code::IsDirectory));
+    }
+    fs.remove_file(&rel)
+}
+
+pub(crate) fn remove_directory(path: &str) -> Result<(), Error> {
+    let (_, fs, rel) = resolve(path)?;
+    if rel.is_empty() {
+        return Err(make_error!(Code::Busy));
+    }
+    fs.remove_directory(&rel)
+}
+
+pub(crate) fn rename(old_path: &str, new_path: &str) -> Result<(), Error> {
+    let (old_mount, fs, old_rel) = resolve(old_path)?;
+    let (new_mount, _, new_rel) = resolve(new_path)?;
+    if old_rel.is_empty() || new_rel.is_empty() {
+        return Err(make_error!(Code::Busy));
+    }
+    if old_mount != new_mount {
+        return Err(make_error!(Code::CrossDevice));
+    }
+    fs.rename(&old_rel, &new_rel)
+}
+
+/// Writes modified data of all mounted filesystems back.
+pub(crate) fn sync() -> Result<(), Error> {
+    let filesystems = MOUNTS
+        .lock()
+        .iter()
+        .map(|m| Arc::clone(&m.fs))
+        .collect::<Vec<_>>();
+    filesystems.iter().try_for_each(|fs| fs.sync())
+}
+
+/// Returns the index of the mount `path` belongs to, its filesystem and the path relative to it.
+fn resolve(path: &str) -> Result<(usize, Arc<dyn FileSystem>, String), Error> {
+    let mounts = MOUNTS.lock();
+    let (index, rel) = mounts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, m)| relative_path(&m.path, path).map(|rel| (i, rel)))
+        .max_by_key(|(i, _)| mounts[*i].path.len())
+        .ok_or_else(|| make_error!(Code::NoSuchEntry))?;
+    Ok((index, Arc::clone(&mounts[index].fs), rel.to_string()))
+}
+
+/// Returns `path` relative to `mount_point` without leading and trailing slashes,
+/// if `path` is under `mount_point`.
+fn relative_path<'a>(mount_point: &str, path: &'a str) -> Option<&'a str> {
+    let rest = if mount_point == "/" {
+        path
+    } else {
+        let rest = path.strip_prefix(mount_point)?;
+        if !rest.is_empty() && !rest.starts_with('/') {
+            return None;
+        }
+        rest
+    };
+    Some(rest.trim_matches('/'))
+}
+
+fn normalize_mount_point(path: &str) -> String {
+    let path = path.trim_end_matches('/');
+    if path.is_empty() {
+        "/".to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+/// Splits an absolute path into the parent directory and the last element; None for "/".
+fn parent_and_name(path: &str) -> Option<(&str, &str)> {
+    let slash = path.rfind('/')?;
+    let name = &path[slash + 1..];
+    if name.is_empty() {
+        return None;
+    }
+    Some((if slash == 0 { "/" } else { &path[..slash] }, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_path_to_mount_point() {
+        assert_eq!(relative_path("/", "/"), Some(""));
+        assert_eq!(relative_path("/", "/apps/cp"), Some("apps/cp"));
+        assert_eq!(relative_path("/tmp", "/tmp"), Some(""));
+        assert_eq!(relative_path("/tmp", "/tmp/"), Some(""));
+        assert_eq!(relative_path("/tmp", "/tmp/a/b/"), Some("a/b"));
+        assert_eq!(relative_path("/tmp", "/tmpfile"), None);
+        assert_eq!(relative_path("/tmp", "/"), None);
+    }
+
+    #[test]
+    fn parent_and_name_of_mount_point() {
+        assert_eq!(parent_and_name("/"), None);
+        assert_eq!(parent_and_name("/tmp"), Some(("/", "tmp")));
+        assert_eq!(parent_and_name("/mnt/disk"), Some(("/mnt", "disk")));
+    }
+}
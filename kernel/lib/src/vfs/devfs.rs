@@ -0,0 +1,237 @@
+//! Device files, which read and write devices or generate data instead of storing it.
+
+use crate::error::{Code, Error};
+use crate::graphics::global::frame_buffer_config;
+use crate::io::{FileDescriptor, FileStat, S_IFDIR};
+use crate::make_error;
+use crate::vfs::{DirectoryItem, FileSystem};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use core::arch::x86_64::{__cpuid, _rdrand64_step, _rdtsc};
+use core::cmp;
+use core::ptr;
+
+const DEVICES: [&str; 4] = ["null", "zero", "random", "fb"];
+
+pub(crate) struct DevFileSystem;
+
+impl FileSystem for DevFileSystem {
+    fn lookup(&self, path: &str) -> Result<FileStat, Error> {
+        if path.is_empty() {
+            return Ok(FileStat {
+                size: 0,
+                mode: S_IFDIR,
+            });
+        }
+        Ok(open_device(path)?.stat())
+    }
+
+    /// Devices cannot be created or truncated, but opening one with `create` or `truncate`
+    /// succeeds so that a shell can redirect to it.
+    fn open(&self, path: &str, _create: bool, _truncate: bool) -> Result<FileDescriptor, Error> {
+        if path.is_empty() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        open_device(path)
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirectoryItem>, Error> {
+        if !path.is_empty() {
+            open_device(path)?;
+            return Err(make_error!(Code::NotDirectory));
+        }
+        DEVICES
+            .iter()
+            .map(|name| Ok(DirectoryItem::new(name.to_string(), self.lookup(name)?)))
+            .collect()
+    }
+}
+
+fn open_device(name: &str) -> Result<FileDescriptor, Error> {
+    match name {
+        "null" => Ok(FileDescriptor::Null),
+        "zero" => Ok(FileDescriptor::Zero),
+        "random" => Ok(FileDescriptor::Random(RandomDescriptor::new(seed()))),
+        "fb" => {
+            let config = frame_buffer_config();
+            // every pixel format of the frame buffer has 4 bytes per pixel
+            let len =
+                config.pixels_per_scan_line as usize * config.vertical_resolution as usize * 4;
+            Ok(FileDescriptor::FrameBuffer(FrameBufferDescriptor::new(
+                config.frame_buffer as usize,
+                len,
+            )))
+        }
+        _ => Err(make_error!(Code::NoSuchEntry)),
+    }
+}
+
+/// Returns a random number from RDRAND if the CPU supports it, or the time stamp counter otherwise.
+fn seed() -> u64 {
+    let has_rdrand = unsafe { __cpuid(1) }.ecx & (1 << 30) != 0;
+    if has_rdrand {
+        if let Some(value) = unsafe { rdrand() } {
+            return value;
+        }
+    }
+    unsafe { _rdtsc() }
+}
+
+#[target_feature(enable = "rdrand")]
+unsafe fn rdrand() -> Option<u64> {
+    // RDRAND may fail temporarily when its entropy is exhausted
+    for _ in 0..10 {
+        let mut value = 0;
+        if _rdrand64_step(&mut value) == 1 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// Generates pseudo random bytes by xorshift64*.
+pub(crate) struct RandomDescriptor {
+    state: u64,
+}
+
+impl RandomDescriptor {
+    pub(crate) fn new(seed: u64) -> Self {
+        // the state of xorshift must not be 0
+        Self { state: seed | 1 }
+    }
+
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+        buf.len()
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
+
+/// The frame buffer as a file of its bytes, which applications can also map.
+///
+/// Drawing to it bypasses the layers, so the next redraw of a window covers what is drawn.
+pub(crate) struct FrameBufferDescriptor {
+    base: usize,
+    len: usize,
+    offset: usize,
+}
+
+impl FrameBufferDescriptor {
+    pub(crate) fn new(base: usize, len: usize) -> Self {
+        Self {
+            base,
+            len,
+            offset: 0,
+        }
+    }
+
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.load(buf, self.offset);
+        self.offset += n;
+        n
+    }
+
+    pub(crate) fn write(&mut self, buf: &[u8]) -> usize {
+        let n = cmp::min(buf.len(), self.len - self.offset);
+        unsafe { ptr::copy(buf.as_ptr(), (self.base + self.offset) as *mut u8, n) };
+        self.offset += n;
+        n
+    }
+
+    pub(crate) fn load(&self, buf: &mut [u8], offset: usize) -> usize {
+        let begin = cmp::min(offset, self.len);
+        let n = cmp::min(buf.len(), self.len - begin);
+        unsafe { ptr::copy((self.base + begin) as *const u8, buf.as_mut_ptr(), n) };
+        n
+    }
+
+    pub(crate) fn seek(&mut self, offset: usize) -> Result<usize, Error> {
+        if offset > self.len {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+        self.offset = offset;
+        Ok(offset)
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.offset
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the physical address of the page holding `offset`, which the frame buffer
+    /// is assumed to start at the beginning of.
+    pub(crate) fn physical_page(&self, offset: usize) -> Option<u64> {
+        if offset >= self.len {
+            return None;
+        }
+        Some(((self.base + offset) & !0xfff) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::S_IFCHR;
+    use alloc::vec;
+
+    #[test]
+    fn lookup_and_open_devices() {
+        let fs = DevFileSystem;
+        assert_eq!(fs.lookup("null").unwrap().mode, S_IFCHR);
+        assert_eq!(fs.lookup("").unwrap().mode, S_IFDIR);
+        assert!(fs.lookup("none").is_err());
+        assert!(matches!(
+            fs.open("null", true, false),
+            Ok(FileDescriptor::Null)
+        ));
+        assert!(matches!(
+            fs.open("zero", false, false),
+            Ok(FileDescriptor::Zero)
+        ));
+        assert!(matches!(
+            fs.open("random", false, false),
+            Ok(FileDescriptor::Random(_))
+        ));
+        assert!(fs.read_dir("null").is_err());
+    }
+
+    #[test]
+    fn random_fills_buffer() {
+        let mut random = RandomDescriptor::new(0);
+        let mut buf = [0; 13];
+        assert_eq!(random.read(&mut buf), 13);
+        assert!(buf.iter().any(|&b| b != 0));
+
+        let mut other = [0; 13];
+        random.read(&mut other);
+        assert_ne!(buf, other);
+    }
+
+    #[test]
+    fn frame_buffer_is_bounded() {
+        let mut memory = vec![0u8; 0x2000];
+        let mut fb = FrameBufferDescriptor::new(memory.as_mut_ptr() as usize, 0x1800);
+        fb.seek(0x17fe).unwrap();
+        assert_eq!(fb.write(b"abcd"), 2);
+        assert_eq!(&memory[0x17fe..0x1801], b"ab\0");
+
+        let base = memory.as_ptr() as u64;
+        let fb = FrameBufferDescriptor::new(base as usize, 0x1800);
+        assert_eq!(fb.physical_page(0x1001), Some((base + 0x1001) & !0xfff));
+        assert_eq!(fb.physical_page(0x1800), None);
+    }
+}
@@ -0,0 +1,96 @@
+//! The boot FAT volume as a filesystem of the VFS.
+
+use crate::error::{Code, Error};
+use crate::fat::global::{
+    boot_volume_image, boot_volume_image_mut, create_file, find_directory, find_entry,
+    make_directory, remove_directory, remove_file, rename, sync,
+};
+use crate::fat::{FatDirectoryDescriptor, FatFileDescriptor};
+use crate::io::{FileDescriptor, FileStat, S_IFDIR, S_IFREG};
+use crate::make_error;
+use crate::vfs::{DirectoryItem, FileSystem};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub(crate) struct FatFileSystem;
+
+impl FileSystem for FatFileSystem {
+    fn lookup(&self, path: &str) -> Result<FileStat, Error> {
+        if path.is_empty() {
+            return Ok(FileStat {
+                size: 0,
+                mode: S_IFDIR,
+            });
+        }
+        let entry = find_entry(&absolute(path))?.entry;
+        Ok(FileStat {
+            size: entry.file_size() as u64,
+            mode: if entry.is_directory() {
+                S_IFDIR
+            } else {
+                S_IFREG
+            },
+        })
+    }
+
+    fn open(&self, path: &str, create: bool, truncate: bool) -> Result<FileDescriptor, Error> {
+        if path.is_empty() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        let path = absolute(path);
+        let entry = match find_entry(&path) {
+            Ok(found) => {
+                if truncate {
+                    boot_volume_image_mut().truncate_entry(&found)?;
+                }
+                found.entry
+            }
+            Err(e) if matches!(e.code, Code::NoSuchEntry) && create => create_file(&path)?,
+            Err(e) => return Err(e),
+        };
+        Ok(FileDescriptor::Fat(FatFileDescriptor::new(entry)))
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirectoryItem>, Error> {
+        let dir_cluster = find_directory(&absolute(path))?;
+        let mut dir = FatDirectoryDescriptor::new(dir_cluster);
+        let mut items = Vec::new();
+        while let Some(found) = dir.next_entry(boot_volume_image()) {
+            let stat = FileStat {
+                size: found.entry.file_size() as u64,
+                mode: if found.entry.is_directory() {
+                    S_IFDIR
+                } else {
+                    S_IFREG
+                },
+            };
+            items.push(DirectoryItem::new(found.name, stat));
+        }
+        Ok(items)
+    }
+
+    fn make_directory(&self, path: &str) -> Result<(), Error> {
+        make_directory(&absolute(path)).map(|_| ())
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), Error> {
+        remove_file(&absolute(path))
+    }
+
+    fn remove_directory(&self, path: &str) -> Result<(), Error> {
+        remove_directory(&absolute(path))
+    }
+
+    fn rename(&self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        rename(&absolute(old_path), &absolute(new_path))
+    }
+
+    fn sync(&self) -> Result<(), Error> {
+        sync()
+    }
+}
+
+fn absolute(path: &str) -> String {
+    format!("/{}", path)
+}
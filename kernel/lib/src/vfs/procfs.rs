@@ -0,0 +1,296 @@
+//! Read-only pseudo files showing the state of the kernel, such as tasks and memory.
+//!
+//! The content of a file is generated when it is opened, so an open file does not change
+//! while it is read.
+
+use crate::error::{Code, Error};
+use crate::interrupt::{interrupt_count, HANDLED_VECTORS};
+use crate::io::{FileDescriptor, FileStat, S_IFDIR, S_IFREG};
+use crate::make_error;
+use crate::memory_manager::global::MEMORY_MANAGER;
+use crate::memory_manager::{MemoryStat, BYTES_PER_FRAME};
+use crate::pci::devices;
+use crate::task::global::task_manager;
+use crate::vfs::{DirectoryItem, FileSystem};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::arch::asm;
+use core::cmp;
+use core::fmt::Write;
+
+struct ProcFile {
+    name: &'static str,
+    generate: fn() -> String,
+}
+
+const FILES: [ProcFile; 4] = [
+    ProcFile {
+        name: "tasks",
+        generate: tasks,
+    },
+    ProcFile {
+        name: "meminfo",
+        generate: meminfo,
+    },
+    ProcFile {
+        name: "pci",
+        generate: pci,
+    },
+    ProcFile {
+        name: "interrupts",
+        generate: interrupts,
+    },
+];
+
+pub(crate) struct ProcFileSystem {
+    files: &'static [ProcFile],
+}
+
+impl Default for ProcFileSystem {
+    fn default() -> Self {
+        Self { files: &FILES }
+    }
+}
+
+impl ProcFileSystem {
+    fn find(&self, path: &str) -> Result<&ProcFile, Error> {
+        self.files
+            .iter()
+            .find(|f| f.name == path)
+            .ok_or_else(|| make_error!(Code::NoSuchEntry))
+    }
+}
+
+impl FileSystem for ProcFileSystem {
+    fn lookup(&self, path: &str) -> Result<FileStat, Error> {
+        if path.is_empty() {
+            return Ok(FileStat {
+                size: 0,
+                mode: S_IFDIR,
+            });
+        }
+        let file = self.find(path)?;
+        Ok(FileStat {
+            size: (file.generate)().len() as u64,
+            mode: S_IFREG,
+        })
+    }
+
+    fn open(&self, path: &str, create: bool, truncate: bool) -> Result<FileDescriptor, Error> {
+        if path.is_empty() {
+            return Err(make_error!(Code::IsDirectory));
+        }
+        match self.find(path) {
+            Ok(_) if truncate => Err(make_error!(Code::ReadOnly)),
+            Ok(file) => Ok(FileDescriptor::Proc(ProcFileDescriptor::new(
+                (file.generate)().into_bytes(),
+            ))),
+            Err(_) if create => Err(make_error!(Code::ReadOnly)),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirectoryItem>, Error> {
+        if !path.is_empty() {
+            self.find(path)?;
+            return Err(make_error!(Code::NotDirectory));
+        }
+        Ok(self
+            .files
+            .iter()
+            .map(|f| {
+                let stat = FileStat {
+                    size: 0,
+                    mode: S_IFREG,
+                };
+                DirectoryItem::new(f.name.to_string(), stat)
+            })
+            .collect())
+    }
+}
+
+/// An open file of procfs, which holds the content generated at `open`.
+pub(crate) struct ProcFileDescriptor {
+    data: Vec<u8>,
+    offset: usize,
+}
+
+impl ProcFileDescriptor {
+    fn new(data: Vec<u8>) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.load(buf, self.offset);
+        self.offset += n;
+        n
+    }
+
+    pub(crate) fn load(&self, buf: &mut [u8], offset: usize) -> usize {
+        let begin = cmp::min(offset, self.data.len());
+        let n = cmp::min(buf.len(), self.data.len() - begin);
+        buf[..n].copy_from_slice(&self.data[begin..begin + n]);
+        n
+    }
+
+    pub(crate) fn seek(&mut self, offset: usize) -> Result<usize, Error> {
+        if offset > self.data.len() {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+        self.offset = offset;
+        Ok(offset)
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.offset
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.data.len()
+    }
+}
+
+struct TaskRow {
+    id: u64,
+    level: usize,
+    is_running: bool,
+    num_files: usize,
+    cwd: String,
+}
+
+fn tasks() -> String {
+    unsafe { asm!("cli") };
+    let rows = task_manager()
+        .tasks()
+        .map(|task| TaskRow {
+            id: task.id().value(),
+            level: task.level().as_usize(),
+            is_running: task.is_running(),
+            num_files: task.num_files(),
+            cwd: task.cwd().to_string(),
+        })
+        .collect::<Vec<_>>();
+    unsafe { asm!("sti") };
+    format_tasks(&rows)
+}
+
+fn format_tasks(rows: &[TaskRow]) -> String {
+    let mut s = String::from("   ID LEVEL STATE    FILES CWD\n");
+    for row in rows {
+        let state = if row.is_running {
+            "running"
+        } else {
+            "sleeping"
+        };
+        writeln!(
+            s,
+            "{:>5} {:>5} {:<8} {:>5} {}",
+            row.id, row.level, state, row.num_files, row.cwd
+        )
+        .unwrap();
+    }
+    s
+}
+
+fn meminfo() -> String {
+    format_meminfo(&MEMORY_MANAGER.lock().stat())
+}
+
+fn format_meminfo(stat: &MemoryStat) -> String {
+    let kib = |frames: usize| frames * BYTES_PER_FRAME / 1024;
+    let free_frames = stat.total_frames - stat.allocated_frames;
+    let mut s = String::new();
+    writeln!(s, "MemTotal:   {:>10} kB", kib(stat.total_frames)).unwrap();
+    writeln!(s, "MemUsed:    {:>10} kB", kib(stat.allocated_frames)).unwrap();
+    writeln!(s, "MemFree:    {:>10} kB", kib(free_frames)).unwrap();
+    writeln!(s, "FrameSize:  {:>10} B", BYTES_PER_FRAME).unwrap();
+    writeln!(s, "FramesTotal:{:>10}", stat.total_frames).unwrap();
+    writeln!(s, "FramesUsed: {:>10}", stat.allocated_frames).unwrap();
+    s
+}
+
+fn pci() -> String {
+    let mut s = String::new();
+    for device in devices() {
+        writeln!(s, "{}", device).unwrap();
+    }
+    s
+}
+
+fn interrupts() -> String {
+    format_interrupts(
+        HANDLED_VECTORS
+            .iter()
+            .map(|&(vector, name)| (vector, interrupt_count(vector), name)),
+    )
+}
+
+fn format_interrupts<'a>(counts: impl Iterator<Item = (u8, u64, &'a str)>) -> String {
+    let mut s = String::new();
+    for (vector, count, name) in counts {
+        writeln!(s, "{:>3}: {:>10}  {}", vector, count, name).unwrap();
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hello() -> String {
+        "hello\n".to_string()
+    }
+
+    const TEST_FILES: [ProcFile; 1] = [ProcFile {
+        name: "hello",
+        generate: hello,
+    }];
+
+    #[test]
+    fn files_are_read_only_snapshots() {
+        let fs = ProcFileSystem { files: &TEST_FILES };
+        assert_eq!(fs.lookup("").unwrap().mode, S_IFDIR);
+        assert_eq!(fs.lookup("hello").unwrap().size, 6);
+        assert!(fs.lookup("none").is_err());
+        assert_eq!(fs.read_dir("").unwrap()[0].name, "hello");
+        assert!(matches!(fs.open("new", true, false), Err(e) if matches!(e.code, Code::ReadOnly)));
+        assert!(
+            matches!(fs.open("hello", false, true), Err(e) if matches!(e.code, Code::ReadOnly))
+        );
+        assert!(matches!(
+            fs.make_directory("dir").unwrap_err().code,
+            Code::ReadOnly
+        ));
+
+        let mut fd = match fs.open("hello", false, false).unwrap() {
+            FileDescriptor::Proc(fd) => fd,
+            _ => unreachable!(),
+        };
+        let mut buf = [0; 4];
+        assert_eq!(fd.read(&mut buf), 4);
+        assert_eq!(fd.read(&mut buf), 2);
+        assert_eq!(&buf[..2], b"o\n");
+        assert_eq!(fd.read(&mut buf), 0);
+    }
+
+    #[test]
+    fn format_meminfo_in_kib() {
+        let stat = MemoryStat {
+            allocated_frames: 256,
+            total_frames: 1024,
+        };
+        let s = format_meminfo(&stat);
+        assert!(s.starts_with("MemTotal:         4096 kB\nMemUsed:          1024 kB\n"));
+        assert!(s.contains("MemFree:          3072 kB\n"));
+    }
+
+    #[test]
+    fn format_interrupts_per_vector() {
+        let s =
+            format_interrupts([(14, 3, "#PF page fault"), (0x41, 120, "LAPIC timer")].into_iter());
+        assert_eq!(
+            s,
+            " 14:          3  #PF page fault\n 65:        120  LAPIC timer\n"
+        );
+    }
+}
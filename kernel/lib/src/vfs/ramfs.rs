@@ -0,0 +1,322 @@
+//! A filesystem in memory, whose contents are lost on reboot.
+
+use crate::error::{Code, Error};
+use crate::io::{FileDescriptor, FileStat, S_IFDIR, S_IFREG};
+use crate::make_error;
+use crate::sync::Mutex;
+use crate::vfs::{DirectoryItem, FileSystem};
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp;
+
+enum Node {
+    File(Arc<Mutex<Vec<u8>>>),
+    Directory,
+}
+
+/// Nodes are keyed by their paths, and the root directory is "".
+pub(crate) struct RamFileSystem {
+    nodes: Mutex<BTreeMap<String, Node>>,
+}
+
+impl Default for RamFileSystem {
+    fn default() -> Self {
+        let mut nodes = BTreeMap::new();
+        nodes.insert(String::new(), Node::Directory);
+        Self {
+            nodes: Mutex::new(nodes),
+        }
+    }
+}
+
+impl FileSystem for RamFileSystem {
+    fn lookup(&self, path: &str) -> Result<FileStat, Error> {
+        match self.nodes.lock().get(path) {
+            Some(Node::File(data)) => Ok(FileStat {
+                size: data.lock().len() as u64,
+                mode: S_IFREG,
+            }),
+            Some(Node::Directory) => Ok(FileStat {
+                size: 0,
+                mode: S_IFDIR,
+            }),
+            None => Err(make_error!(Code::NoSuchEntry)),
+        }
+    }
+
+    fn open(&self, path: &str, create: bool, truncate: bool) -> Result<FileDescriptor, Error> {
+        let mut nodes = self.nodes.lock();
+        let data = match nodes.get(path) {
+            Some(Node::File(data)) => {
+                if truncate {
+                    data.lock().clear();
+                }
+                Arc::clone(data)
+            }
+            Some(Node::Directory) => return Err(make_error!(Code::IsDirectory)),
+            None if create => {
+                check_parent(&nodes, path)?;
+                let data = Arc::new(Mutex::new(Vec::new()));
+                nodes.insert(path.to_string(), Node::File(Arc::clone(&data)));
+                data
+            }
+            None => return Err(make_error!(Code::NoSuchEntry)),
+        };
+        Ok(FileDescriptor::Ram(RamFileDescriptor::new(data)))
+    }
+
+    fn read_dir(&self, path: &str) -> Result<Vec<DirectoryItem>, Error> {
+        let nodes = self.nodes.lock();
+        match nodes.get(path) {
+            Some(Node::Directory) => {}
+            Some(Node::File(_)) => return Err(make_error!(Code::NotDirectory)),
+            None => return Err(make_error!(Code::NoSuchEntry)),
+        }
+
+        let items = nodes
+            .iter()
+            .filter(|(child, _)| !child.is_empty() && parent(child) == path)
+            .map(|(child, node)| {
+                let stat = match node {
+                    Node::File(data) => FileStat {
+                        size: data.lock().len() as u64,
+                        mode: S_IFREG,
+                    },
+                    Node::Directory => FileStat {
+                        size: 0,
+                        mode: S_IFDIR,
+                    },
+                };
+                DirectoryItem::new(name(child).to_string(), stat)
+            })
+            .collect();
+        Ok(items)
+    }
+
+    fn make_directory(&self, path: &str) -> Result<(), Error> {
+        let mut nodes = self.nodes.lock();
+        if nodes.contains_key(path) {
+            return Err(make_error!(Code::FileExists));
+        }
+        check_parent(&nodes, path)?;
+        nodes.insert(path.to_string(), Node::Directory);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &str) -> Result<(), Error> {
+        let mut nodes = self.nodes.lock();
+        match nodes.get(path) {
+            Some(Node::File(_)) => {
+                nodes.remove(path);
+                Ok(())
+            }
+            Some(Node::Directory) => Err(make_error!(Code::IsDirectory)),
+            None => Err(make_error!(Code::NoSuchEntry)),
+        }
+    }
+
+    fn remove_directory(&self, path: &str) -> Result<(), Error> {
+        let mut nodes = self.nodes.lock();
+        match nodes.get(path) {
+            Some(Node::Directory) => {}
+            Some(Node::File(_)) => return Err(make_error!(Code::NotDirectory)),
+            None => return Err(make_error!(Code::NoSuchEntry)),
+        }
+        if nodes.keys().any(|child| is_descendant(child, path)) {
+            return Err(make_error!(Code::DirectoryNotEmpty));
+        }
+        nodes.remove(path);
+        Ok(())
+    }
+
+    fn rename(&self, old_path: &str, new_path: &str) -> Result<(), Error> {
+        let mut nodes = self.nodes.lock();
+        let is_directory = match nodes.get(old_path) {
+            Some(node) => matches!(node, Node::Directory),
+            None => return Err(make_error!(Code::NoSuchEntry)),
+        };
+        if old_path == new_path {
+            return Ok(());
+        }
+        if is_directory && is_descendant(new_path, old_path) {
+            // a directory cannot be moved into itself
+            return Err(make_error!(Code::InvalidFile));
+        }
+        match nodes.get(new_path) {
+            Some(Node::Directory) => return Err(make_error!(Code::FileExists)),
+            Some(Node::File(_)) if is_directory => return Err(make_error!(Code::FileExists)),
+            _ => {}
+        }
+        check_parent(&nodes, new_path)?;
+
+        let moved = nodes
+            .keys()
+            .filter(|key| *key == old_path || is_descendant(key, old_path))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in moved {
+            let node = nodes.remove(&key).unwrap();
+            nodes.insert(format!("{}{}", new_path, &key[old_path.len()..]), node);
+        }
+        Ok(())
+    }
+}
+
+/// An open file of a ramfs. Its data outlives the file removed while it is open.
+pub(crate) struct RamFileDescriptor {
+    data: Arc<Mutex<Vec<u8>>>,
+    offset: usize,
+}
+
+impl RamFileDescriptor {
+    pub(crate) fn new(data: Arc<Mutex<Vec<u8>>>) -> Self {
+        Self { data, offset: 0 }
+    }
+
+    pub(crate) fn read(&mut self, buf: &mut [u8]) -> usize {
+        let n = self.load(buf, self.offset);
+        self.offset += n;
+        n
+    }
+
+    pub(crate) fn write(&mut self, buf: &[u8]) -> usize {
+        let mut data = self.data.lock();
+        let end = self.offset + buf.len();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[self.offset..end].copy_from_slice(buf);
+        self.offset = end;
+        buf.len()
+    }
+
+    pub(crate) fn load(&self, buf: &mut [u8], offset: usize) -> usize {
+        let data = self.data.lock();
+        let begin = cmp::min(offset, data.len());
+        let n = cmp::min(buf.len(), data.len() - begin);
+        buf[..n].copy_from_slice(&data[begin..begin + n]);
+        n
+    }
+
+    pub(crate) fn seek(&mut self, offset: usize) -> Result<usize, Error> {
+        if offset > self.size() {
+            return Err(make_error!(Code::IndexOutOfRange));
+        }
+        self.offset = offset;
+        Ok(offset)
+    }
+
+    pub(crate) fn position(&self) -> usize {
+        self.offset
+    }
+
+    pub(crate) fn size(&self) -> usize {
+        self.data.lock().len()
+    }
+}
+
+/// The directory containing `path`; "" for the entries in the root.
+fn parent(path: &str) -> &str {
+    path.rfind('/').map_or("", |slash| &path[..slash])
+}
+
+fn name(path: &str) -> &str {
+    path.rfind('/').map_or(path, |slash| &path[slash + 1..])
+}
+
+fn is_descendant(path: &str, dir: &str) -> bool {
+    if dir.is_empty() {
+        return !path.is_empty();
+    }
+    path.strip_prefix(dir)
+        .map_or(false, |rest| rest.starts_with('/'))
+}
+
+fn check_parent(nodes: &BTreeMap<String, Node>, path: &str) -> Result<(), Error> {
+    match nodes.get(parent(path)) {
+        Some(Node::Directory) => Ok(()),
+        Some(Node::File(_)) => Err(make_error!(Code::NotDirectory)),
+        None => Err(make_error!(Code::NoSuchEntry)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read_file() {
+        let fs = RamFileSystem::default();
+        assert!(fs.open("a.txt", false, false).is_err());
+        let mut fd = match fs.open("a.txt", true, false).unwrap() {
+            FileDescriptor::Ram(fd) => fd,
+            _ => unreachable!(),
+        };
+        assert_eq!(fd.write(b"hello"), 5);
+        fd.seek(1).unwrap();
+        assert_eq!(fd.write(b"EL"), 2);
+        assert_eq!(fs.lookup("a.txt").unwrap().size, 5);
+
+        let mut fd = RamFileDescriptor::new(match fs.nodes.lock().get("a.txt") {
+            Some(Node::File(data)) => Arc::clone(data),
+            _ => unreachable!(),
+        });
+        let mut buf = [0; 8];
+        assert_eq!(fd.read(&mut buf), 5);
+        assert_eq!(&buf[..5], b"hELlo");
+        assert_eq!(fd.read(&mut buf), 0);
+
+        fs.open("a.txt", false, true).unwrap();
+        assert_eq!(fs.lookup("a.txt").unwrap().size, 0);
+    }
+
+    #[test]
+    fn directories() {
+        let fs = RamFileSystem::default();
+        fs.make_directory("d").unwrap();
+        fs.open("d/f", true, false).unwrap();
+        assert!(fs.open("none/f", true, false).is_err());
+        assert!(fs.make_directory("d").is_err());
+
+        let names = |path| {
+            fs.read_dir(path)
+                .unwrap()
+                .into_iter()
+                .map(|item| item.name)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(""), ["d"]);
+        assert_eq!(names("d"), ["f"]);
+        assert_eq!(fs.lookup("d").unwrap().mode, S_IFDIR);
+
+        assert!(matches!(
+            fs.remove_directory("d").unwrap_err().code,
+            Code::DirectoryNotEmpty
+        ));
+        fs.remove_file("d/f").unwrap();
+        fs.remove_directory("d").unwrap();
+        assert!(names("").is_empty());
+    }
+
+    #[test]
+    fn rename_moves_descendants() {
+        let fs = RamFileSystem::default();
+        fs.make_directory("a").unwrap();
+        fs.make_directory("a/b").unwrap();
+        fs.open("a/b/f", true, false).unwrap();
+        fs.make_directory("c").unwrap();
+
+        assert!(fs.rename("a", "a/b/a").is_err());
+        fs.rename("a", "c/a").unwrap();
+        assert!(fs.lookup("a").is_err());
+        assert_eq!(fs.lookup("c/a/b/f").unwrap().mode, S_IFREG);
+
+        fs.open("g", true, false).unwrap();
+        assert!(fs.rename("g", "c").is_err());
+        fs.rename("g", "c/a/b/f").unwrap();
+        assert!(fs.lookup("g").is_err());
+    }
+}
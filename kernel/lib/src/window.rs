@@ -7,6 +7,7 @@ use crate::graphics::{
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
 use core::cmp::{max, min};
+use core::mem;
 use shared::{FrameBufferConfig, PixelFormat};
 
 pub const TITLED_WINDOW_TOP_LEFT_MARGIN: Vector2D<i32> = Vector2D::new(4, 24);
@@ -15,7 +16,9 @@ pub const TITLED_WINDOW_BOTTOM_RIGHT_MARGIN: Vector2D<i32> = Vector2D::new(4, 4)
 pub(crate) enum WindowRegion {
     TitleBar,
     CloseButton,
-    Border,
+    /// the edges the position is on, which are -1 for the left or the top edge and 1 for the
+    /// right or the bottom edge
+    Border(Vector2D<i32>),
     Other,
 }
 
@@ -84,6 +87,22 @@ impl Window {
         Vector2D::new(self.width, self.height)
     }
 
+    /// Changes the size of the window, whose contents are cleared and the frame is drawn again.
+    pub fn resize(&mut self, width: usize, height: usize, is_active: bool) {
+        let shadow_format = self.shadow_buffer.config().pixel_format;
+        let type_ = mem::replace(&mut self.type_, Type::Normal);
+        let transparent_color = self.transparent_color;
+        *self = Window::_new(width, height, shadow_format, type_);
+        self.transparent_color = transparent_color;
+        if let Type::TopLevel { title } = &self.type_ {
+            let title = title.to_string();
+            draw_window(&mut self.normal_window_writer(), title.as_str());
+            if is_active {
+                self.activate();
+            }
+        }
+    }
+
     pub fn draw_to(&mut self, dst: &mut FrameBuffer, pos: Vector2D<i32>, area: Rectangle<i32>) {
         match self.transparent_color {
             None => {
@@ -168,7 +187,16 @@ impl Window {
         let close_button_width = CLOSE_BUTTON[0].len() as i32;
 
         if pos.x < 2 || width - 2 <= pos.x || pos.y < 2 || height - 2 <= pos.y {
-            WindowRegion::Border
+            let edge = |p: i32, len: i32| {
+                if p < 2 {
+                    -1
+                } else if len - 2 <= p {
+                    1
+                } else {
+                    0
+                }
+            };
+            WindowRegion::Border(Vector2D::new(edge(pos.x, width), edge(pos.y, height)))
         } else if pos.y < TITLED_WINDOW_TOP_LEFT_MARGIN.y {
             if width - 5 - close_button_width <= pos.x
                 && pos.x < width - 5
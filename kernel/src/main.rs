@@ -18,7 +18,7 @@ use lib::font::{write_ascii, write_string};
 use lib::graphics::global::{frame_buffer_config, screen_size};
 use lib::graphics::{fill_rectangle, PixelColor, Rectangle, Vector2D, COLOR_BLACK, COLOR_WHITE};
 use lib::interrupt::global::initialize_interrupt;
-use lib::keyboard::KEY_F2;
+use lib::keyboard::{is_control_key_inputted, KEY_C, KEY_F2};
 use lib::layer::global::layer_manager;
 use lib::layer::LayerID;
 use lib::message::{Message, MessageType};
@@ -31,8 +31,8 @@ use lib::timer::global::{current_tick, do_with_timer_manager};
 use lib::timer::{Timer, TIMER_FREQ};
 use lib::window::Window;
 use lib::{
-    acpi, console, fat, font, graphics, keyboard, layer, memory_manager, mouse, paging, pci,
-    segment, syscall, task, timer,
+    acpi, block, console, fat, font, graphics, keyboard, layer, memory_manager, mouse, paging, pci,
+    segment, syscall, task, timer, vfs,
 };
 use memory_allocator::MemoryAllocator;
 use shared::{FrameBufferConfig, MemoryMap};
@@ -79,9 +79,11 @@ pub extern "C" fn KernelMainNewStack(
     segment::global::initialize_tss();
     initialize_interrupt();
 
+    pci::initialize();
     fat::global::initialize(volume_image);
+    initialize_block_device();
+    vfs::global::initialize();
     font::initialize();
-    pci::initialize();
     usb::register_mouse_observer(mouse_observer);
 
     layer::global::initialize();
@@ -195,7 +197,14 @@ pub extern "C" fn KernelMainNewStack(
                     unsafe { asm!("sti") };
                     if let Some(task_id) = task_id {
                         unsafe { asm!("cli") };
-                        let _ = task_manager().send_message(task_id, message);
+                        let _ = if arg.press
+                            && arg.keycode == KEY_C
+                            && is_control_key_inputted(arg.modifier)
+                        {
+                            task_manager().interrupt(task_id)
+                        } else {
+                            task_manager().send_message(task_id, message)
+                        };
                         unsafe { asm!("sti") };
                     } else {
                         printk!(
@@ -233,11 +242,12 @@ fn alloc_error_handle(layout: alloc::alloc::Layout) -> ! {
     panic!("allocation error: {:?}", layout)
 }
 
-extern "C" fn mouse_observer(buttons: u8, displacement_x: i8, displacement_y: i8) {
+extern "C" fn mouse_observer(buttons: u8, displacement_x: i8, displacement_y: i8, wheel: i8) {
     MOUSE.lock().as_mut().unwrap().on_interrupt(
         buttons,
         displacement_x,
         displacement_y,
+        wheel,
         screen_size().to_i32_vec2d(),
         task_manager(),
     );
@@ -247,6 +257,22 @@ extern "C" fn keyboard_observer(modifier: u8, keycode: u8, press: bool) {
     keyboard::on_input(modifier, keycode, press, task_manager());
 }
 
+/// Switches the volume to the one on a block device, if any, so that modified files persist.
+fn initialize_block_device() {
+    let device = match block::find_device() {
+        None => return,
+        Some(Ok(device)) => device,
+        Some(Err(e)) => {
+            printk!("failed to initialize the block device: {}\n", e);
+            return;
+        }
+    };
+    match fat::global::attach_block_device(device) {
+        Ok(_) => printk!("the volume is on the block device\n"),
+        Err(e) => printk!("failed to attach the block device: {}\n", e),
+    }
+}
+
 fn initialize_main_window() {
     let main_window = MAIN_WINDOW.call_once(|| {
         let w = Window::new_with_title(160, 52, frame_buffer_config().pixel_format, "Hello Window");
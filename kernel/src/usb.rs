@@ -13,7 +13,7 @@ extern "C" {
 
     /// ref: https://doc.rust-lang.org/nomicon/ffi.html#targeting-callbacks-to-rust-objects
     fn RegisterMouseObserver(
-        cb: extern "C" fn(buttons: u8, displacement_x: i8, displacement_y: i8),
+        cb: extern "C" fn(buttons: u8, displacement_x: i8, displacement_y: i8, wheel: i8),
     );
 
     fn RegisterKeyboardObserver(cb: extern "C" fn(modifier: u8, keycode: u8, press: bool));
@@ -72,7 +72,7 @@ pub mod global {
 }
 
 pub fn register_mouse_observer(
-    cb: extern "C" fn(buttons: u8, displacement_x: i8, displacement_y: i8),
+    cb: extern "C" fn(buttons: u8, displacement_x: i8, displacement_y: i8, wheel: i8),
 ) {
     unsafe { RegisterMouseObserver(cb) };
 }
@@ -0,0 +1,60 @@
+#include "usb/classdriver/mouse.hpp"
+
+#include <algorithm>
+#include "usb/memory.hpp"
+#include "usb/device.hpp"
+
+namespace {
+  const uint16_t kBootProtocol = 0;
+  const uint16_t kReportProtocol = 1;
+}
+
+namespace usb {
+  // buttons, X, Y and the wheel, as the report protocol of a mouse without report IDs has.
+  // A mouse whose report has no wheel leaves the last byte 0.
+  HIDMouseDriver::HIDMouseDriver(Device* dev, int interface_index)
+      : HIDBaseDriver{dev, interface_index, 4} {
+  }
+
+  // HIDBaseDriver puts the mouse in the boot protocol, whose report has no wheel,
+  // so it is switched back to the report protocol before the reports are read.
+  Error HIDMouseDriver::OnControlCompleted(EndpointID ep_id, SetupData setup_data,
+                                           const void* buf, int len) {
+    if (setup_data.request == request::kSetProtocol &&
+        setup_data.value == kBootProtocol) {
+      setup_data.value = kReportProtocol;
+      return ParentDevice()->ControlOut(ep_id, setup_data, nullptr, 0, this);
+    }
+    return HIDBaseDriver::OnControlCompleted(ep_id, setup_data, buf, len);
+  }
+
+  Error HIDMouseDriver::OnDataReceived() {
+    uint8_t buttons = Buffer()[0];
+    int8_t displacement_x = Buffer()[1];
+    int8_t displacement_y = Buffer()[2];
+    int8_t wheel = Buffer()[3];
+    NotifyMouseMove(buttons, displacement_x, displacement_y, wheel);
+    return MAKE_ERROR(Error::kSuccess);
+  }
+
+  void* HIDMouseDriver::operator new(size_t size) {
+    return AllocMem(sizeof(HIDMouseDriver), 0, 0);
+  }
+
+  void HIDMouseDriver::operator delete(void* ptr) noexcept {
+    FreeMem(ptr);
+  }
+
+  void HIDMouseDriver::SubscribeMouseMove(std::function<ObserverType> observer) {
+    observers_[num_observers_++] = observer;
+  }
+
+  std::function<HIDMouseDriver::ObserverType> HIDMouseDriver::default_observer;
+
+  void HIDMouseDriver::NotifyMouseMove(uint8_t buttons, int8_t displacement_x,
+                                       int8_t displacement_y, int8_t wheel) {
+    for (int i = 0; i < num_observers_; ++i) {
+      observers_[i](buttons, displacement_x, displacement_y, wheel);
+    }
+  }
+}
@@ -0,0 +1,36 @@
+/**
+ * @file usb/classdriver/mouse.hpp
+ *
+ * HID mouse class driver, which replaces the upstream one to report the wheel.
+ */
+
+#pragma once
+
+#include <functional>
+#include "usb/classdriver/hid.hpp"
+
+namespace usb {
+  class HIDMouseDriver : public HIDBaseDriver {
+   public:
+    HIDMouseDriver(Device* dev, int interface_index);
+
+    void* operator new(size_t size);
+    void operator delete(void* ptr) noexcept;
+
+    Error OnControlCompleted(EndpointID ep_id, SetupData setup_data,
+                             const void* buf, int len) override;
+    Error OnDataReceived() override;
+
+    using ObserverType = void (uint8_t buttons, int8_t displacement_x, int8_t displacement_y,
+                               int8_t wheel);
+    void SubscribeMouseMove(std::function<ObserverType> observer);
+    static std::function<ObserverType> default_observer;
+
+   private:
+    std::array<std::function<ObserverType>, 4> observers_;
+    int num_observers_ = 0;
+
+    void NotifyMouseMove(uint8_t buttons, int8_t displacement_x, int8_t displacement_y,
+                         int8_t wheel);
+  };
+}
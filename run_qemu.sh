@@ -44,6 +44,14 @@ build_and_run() {
   cd $script_dir
   make -C apps/onlyhlt/ onlyhlt
 
+  if [ -n "$disk" ]; then
+    # the kernel copies the boot volume to a blank disk, and uses the volume on it from then on
+    if [ ! -e "$disk" ]; then
+      qemu-img create -f raw "$disk" 200M
+    fi
+    export QEMU_OPTS="${QEMU_OPTS-} -drive if=none,id=persistent,format=raw,file=$disk -device virtio-blk-pci,drive=persistent"
+  fi
+
   export APPS_DIR=apps
   export RESOURCE_DIR=resource
   MIKANOS_DIR=$PWD $HOME/osbook/devenv/run_mikanos.sh
@@ -72,6 +80,7 @@ parse_params() {
   kernel=0
   apps=()
   official=0
+  disk=""
 
   while :; do
     case "${1-}" in
@@ -85,6 +94,7 @@ parse_params() {
       fi
     ;;
     -o | --official) official=1 ;;
+    --disk=*) disk=$(echo $1 | sed -e 's/^--disk=//') ;;
     -?*) die "Unknown option: $1" ;;
     *) break ;;
     esac